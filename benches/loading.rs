@@ -3,7 +3,7 @@ extern crate criterion;
 extern crate syntect;
 
 use criterion::{Bencher, Criterion};
-use syntect::parsing::{SyntaxSet, SyntaxSetBuilder};
+use syntect::parsing::{ParseState, SyntaxSet, SyntaxSetBuilder};
 use syntect::highlighting::ThemeSet;
 
 
@@ -13,6 +13,29 @@ fn bench_load_internal_dump(b: &mut Bencher) {
     });
 }
 
+fn bench_load_lazy_internal_dump(b: &mut Bencher) {
+    b.iter(|| {
+        SyntaxSet::load_defaults_newlines_lazy()
+    });
+}
+
+fn parse_rust_line(ss: &SyntaxSet) {
+    let syntax = ss.find_syntax_by_extension("rs").unwrap();
+    ParseState::new(syntax).parse_line("fn main() {}\n", ss);
+}
+
+fn bench_load_and_parse_line(b: &mut Bencher) {
+    b.iter(|| {
+        parse_rust_line(&SyntaxSet::load_defaults_newlines())
+    });
+}
+
+fn bench_lazy_load_and_parse_line(b: &mut Bencher) {
+    b.iter(|| {
+        parse_rust_line(&SyntaxSet::load_defaults_newlines_lazy())
+    });
+}
+
 fn bench_load_internal_themes(b: &mut Bencher) {
     b.iter(|| {
         ThemeSet::load_defaults()
@@ -42,6 +65,9 @@ fn bench_link_syntaxes(b: &mut Bencher) {
 
 fn loading_benchmark(c: &mut Criterion) {
     c.bench_function("load_internal_dump", bench_load_internal_dump);
+    c.bench_function("load_lazy_internal_dump", bench_load_lazy_internal_dump);
+    c.bench_function("load_and_parse_line", bench_load_and_parse_line);
+    c.bench_function("lazy_load_and_parse_line", bench_lazy_load_and_parse_line);
     c.bench_function("load_internal_themes", bench_load_internal_themes);
     c.bench_function("load_theme", bench_load_theme);
     c.bench_function("add_from_folder", bench_add_from_folder);
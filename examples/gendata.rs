@@ -6,15 +6,18 @@
 //! An example of how this script is used to generate the pack files included
 //! with syntect can be found under `make packs` in the Makefile.
 extern crate syntect;
-use syntect::parsing::SyntaxSetBuilder;
+use syntect::parsing::{SyntaxSet, SyntaxSetBuilder};
 use syntect::highlighting::ThemeSet;
 use syntect::dumps::*;
 use std::env;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
 
 fn usage_and_exit() -> ! {
     println!("USAGE: gendata synpack source-dir \
               newlines.packdump nonewlines.packdump \
-              [metadata.packdump] [metadata extra-source-dir]\n       \
+              [metadata extra-source-dir]\n       \
+              gendata lazypack syntaxes.packdump lazy.packdump\n       \
               gendata themepack source-dir themepack.themedump");
     ::std::process::exit(2);
 }
@@ -22,19 +25,27 @@ fn usage_and_exit() -> ! {
 fn main() {
 
     let mut a = env::args().skip(1);
-    match (a.next(), a.next(), a.next(), a.next(), a.next(), a.next()) {
+    match (a.next(), a.next(), a.next(), a.next(), a.next()) {
         (Some(ref cmd),
          Some(ref package_dir),
          Some(ref packpath_newlines),
          Some(ref packpath_nonewlines),
-         ref _option_metapath,
          ref _option_metasource,
          ) if cmd == "synpack" => {
             let mut builder = SyntaxSetBuilder::new();
             builder.add_plain_text_syntax();
             builder.add_from_folder(package_dir, true).unwrap();
+
+            #[cfg(feature = "metadata")]
+            {
+                if let Some(metasource) = _option_metasource {
+                    builder.add_from_folder(metasource, true).unwrap();
+                }
+            }
+
             let ss = builder.build();
-            dump_to_file(&ss, packpath_newlines).unwrap();
+            let info = DumpInfo { lines_include_newline: Some(true) };
+            dump_to_file_with_info(&ss, info, packpath_newlines).unwrap();
 
             let mut builder_nonewlines = SyntaxSetBuilder::new();
             builder_nonewlines.add_plain_text_syntax();
@@ -48,15 +59,12 @@ fn main() {
             }
 
             let ss_nonewlines = builder_nonewlines.build();
-            dump_to_file(&ss_nonewlines, packpath_nonewlines).unwrap();
-
-            #[cfg(feature = "metadata")]
-            {
-                if let Some(metapath) = _option_metapath {
-                    dump_to_file(&ss_nonewlines.metadata(), metapath).unwrap();
-                }
-            }
-
+            let info = DumpInfo { lines_include_newline: Some(false) };
+            dump_to_file_with_info(&ss_nonewlines, info, packpath_nonewlines).unwrap();
+        }
+        (Some(ref s), Some(ref packpath), Some(ref lazypath), ..) if s == "lazypack" => {
+            let (ss, info): (SyntaxSet, DumpInfo) = from_reader_with_info(BufReader::new(File::open(packpath).unwrap())).unwrap();
+            dump_lazy_syntax_set(&ss, info, BufWriter::new(File::create(lazypath).unwrap())).unwrap();
         }
         (Some(ref s), Some(ref theme_dir), Some(ref packpath), ..) if s == "themepack" => {
             let ts = ThemeSet::load_from_folder(theme_dir).unwrap();
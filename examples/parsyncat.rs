@@ -1,20 +1,32 @@
 //! Highlights the files given on the command line, in parallel.
 //! Prints the highlighted output to stdout.
+//!
+//! If only one file is given, it is split into chunks which are highlighted in
+//! parallel instead, see `highlight_chunked`. Pass `--check` to also highlight it
+//! sequentially and make sure the results are the same.
 
 extern crate rayon;
 extern crate syntect;
 
-use syntect::parsing::SyntaxSet;
-use syntect::highlighting::{ThemeSet, Style};
+use syntect::parsing::{ParseState, ScopeStack, ScopeStackOp, SyntaxReference, SyntaxSet};
+use syntect::highlighting::{HighlightIterator, HighlightState, Highlighter, Style, Theme, ThemeSet};
 use syntect::easy::HighlightFile;
 use rayon::prelude::*;
 
 use std::fs::File;
 use std::io::{BufReader, BufRead};
+use std::iter;
 
+/// Roughly how many lines a chunk of a single file should have
+const CHUNK_LINES: usize = 1000;
+
+/// The operations for each line of a chunk, and the state after its last line
+type ParsedChunk = (Vec<Vec<(usize, ScopeStackOp)>>, ParseState);
 
 fn main() {
-    let files: Vec<String> = std::env::args().skip(1).collect();
+    let mut files: Vec<String> = std::env::args().skip(1).collect();
+    let check = files.iter().any(|arg| arg == "--check");
+    files.retain(|arg| arg != "--check");
 
     if files.is_empty() {
         println!("Please provide some files to highlight.");
@@ -23,21 +35,32 @@ fn main() {
 
     let syntax_set = SyntaxSet::load_defaults_newlines();
     let theme_set = ThemeSet::load_defaults();
+    let theme = &theme_set.themes["base16-ocean.dark"];
+
+    if files.len() == 1 {
+        let filename = &files[0];
+        let lines = read_lines(filename);
+        let syntax = syntax_set.find_syntax_for_file(filename)
+            .unwrap()
+            .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+        let regions = highlight_chunked(&lines, syntax, &syntax_set, theme);
+
+        if check {
+            let mut highlighter = HighlightFile::new(filename, &syntax_set, theme).unwrap();
+            let mut expected = Vec::new();
+            for line in &lines {
+                expected.extend(highlighter.highlight_lines.highlight(line, &syntax_set));
+            }
+            assert!(regions == expected, "chunked highlighting differs from sequential highlighting");
+        }
+
+        print!("{}", syntect::util::as_24_bit_terminal_escaped(&regions[..], true));
+        return;
+    }
 
     // We first collect the contents of the files...
     let contents: Vec<Vec<String>> = files.par_iter()
-        .map(|filename| {
-            let mut lines = Vec::new();
-            // We use `String::new()` and `read_line()` instead of `BufRead::lines()`
-            // in order to preserve the newlines and get better highlighting.
-            let mut line = String::new();
-            let mut reader = BufReader::new(File::open(filename).unwrap());
-            while reader.read_line(&mut line).unwrap() > 0 {
-                lines.push(line);
-                line = String::new();
-            }
-            lines
-        })
+        .map(|filename| read_lines(filename))
         .collect();
 
     // ...so that the highlighted regions have valid lifetimes...
@@ -45,7 +68,6 @@ fn main() {
         .zip(&contents)
         .map(|(filename, contents)| {
             let mut regions = Vec::new();
-            let theme = &theme_set.themes["base16-ocean.dark"];
             let mut highlighter = HighlightFile::new(filename, &syntax_set, theme).unwrap();
 
             for line in contents {
@@ -63,3 +85,103 @@ fn main() {
         print!("{}", syntect::util::as_24_bit_terminal_escaped(&file_regions[..], true));
     }
 }
+
+fn read_lines(filename: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    // We use `String::new()` and `read_line()` instead of `BufRead::lines()`
+    // in order to preserve the newlines and get better highlighting.
+    let mut line = String::new();
+    let mut reader = BufReader::new(File::open(filename).unwrap());
+    while reader.read_line(&mut line).unwrap() > 0 {
+        lines.push(line);
+        line = String::new();
+    }
+    lines
+}
+
+/// Highlights a single file using all cores, with exactly the same result as
+/// highlighting it line by line.
+///
+/// Chunks of the file are parsed in parallel, guessing that each chunk starts in a
+/// clean parse state (see `ParseState::is_clean`). The guesses are then checked in
+/// order and chunks where the guess was wrong are parsed again, this time sequentially.
+/// Finally the chunks are highlighted in parallel, starting from the scope stack
+/// the previous chunks left behind.
+fn highlight_chunked<'a>(lines: &'a [String],
+                         syntax: &SyntaxReference,
+                         syntax_set: &SyntaxSet,
+                         theme: &Theme)
+                         -> Vec<(Style, &'a str)> {
+    let starts = guess_clean_lines(lines);
+    let ranges: Vec<(usize, usize)> = starts.iter()
+        .cloned()
+        .zip(starts.iter().cloned().skip(1).chain(iter::once(lines.len())))
+        .collect();
+
+    let mut chunks: Vec<ParsedChunk> = ranges.par_iter()
+        .map(|&(start, end)| {
+            let state = if start == 0 {
+                ParseState::new(syntax)
+            } else {
+                ParseState::new_clean(syntax)
+            };
+            parse_chunk(state, &lines[start..end], syntax_set)
+        })
+        .collect();
+
+    for i in 1..chunks.len() {
+        if !chunks[i - 1].1.is_clean(syntax) {
+            let state = chunks[i - 1].1.clone();
+            let (start, end) = ranges[i];
+            chunks[i] = parse_chunk(state, &lines[start..end], syntax_set);
+        }
+    }
+
+    // Applying the operations is cheap compared to parsing or highlighting
+    let mut stack = ScopeStack::new();
+    let mut start_stacks = Vec::with_capacity(chunks.len());
+    for (ops, _) in &chunks {
+        start_stacks.push(stack.clone());
+        for (_, op) in ops.iter().flatten() {
+            stack.apply(op);
+        }
+    }
+
+    let highlighter = Highlighter::new(theme);
+    let regions: Vec<Vec<(Style, &str)>> = chunks.par_iter()
+        .zip(&ranges)
+        .zip(start_stacks)
+        .map(|(((ops, _), &(start, end)), stack)| {
+            let mut state = HighlightState::new(&highlighter, stack);
+            let mut regions = Vec::new();
+            for (line, line_ops) in lines[start..end].iter().zip(ops) {
+                regions.extend(HighlightIterator::new(&mut state, line_ops, line, &highlighter));
+            }
+            regions
+        })
+        .collect();
+
+    regions.concat()
+}
+
+/// A cheap guess at where the parser might be in a clean state: an unindented line
+/// after an empty one, at most one every `CHUNK_LINES` lines.
+fn guess_clean_lines(lines: &[String]) -> Vec<usize> {
+    let mut starts = vec![0];
+    for i in 1..lines.len() {
+        if i - starts[starts.len() - 1] >= CHUNK_LINES &&
+            lines[i - 1].trim().is_empty() &&
+            lines[i].starts_with(|c: char| !c.is_whitespace()) {
+            starts.push(i);
+        }
+    }
+    starts
+}
+
+fn parse_chunk(mut state: ParseState,
+               lines: &[String],
+               syntax_set: &SyntaxSet)
+               -> ParsedChunk {
+    let ops = lines.iter().map(|line| state.parse_line(line, syntax_set)).collect();
+    (ops, state)
+}
@@ -51,9 +51,9 @@ fn main() {
     let mut ss = if matches.opt_present("no-default-syntaxes") {
         SyntaxSet::new()
     } else if no_newlines {
-        SyntaxSet::load_defaults_nonewlines()
+        SyntaxSet::load_defaults_nonewlines_lazy()
     } else {
-        SyntaxSet::load_defaults_newlines()
+        SyntaxSet::load_defaults_newlines_lazy()
     };
 
     if let Some(folder) = matches.opt_str("extra-syntaxes") {
@@ -0,0 +1,309 @@
+//! Highlighting unified diffs with the syntax of the files they change, instead of
+//! the Diff syntax which only colours whole lines.
+//!
+//! Every file in the diff gets its syntax from the path in its `+++` header (or the `---`
+//! header for deleted files) and has separate parse states for the old and new version,
+//! which carry on across hunks. Removed lines are highlighted as part of the old file,
+//! added lines as part of the new file and context lines as part of both. The lines come
+//! out tagged with a `DiffLineKind`, so renderers can put diff backgrounds behind the
+//! syntax colours.
+//!
+//! ```
+//! use syntect::diff::{highlight_diff, DiffLineKind};
+//! use syntect::parsing::SyntaxSet;
+//! use syntect::highlighting::ThemeSet;
+//!
+//! let ss = SyntaxSet::load_defaults_newlines();
+//! let ts = ThemeSet::load_defaults();
+//! let diff = "--- a/src/main.rs\n\
+//!             +++ b/src/main.rs\n\
+//!             @@ -1,2 +1,2 @@\n\
+//!             \x20fn main() {\n\
+//!             -    old();\n\
+//!             +    new();\n";
+//! let lines = highlight_diff(diff, &ss, &ts.themes["base16-ocean.dark"]);
+//! assert_eq!(lines[5].kind, DiffLineKind::Added);
+//! assert_eq!(lines[5].marker, "+");
+//! assert_eq!(lines[5].new_line, Some(2));
+//! ```
+use highlighting::{HighlightIterator, HighlightState, Highlighter, Style, Theme};
+use parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet};
+use util::LinesWithEndings;
+
+/// What a line of a diff is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLineKind {
+    /// File headers, hunk headers, `\ No newline at end of file` and anything else
+    /// that isn't code
+    Header,
+    Added,
+    Removed,
+    Context,
+}
+
+/// A highlighted line of a diff
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffLine<'a> {
+    pub kind: DiffLineKind,
+    /// The `+`, `-` or ` ` at the start of a code line, empty for headers
+    pub marker: &'a str,
+    /// The rest of the line, highlighted with the syntax of the file for code lines
+    /// and with the theme's default style for headers
+    pub regions: Vec<(Style, &'a str)>,
+    /// The line number in the old file, for removed and context lines
+    pub old_line: Option<usize>,
+    /// The line number in the new file, for added and context lines
+    pub new_line: Option<usize>,
+}
+
+/// One side of a file being diffed
+struct Side {
+    parse_state: ParseState,
+    highlight_state: HighlightState,
+    /// The number of the next line
+    line: usize,
+    /// How many lines of the current hunk are left
+    remaining: usize,
+}
+
+impl Side {
+    fn new(syntax: &SyntaxReference, highlighter: &Highlighter) -> Side {
+        Side {
+            parse_state: ParseState::new(syntax),
+            highlight_state: HighlightState::new(highlighter, ScopeStack::new()),
+            line: 1,
+            remaining: 0,
+        }
+    }
+
+    fn highlight<'b>(&mut self, code: &'b str, ss: &SyntaxSet, highlighter: &Highlighter) -> Vec<(Style, &'b str)> {
+        let ops = self.parse_state.parse_line(code, ss);
+        let regions = HighlightIterator::new(&mut self.highlight_state, &ops[..], code, highlighter).collect();
+        self.line += 1;
+        self.remaining = self.remaining.saturating_sub(1);
+        regions
+    }
+}
+
+/// Highlights a unified diff a line at a time, see the module docs.
+///
+/// The lines must be passed with their line endings, and the `SyntaxSet` must be
+/// compiled for newline characters.
+pub struct DiffHighlighter<'a> {
+    syntax_set: &'a SyntaxSet,
+    highlighter: Highlighter<'a>,
+    old_path: Option<String>,
+    files: Option<(Side, Side)>,
+}
+
+impl<'a> DiffHighlighter<'a> {
+    pub fn new(syntax_set: &'a SyntaxSet, theme: &'a Theme) -> DiffHighlighter<'a> {
+        DiffHighlighter {
+            syntax_set,
+            highlighter: Highlighter::new(theme),
+            old_path: None,
+            files: None,
+        }
+    }
+
+    /// Highlights the next line of the diff
+    pub fn highlight_line<'b>(&mut self, line: &'b str) -> DiffLine<'b> {
+        let in_hunk = match self.files {
+            Some((ref old, ref new)) => old.remaining > 0 || new.remaining > 0,
+            None => false,
+        };
+        if in_hunk {
+            if let Some(diff_line) = self.highlight_code_line(line) {
+                return diff_line;
+            }
+        } else if line.starts_with("--- ") {
+            self.old_path = header_path(&line[4..]);
+        } else if line.starts_with("+++ ") {
+            let path = header_path(&line[4..]).or_else(|| self.old_path.take());
+            self.start_file(path.as_ref().map(|p| &p[..]));
+        } else if line.starts_with("@@ ") {
+            if let Some((old_start, old_count, new_start, new_count)) = parse_hunk_header(line) {
+                if self.files.is_none() {
+                    self.start_file(None);
+                }
+                if let Some((ref mut old, ref mut new)) = self.files {
+                    old.line = old_start;
+                    old.remaining = old_count;
+                    new.line = new_start;
+                    new.remaining = new_count;
+                }
+            }
+        } else if line.starts_with("diff ") {
+            self.old_path = None;
+            self.files = None;
+        }
+        self.header(line)
+    }
+
+    fn start_file(&mut self, path: Option<&str>) {
+        let ss = self.syntax_set;
+        // the files usually aren't there to look at their first line
+        let syntax = path.and_then(|p| ss.find_syntax_for_file(p).ok().and_then(|s| s))
+            .unwrap_or_else(|| ss.find_syntax_plain_text());
+        self.files = Some((Side::new(syntax, &self.highlighter), Side::new(syntax, &self.highlighter)));
+    }
+
+    fn highlight_code_line<'b>(&mut self, line: &'b str) -> Option<DiffLine<'b>> {
+        let ss = self.syntax_set;
+        let highlighter = &self.highlighter;
+        let (old, new) = match self.files {
+            Some((ref mut old, ref mut new)) => (old, new),
+            None => return None,
+        };
+        // some tools strip the space from empty context lines
+        let (marker, code) = if line == "\n" || line == "\r\n" {
+            ("", line)
+        } else {
+            line.split_at(line.chars().next().map_or(0, |c| c.len_utf8()))
+        };
+        let diff_line = match marker {
+            "-" => DiffLine {
+                kind: DiffLineKind::Removed,
+                marker,
+                old_line: Some(old.line),
+                new_line: None,
+                regions: old.highlight(code, ss, highlighter),
+            },
+            "+" => DiffLine {
+                kind: DiffLineKind::Added,
+                marker,
+                old_line: None,
+                new_line: Some(new.line),
+                regions: new.highlight(code, ss, highlighter),
+            },
+            " " | "" => {
+                let (old_line, new_line) = (old.line, new.line);
+                old.highlight(code, ss, highlighter);
+                DiffLine {
+                    kind: DiffLineKind::Context,
+                    marker,
+                    old_line: Some(old_line),
+                    new_line: Some(new_line),
+                    regions: new.highlight(code, ss, highlighter),
+                }
+            }
+            _ => return None,
+        };
+        Some(diff_line)
+    }
+
+    fn header<'b>(&self, line: &'b str) -> DiffLine<'b> {
+        DiffLine {
+            kind: DiffLineKind::Header,
+            marker: "",
+            regions: vec![(self.highlighter.get_default(), line)],
+            old_line: None,
+            new_line: None,
+        }
+    }
+}
+
+/// The path in a `---` or `+++` header, without the `a/` or `b/` prefix git adds.
+/// `None` for `/dev/null`.
+fn header_path(header: &str) -> Option<String> {
+    let path = header.split('\t').next().unwrap_or("").trim_end();
+    if path == "/dev/null" || path.is_empty() {
+        return None;
+    }
+    let path = if path.starts_with("a/") || path.starts_with("b/") { &path[2..] } else { path };
+    Some(path.to_owned())
+}
+
+/// Parses `@@ -old_start,old_count +new_start,new_count @@`, where the counts are 1 if left out
+fn parse_hunk_header(line: &str) -> Option<(usize, usize, usize, usize)> {
+    fn range(s: &str) -> Option<(usize, usize)> {
+        let mut parts = s.splitn(2, ',');
+        let start = parts.next()?.parse().ok()?;
+        let count = match parts.next() {
+            Some(count) => count.parse().ok()?,
+            None => 1,
+        };
+        Some((start, count))
+    }
+    let mut words = line.split_whitespace().skip(1);
+    let old = words.next()?;
+    let new = words.next()?;
+    if !old.starts_with('-') || !new.starts_with('+') {
+        return None;
+    }
+    let (old_start, old_count) = range(&old[1..])?;
+    let (new_start, new_count) = range(&new[1..])?;
+    Some((old_start, old_count, new_start, new_count))
+}
+
+/// Convenience method that highlights every line of a diff with a `DiffHighlighter`.
+pub fn highlight_diff<'b>(diff: &'b str, ss: &SyntaxSet, theme: &Theme) -> Vec<DiffLine<'b>> {
+    let mut highlighter = DiffHighlighter::new(ss, theme);
+    LinesWithEndings::from(diff).map(|line| highlighter.highlight_line(line)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use highlighting::ThemeSet;
+
+    #[test]
+    fn parses_headers() {
+        assert_eq!(header_path("a/src/lib.rs\t2018-01-01 00:00:00\n"), Some("src/lib.rs".to_owned()));
+        assert_eq!(header_path("Makefile\n"), Some("Makefile".to_owned()));
+        assert_eq!(header_path("/dev/null\n"), None);
+        assert_eq!(parse_hunk_header("@@ -1,5 +1,6 @@ fn main() {\n"), Some((1, 5, 1, 6)));
+        assert_eq!(parse_hunk_header("@@ -3 +0,0 @@\n"), Some((3, 1, 0, 0)));
+        assert_eq!(parse_hunk_header("@@ nonsense @@\n"), None);
+    }
+
+    #[test]
+    fn highlights_hunks() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let ts = ThemeSet::load_defaults();
+        let diff = "diff --git a/lib.rs b/lib.rs\n\
+                    --- a/lib.rs\n\
+                    +++ b/lib.rs\n\
+                    @@ -1,3 +1,3 @@\n\
+                    \x20/* start\n\
+                    --- a comment, not a header\n\
+                    +++ still a comment\n\
+                    \x20*/\n\
+                    @@ -10 +10,2 @@\n\
+                    -let x = 1;\n\
+                    +let x = 2;\n\
+                    +let y = 3;\n\
+                    \\ No newline at end of file\n";
+        let lines = highlight_diff(diff, &ss, &ts.themes["base16-ocean.dark"]);
+        let kinds: Vec<DiffLineKind> = lines.iter().map(|l| l.kind).collect();
+        use self::DiffLineKind::*;
+        assert_eq!(kinds, vec![Header, Header, Header, Header, Context, Removed, Added, Context,
+                               Header, Removed, Added, Added, Header]);
+
+        // the removed and added lines are inside the comment on both sides
+        let comment = lines[4].regions.last().unwrap().0;
+        assert_eq!(lines[5].marker, "-");
+        assert_eq!(lines[5].regions, vec![(comment, "-- a comment, not a header\n")]);
+        assert_eq!(lines[6].regions, vec![(comment, "++ still a comment\n")]);
+        assert_eq!((lines[7].old_line, lines[7].new_line), (Some(3), Some(3)));
+
+        // the code after the comment is highlighted as Rust
+        assert_eq!((lines[9].old_line, lines[9].new_line), (Some(10), None));
+        assert_eq!((lines[11].old_line, lines[11].new_line), (None, Some(11)));
+        assert!(lines[11].regions.len() > 1);
+        assert!(lines[11].regions.iter().all(|&(style, _)| style != comment));
+        assert_eq!(lines[12].regions.len(), 1);
+    }
+
+    #[test]
+    fn falls_back_to_plain_text() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let ts = ThemeSet::load_defaults();
+        let diff = "@@ -1 +1 @@\n-a\n+b\n";
+        let lines = highlight_diff(diff, &ss, &ts.themes["base16-ocean.dark"]);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2].kind, DiffLineKind::Added);
+        let text: String = lines[2].regions.iter().map(|&(_, text)| text).collect();
+        assert_eq!(text, "b\n");
+    }
+}
@@ -7,21 +7,42 @@
 //! You can use these methods to manage your own caching of compiled syntaxes and
 //! themes. And even your own `serde::Serialize` structures if you want to
 //! be consistent with your format.
-use bincode::Result;
+//!
+//! Dumps start with a small header containing the version of the dump format, the version
+//! of syntect and the features it was built with, a checksum and a `DumpInfo`, so that loading
+//! an outdated or damaged dump returns a `DumpError`. See `read_header` for inspecting it.
+//! Dumps made by older versions without a header are rejected by the functions that load
+//! dumps, use `from_legacy_binary` to load them anyway.
+use bincode;
 #[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
 use bincode::deserialize_from;
 #[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
 use bincode::serialize_into;
 use std::fs::File;
 #[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader};
+#[cfg(all(feature = "parsing", any(feature = "dump-load", feature = "dump-load-rs")))]
+use std::io::Read;
+#[cfg(all(feature = "parsing", any(feature = "dump-create", feature = "dump-create-rs")))]
+use std::usize;
 #[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
 use std::io::{BufWriter, Write};
-#[cfg(all(feature = "parsing", feature = "assets", any(feature = "dump-load", feature = "dump-load-rs")))]
-use parsing::SyntaxSet;
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+use std::error::Error;
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+use std::fmt;
+#[cfg(all(feature = "parsing", any(feature = "dump-load", feature = "dump-load-rs", feature = "dump-create", feature = "dump-create-rs")))]
+use parsing::{SyntaxSet, SyntaxReference};
+#[cfg(all(feature = "parsing", any(feature = "dump-load", feature = "dump-load-rs", feature = "dump-create", feature = "dump-create-rs")))]
+use parsing::syntax_definition::Context;
+#[cfg(all(feature = "parsing", any(feature = "dump-load", feature = "dump-load-rs")))]
+use parsing::LegacySyntaxSet;
+#[cfg(all(feature = "parsing", any(feature = "dump-create", feature = "dump-create-rs")))]
+use parsing::syntax_definition::ContextId;
 #[cfg(all(feature = "assets", any(feature = "dump-load", feature = "dump-load-rs")))]
 use highlighting::ThemeSet;
 use std::path::Path;
+use flate2::Crc;
 #[cfg(feature = "dump-create")]
 use flate2::write::ZlibEncoder;
 #[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
@@ -33,10 +54,176 @@ use serde::Serialize;
 #[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
 use serde::de::DeserializeOwned;
 
+/// The version of the dump format written by this version of syntect.
+/// Dumps with a different version can't be loaded and have to be created again.
+pub const DUMP_FORMAT_VERSION: u32 = 2;
+
+/// Dumps with a header start with this. Older dumps start with the zlib header instead,
+/// which can't be mistaken for it because its first byte is always `0x78`.
+const DUMP_MAGIC: &[u8; 8] = b"SYNTECT\x1a";
+/// Magic, format version, flags, features, syntect version and checksum
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+const HEADER_LEN: usize = 8 + 4 + 1 + 1 + 3 * 2 + 4;
+/// Set in the flags of dumps made with `dump_lazy_syntax_set`, next to the `DumpInfo`
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs", all(feature = "parsing", any(feature = "dump-create", feature = "dump-create-rs"))))]
+const LAZY_SYNTAX_SET_FLAG: u8 = 4;
+
+/// Information about the dumped value that is stored in the header of a dump
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DumpInfo {
+    /// Whether a dumped `SyntaxSet` was built for lines that include the newline at the end,
+    /// see `SyntaxSetBuilder::add_from_folder`. `None` if unknown or not a syntax set.
+    pub lines_include_newline: Option<bool>,
+}
+
+bitflags! {
+    /// The features syntect was built with when it made a dump, see `DumpHeader`
+    pub struct DumpFeatures: u8 {
+        /// The `parsing` feature
+        const PARSING = 1;
+        /// The `metadata` feature
+        const METADATA = 2;
+        /// The `yaml-load` feature
+        const YAML_LOAD = 4;
+    }
+}
+
+impl DumpFeatures {
+    /// The features of the syntect that is running
+    pub fn current() -> DumpFeatures {
+        let mut features = DumpFeatures::empty();
+        if cfg!(feature = "parsing") {
+            features |= DumpFeatures::PARSING;
+        }
+        if cfg!(feature = "metadata") {
+            features |= DumpFeatures::METADATA;
+        }
+        if cfg!(feature = "yaml-load") {
+            features |= DumpFeatures::YAML_LOAD;
+        }
+        features
+    }
+}
+
+/// Everything in the header of a dump, returned by `read_header`
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DumpHeader {
+    /// The version of syntect that made the dump, as major, minor and patch version
+    pub syntect_version: (u16, u16, u16),
+    /// The features syntect was built with when it made the dump
+    pub features: DumpFeatures,
+    /// The information given when the dump was made
+    pub info: DumpInfo,
+    /// Whether the dump was made with `dump_lazy_syntax_set`
+    pub lazy_syntax_set: bool,
+}
+
+/// Errors that can occur when loading a dump
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+#[derive(Debug)]
+pub enum DumpError {
+    /// error reading the dump
+    Io(io::Error),
+    /// the dump doesn't start with a header, so it was made by an older version of syntect
+    /// or isn't a dump at all. Create it again, or see `from_legacy_binary`.
+    MissingHeader,
+    /// the dump has a different format version than `DUMP_FORMAT_VERSION`
+    UnsupportedVersion(u32),
+    /// the dump was damaged, its contents don't match the checksum in the header
+    ChecksumMismatch,
+    /// the dump contents couldn't be decompressed or don't match the type they're loaded as
+    Decode(bincode::Error),
+    /// a dump made with `dump_lazy_syntax_set` was loaded with the functions for other dumps,
+    /// or the other way around
+    WrongLayout,
+}
+
+/// The contexts of one syntax in a dump made with `dump_lazy_syntax_set`,
+/// compressed separately from the rest of the syntax set
+#[cfg(all(feature = "parsing", any(feature = "dump-load", feature = "dump-load-rs", feature = "dump-create", feature = "dump-create-rs")))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ContextBlob {
+    /// The index of the first context
+    pub(crate) start: usize,
+    pub(crate) len: usize,
+    data: Vec<u8>,
+}
+
+/// Everything in a lazily loaded syntax set except the contexts
+#[cfg(all(feature = "parsing", any(feature = "dump-load", feature = "dump-load-rs", feature = "dump-create", feature = "dump-create-rs")))]
+#[derive(Serialize, Deserialize)]
+struct LazySyntaxSetIndex {
+    syntaxes: Vec<SyntaxReference>,
+    path_syntaxes: Vec<(String, usize)>,
+    context_count: usize,
+    metadata: Option<String>,
+}
+
+#[cfg(all(feature = "parsing", any(feature = "dump-load", feature = "dump-load-rs", feature = "dump-create", feature = "dump-create-rs")))]
+#[derive(Serialize, Deserialize)]
+struct LazySyntaxSetDump {
+    /// The compressed `LazySyntaxSetIndex`
+    index: Vec<u8>,
+    blobs: Vec<ContextBlob>,
+}
+
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+pub fn dump_to_writer<T: Serialize, W: Write>(to_dump: &T, output: W) -> bincode::Result<()> {
+    dump_to_writer_with_info(to_dump, DumpInfo::default(), output)
+}
+
+/// Like `dump_to_writer`, but also records `info` in the header of the dump
 #[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
-pub fn dump_to_writer<T: Serialize, W: Write>(to_dump: &T, output: W) -> Result<()> {
-    let mut encoder = ZlibEncoder::new(output, Compression::best());
-    serialize_into(&mut encoder, to_dump)
+pub fn dump_to_writer_with_info<T: Serialize, W: Write>(to_dump: &T,
+                                                        info: DumpInfo,
+                                                        mut output: W)
+                                                        -> bincode::Result<()> {
+    // The checksum goes before the contents, so they have to be compressed in memory first
+    let payload = compress(to_dump)?;
+    write_dump(&mut output, info_flags(info), &payload)
+}
+
+/// Dumps a syntax set in a layout where the contexts of each syntax are compressed separately.
+///
+/// Loading such a dump with `lazy_syntax_set_from_binary` only decompresses the syntaxes
+/// and their names, extensions, first line regexes and so on. The contexts of a syntax are
+/// decompressed when they're first used, for example when parsing with the syntax or with
+/// one that embeds it. This makes loading a lot faster when only a few syntaxes are used.
+#[cfg(all(feature = "parsing", any(feature = "dump-create", feature = "dump-create-rs")))]
+pub fn dump_lazy_syntax_set<W: Write>(syntax_set: &SyntaxSet,
+                                      info: DumpInfo,
+                                      mut output: W)
+                                      -> bincode::Result<()> {
+    let context_count = syntax_set.context_count();
+    // Contexts of a syntax are next to each other, contexts without a syntax are grouped too
+    let mut owners = vec![usize::MAX; context_count];
+    for (i, syntax) in syntax_set.syntaxes.iter().enumerate() {
+        for context_id in syntax.contexts.values() {
+            owners[context_id.index()] = i;
+        }
+    }
+
+    let mut blobs = Vec::new();
+    let mut start = 0;
+    while start < context_count {
+        let end = (start..context_count).find(|&i| owners[i] != owners[start]).unwrap_or(context_count);
+        let contexts: Vec<&Context> = (start..end)
+            .map(|i| syntax_set.get_context(&ContextId::new(i)))
+            .collect();
+        blobs.push(ContextBlob { start, len: end - start, data: compress(&contexts)? });
+        start = end;
+    }
+
+    let index = LazySyntaxSetIndex {
+        syntaxes: syntax_set.syntaxes.clone(),
+        path_syntaxes: syntax_set.path_syntaxes.clone(),
+        context_count,
+        metadata: syntax_set.metadata_json().map_err(|e| bincode::ErrorKind::Custom(e.to_string()))?,
+    };
+    let dump = LazySyntaxSetDump { index: compress(&index)?, blobs };
+    let payload = bincode::serialize(&dump)?;
+    write_dump(&mut output, info_flags(info) | LAZY_SYNTAX_SET_FLAG, &payload)
 }
 
 /// Dumps an object to a binary array in the same format as `dump_to_file`
@@ -51,32 +238,316 @@ pub fn dump_binary<T: Serialize>(o: &T) -> Vec<u8> {
 /// it will be overwritten. The files created are encoded with the `bincode` crate and then
 /// compressed with the `flate2` crate.
 #[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
-pub fn dump_to_file<T: Serialize, P: AsRef<Path>>(o: &T, path: P) -> Result<()> {
+pub fn dump_to_file<T: Serialize, P: AsRef<Path>>(o: &T, path: P) -> bincode::Result<()> {
+    dump_to_file_with_info(o, DumpInfo::default(), path)
+}
+
+/// Like `dump_to_file`, but also records `info` in the header of the dump
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+pub fn dump_to_file_with_info<T: Serialize, P: AsRef<Path>>(o: &T, info: DumpInfo, path: P) -> bincode::Result<()> {
     let out = BufWriter::new(File::create(path)?);
-    dump_to_writer(o, out)
+    dump_to_writer_with_info(o, info, out)
 }
 
+/// Loads a dump made with `dump_to_writer` or one of the other functions for making dumps.
+///
+/// Before dumps had a header this returned a `bincode::Result`, now the header is checked
+/// and any problem with the dump is a `DumpError`.
 #[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
-pub fn from_reader<T: DeserializeOwned, R: BufRead>(input: R) -> Result<T> {
-    let mut decoder = ZlibDecoder::new(input);
-    deserialize_from(&mut decoder)
+pub fn from_reader<T: DeserializeOwned, R: BufRead>(input: R) -> Result<T, DumpError> {
+    from_reader_with_info(input).map(|(value, _)| value)
+}
+
+/// Like `from_reader`, but also returns the `DumpInfo` from the header of the dump.
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+pub fn from_reader_with_info<T: DeserializeOwned, R: BufRead>(mut input: R) -> Result<(T, DumpInfo), DumpError> {
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+    decode(&data)
 }
 
 /// Returns a fully loaded syntax set from
-/// a binary dump. Panics if the dump is invalid.
+/// a binary dump. Panics if the dump is invalid, see `try_from_binary`.
 #[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
 pub fn from_binary<T: DeserializeOwned>(v: &[u8]) -> T {
-    from_reader(v).unwrap()
+    try_from_binary(v).unwrap()
+}
+
+/// Like `from_binary`, but returns an error if the dump is invalid instead of panicking.
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+pub fn try_from_binary<T: DeserializeOwned>(v: &[u8]) -> Result<T, DumpError> {
+    decode(v).map(|(value, _)| value)
+}
+
+/// Loads a dump made before dumps had a header, which the other functions reject with
+/// `DumpError::MissingHeader`.
+///
+/// Nothing about the dump can be checked, so it only loads correctly if the layout of
+/// `T` hasn't changed since the dump was made. That isn't the case for `SyntaxSet`,
+/// use `legacy_syntax_set_from_binary` for those.
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+pub fn from_legacy_binary<T: DeserializeOwned>(v: &[u8]) -> Result<T, DumpError> {
+    if v.starts_with(DUMP_MAGIC) {
+        return Err(DumpError::UnsupportedVersion(0));
+    }
+    Ok(decompress(v)?)
+}
+
+/// Loads a syntax set dumped by an older version of syntect, before dumps had a header
+/// and syntax sets included their metadata.
+///
+/// Those kept the metadata in a separate dump, which can be passed as `metadata` when
+/// the `metadata` feature is enabled, see `legacy_syntax_set_with_metadata_from_binary`.
+#[cfg(all(feature = "parsing", any(feature = "dump-load", feature = "dump-load-rs")))]
+pub fn legacy_syntax_set_from_binary(v: &[u8]) -> Result<SyntaxSet, DumpError> {
+    let legacy: LegacySyntaxSet = from_legacy_binary(v)?;
+    Ok(legacy.into_syntax_set())
+}
+
+/// Same as `legacy_syntax_set_from_binary`, but also loads the separate metadata dump
+/// that was made alongside the syntax set.
+#[cfg(all(feature = "metadata", any(feature = "dump-load", feature = "dump-load-rs")))]
+pub fn legacy_syntax_set_with_metadata_from_binary(v: &[u8], metadata: &[u8]) -> Result<SyntaxSet, DumpError> {
+    let mut syntax_set = legacy_syntax_set_from_binary(v)?;
+    syntax_set.metadata = from_legacy_binary(metadata)?;
+    Ok(syntax_set)
+}
+
+/// Reads the header of a dump, without checking the rest of it.
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+pub fn read_header(v: &[u8]) -> Result<DumpHeader, DumpError> {
+    check_header(v)?;
+    Ok(DumpHeader {
+        syntect_version: (u16_from_le_bytes(&v[14..16]), u16_from_le_bytes(&v[16..18]), u16_from_le_bytes(&v[18..20])),
+        features: DumpFeatures::from_bits_truncate(v[13]),
+        info: info_from_flags(v[12]),
+        lazy_syntax_set: v[12] & LAZY_SYNTAX_SET_FLAG != 0,
+    })
 }
 
 /// Returns a fully loaded syntax set from a binary dump file.
 #[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
-pub fn from_dump_file<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T> {
+pub fn from_dump_file<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T, DumpError> {
     let f = File::open(path)?;
     let reader = BufReader::new(f);
     from_reader(reader)
 }
 
+/// Loads a syntax set dumped with `dump_lazy_syntax_set`.
+#[cfg(all(feature = "parsing", any(feature = "dump-load", feature = "dump-load-rs")))]
+pub fn lazy_syntax_set_from_binary(v: &[u8]) -> Result<SyntaxSet, DumpError> {
+    let (flags, payload) = read_dump(v)?;
+    if flags & LAZY_SYNTAX_SET_FLAG == 0 {
+        return Err(DumpError::WrongLayout);
+    }
+    let dump: LazySyntaxSetDump = bincode::deserialize(payload)?;
+    let index: LazySyntaxSetIndex = decompress(&dump.index)?;
+
+    // The contexts are only decompressed when they're used, so make sure that will work now
+    let mut expected_start = 0;
+    for blob in &dump.blobs {
+        if blob.start != expected_start || blob.len == 0 {
+            return Err(invalid_dump("context blobs don't cover every context"));
+        }
+        expected_start += blob.len;
+    }
+    if expected_start != index.context_count {
+        return Err(invalid_dump("context blobs don't cover every context"));
+    }
+    let mut syntax_set = SyntaxSet::new_lazy(index.syntaxes, index.path_syntaxes, index.context_count, dump.blobs);
+    syntax_set.set_metadata_json(index.metadata).map_err(|e| invalid_dump(&e.to_string()))?;
+    Ok(syntax_set)
+}
+
+/// Loads a syntax set from a file created with `dump_lazy_syntax_set`.
+#[cfg(all(feature = "parsing", any(feature = "dump-load", feature = "dump-load-rs")))]
+pub fn lazy_syntax_set_from_dump_file<P: AsRef<Path>>(path: P) -> Result<SyntaxSet, DumpError> {
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+    lazy_syntax_set_from_binary(&data)
+}
+
+#[cfg(all(feature = "parsing", any(feature = "dump-load", feature = "dump-load-rs")))]
+impl ContextBlob {
+    /// The checksum and the layout of the blobs are checked by `lazy_syntax_set_from_binary`,
+    /// so this only panics for a dump that `dump_lazy_syntax_set` didn't write correctly.
+    pub(crate) fn load(&self) -> Vec<Context> {
+        let contexts: Vec<Context> = decompress(&self.data)
+            .expect("invalid contexts in lazily loaded syntax set");
+        assert_eq!(contexts.len(), self.len);
+        contexts
+    }
+}
+
+#[cfg(all(feature = "parsing", any(feature = "dump-load", feature = "dump-load-rs")))]
+fn invalid_dump(message: &str) -> DumpError {
+    DumpError::Decode(Box::new(bincode::ErrorKind::Custom(message.to_string())))
+}
+
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<(T, DumpInfo), DumpError> {
+    let (flags, payload) = read_dump(data)?;
+    if flags & LAZY_SYNTAX_SET_FLAG != 0 {
+        return Err(DumpError::WrongLayout);
+    }
+    let value = decompress(payload)?;
+    Ok((value, info_from_flags(flags)))
+}
+
+/// Checks the header and returns the flags from it and the rest of the dump
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+fn read_dump(data: &[u8]) -> Result<(u8, &[u8]), DumpError> {
+    check_header(data)?;
+    let payload = &data[HEADER_LEN..];
+    if checksum(payload) != u32_from_le_bytes(&data[20..24]) {
+        return Err(DumpError::ChecksumMismatch);
+    }
+    Ok((data[12], payload))
+}
+
+/// Checks that the dump has a header of the current format version
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+fn check_header(data: &[u8]) -> Result<(), DumpError> {
+    if !data.starts_with(DUMP_MAGIC) {
+        return Err(DumpError::MissingHeader);
+    }
+    // The version comes first so that it can be read from dumps with other header layouts
+    if data.len() < 12 {
+        return Err(DumpError::Io(io::ErrorKind::UnexpectedEof.into()));
+    }
+    let version = u32_from_le_bytes(&data[8..12]);
+    if version != DUMP_FORMAT_VERSION {
+        return Err(DumpError::UnsupportedVersion(version));
+    }
+    if data.len() < HEADER_LEN {
+        return Err(DumpError::Io(io::ErrorKind::UnexpectedEof.into()));
+    }
+    Ok(())
+}
+
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+fn write_dump<W: Write>(output: &mut W, flags: u8, payload: &[u8]) -> bincode::Result<()> {
+    output.write_all(DUMP_MAGIC)?;
+    output.write_all(&u32_to_le_bytes(DUMP_FORMAT_VERSION))?;
+    output.write_all(&[flags, DumpFeatures::current().bits()])?;
+    for part in syntect_version().iter() {
+        output.write_all(&[*part as u8, (*part >> 8) as u8])?;
+    }
+    output.write_all(&u32_to_le_bytes(checksum(payload)))?;
+    output.write_all(payload)?;
+    Ok(())
+}
+
+/// The major, minor and patch version of this syntect
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+fn syntect_version() -> [u16; 3] {
+    let mut version = [0; 3];
+    for (part, number) in version.iter_mut().zip(env!("CARGO_PKG_VERSION").split('.')) {
+        *part = number.parse().unwrap_or(0);
+    }
+    version
+}
+
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+fn compress<T: Serialize>(value: &T) -> bincode::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    serialize_into(&mut encoder, value)?;
+    Ok(encoder.finish()?)
+}
+
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+fn decompress<T: DeserializeOwned>(data: &[u8]) -> bincode::Result<T> {
+    let mut decoder = ZlibDecoder::new(data);
+    deserialize_from(&mut decoder)
+}
+
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+fn info_flags(info: DumpInfo) -> u8 {
+    match info.lines_include_newline {
+        None => 0,
+        Some(false) => 1,
+        Some(true) => 2,
+    }
+}
+
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+fn info_from_flags(flags: u8) -> DumpInfo {
+    let lines_include_newline = match flags & 3 {
+        1 => Some(false),
+        2 => Some(true),
+        _ => None,
+    };
+    DumpInfo { lines_include_newline }
+}
+
+fn checksum(payload: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(payload);
+    crc.sum()
+}
+
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+fn u32_to_le_bytes(n: u32) -> [u8; 4] {
+    [n as u8, (n >> 8) as u8, (n >> 16) as u8, (n >> 24) as u8]
+}
+
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+fn u32_from_le_bytes(bytes: &[u8]) -> u32 {
+    bytes.iter().rev().fold(0, |n, &byte| (n << 8) | u32::from(byte))
+}
+
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+fn u16_from_le_bytes(bytes: &[u8]) -> u16 {
+    u16::from(bytes[0]) | (u16::from(bytes[1]) << 8)
+}
+
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+impl From<io::Error> for DumpError {
+    fn from(error: io::Error) -> DumpError {
+        DumpError::Io(error)
+    }
+}
+
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+impl From<bincode::Error> for DumpError {
+    fn from(error: bincode::Error) -> DumpError {
+        DumpError::Decode(error)
+    }
+}
+
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+impl fmt::Display for DumpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::DumpError::*;
+
+        match *self {
+            Io(ref error) => error.fmt(f),
+            UnsupportedVersion(version) => {
+                write!(f, "Unsupported dump format version {}, expected {}", version, DUMP_FORMAT_VERSION)
+            }
+            MissingHeader => write!(f, "Dump without a header, made by an older version of syntect"),
+            ChecksumMismatch => write!(f, "Dump checksum mismatch"),
+            Decode(ref error) => error.fmt(f),
+            WrongLayout => {
+                write!(f, "Dump of a lazily loaded syntax set loaded as other dump or the other way around")
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+impl Error for DumpError {
+    fn cause(&self) -> Option<&dyn Error> {
+        use self::DumpError::*;
+
+        match *self {
+            Io(ref error) => Some(error),
+            Decode(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
 #[cfg(all(feature = "parsing", feature = "assets", any(feature = "dump-load", feature = "dump-load-rs")))]
 impl SyntaxSet {
     /// Instantiates a new syntax set from a binary dump of
@@ -94,36 +565,29 @@ impl SyntaxSet {
     /// you can even use the fact that SyntaxDefinitions are serializable with
     /// the bincode crate to cache dumps of additional syntaxes yourself.
     pub fn load_defaults_nonewlines() -> SyntaxSet {
-
-        #[cfg(feature = "metadata")]
-        {
-            let mut ps: SyntaxSet = from_binary(include_bytes!("../assets/default_nonewlines.packdump"));
-            let metadata = from_binary(include_bytes!("../assets/default_metadata.packdump"));
-            ps.metadata = metadata;
-            ps
-        }
-        #[cfg(not(feature = "metadata"))]
-        {
-            from_binary(include_bytes!("../assets/default_nonewlines.packdump"))
-        }
+        from_binary(include_bytes!("../assets/default_nonewlines.packdump"))
     }
 
     /// Same as `load_defaults_nonewlines` but for parsing line strings with newlines at the end.
     /// These are separate methods because thanks to linker garbage collection, only the serialized
     /// dumps for the method(s) you call will be included in the binary (each is ~200kb for now).
     pub fn load_defaults_newlines() -> SyntaxSet {
+        from_binary(include_bytes!("../assets/default_newlines.packdump"))
+    }
 
-        #[cfg(feature = "metadata")]
-        {
-            let mut ps: SyntaxSet = from_binary(include_bytes!("../assets/default_newlines.packdump"));
-            let metadata = from_binary(include_bytes!("../assets/default_metadata.packdump"));
-            ps.metadata = metadata;
-            ps
-        }
-        #[cfg(not(feature = "metadata"))]
-        {
-            from_binary(include_bytes!("../assets/default_newlines.packdump"))
-        }
+    /// Same as `load_defaults_nonewlines`, but the contexts of each syntax are only decompressed
+    /// when the syntax is first used, see `dumps::dump_lazy_syntax_set`.
+    ///
+    /// This makes loading a lot faster for programs that only highlight a file or two before
+    /// they exit, parsing is just as fast once a syntax has been loaded.
+    pub fn load_defaults_nonewlines_lazy() -> SyntaxSet {
+        lazy_syntax_set_from_binary(include_bytes!("../assets/default_nonewlines_lazy.packdump")).unwrap()
+    }
+
+    /// Same as `load_defaults_newlines`, but loads the contexts of each syntax lazily
+    /// like `load_defaults_nonewlines_lazy`.
+    pub fn load_defaults_newlines_lazy() -> SyntaxSet {
+        lazy_syntax_set_from_binary(include_bytes!("../assets/default_newlines_lazy.packdump")).unwrap()
     }
 }
 
@@ -178,6 +642,220 @@ mod tests {
         assert_eq!(bin1, bin2);
     }
 
+    #[cfg(all(feature = "yaml-load", feature = "metadata", any(feature = "dump-create", feature = "dump-create-rs"), any(feature = "dump-load", feature = "dump-load-rs")))]
+    #[test]
+    fn dump_includes_metadata() {
+        use super::*;
+        use parsing::{SyntaxSetBuilder, Scope};
+
+        let mut builder = SyntaxSetBuilder::new();
+        builder.add_from_folder("testdata/Packages", false).unwrap();
+        builder.add_from_folder("testdata/DefaultPackage", false).unwrap();
+        let ss = builder.build();
+        assert!(!ss.metadata().scoped_metadata.is_empty());
+
+        let ss2: SyntaxSet = from_binary(&dump_binary(&ss)[..]);
+        assert_eq!(ss.metadata().scoped_metadata, ss2.metadata().scoped_metadata);
+        let scope = [Scope::new("source.rust").unwrap()];
+        assert!(ss2.metadata().metadata_for_scope(&scope).increase_indent("struct This {"));
+    }
+
+    #[cfg(all(feature = "parsing", feature = "metadata", feature = "assets", any(feature = "dump-load", feature = "dump-load-rs")))]
+    #[test]
+    fn default_dumps_include_metadata() {
+        use parsing::SyntaxSet;
+        let ss = SyntaxSet::load_defaults_newlines();
+        assert!(ss.syntaxes().len() > 10);
+        assert!(!ss.metadata().scoped_metadata.is_empty());
+    }
+
+    #[cfg(all(feature = "parsing", feature = "assets", feature = "dump-create", any(feature = "dump-load", feature = "dump-load-rs")))]
+    #[test]
+    fn legacy_syntax_sets_are_only_loaded_explicitly() {
+        use super::*;
+        use bincode::serialize_into;
+        use flate2::write::ZlibEncoder;
+        use flate2::Compression;
+        use parsing::ParseState;
+
+        // Older versions serialized the syntaxes, contexts and path syntaxes as a struct
+        let ss = SyntaxSet::load_defaults_newlines();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        serialize_into(&mut encoder, &(&ss.syntaxes, &ss.contexts, &ss.path_syntaxes)).unwrap();
+        let bin = encoder.finish().unwrap();
+        assert!(try_from_binary::<SyntaxSet>(&bin).is_err());
+
+        let legacy = legacy_syntax_set_from_binary(&bin).unwrap();
+        assert_eq!(legacy.syntaxes().len(), ss.syntaxes().len());
+        let line = "fn main() {}\n";
+        let syntax = ss.find_syntax_by_extension("rs").unwrap();
+        let legacy_syntax = legacy.find_syntax_by_extension("rs").unwrap();
+        assert_eq!(ParseState::new(legacy_syntax).parse_line(line, &legacy),
+                   ParseState::new(syntax).parse_line(line, &ss));
+    }
+
+    #[cfg(all(any(feature = "dump-create", feature = "dump-create-rs"), any(feature = "dump-load", feature = "dump-load-rs")))]
+    #[test]
+    fn header_records_info() {
+        use super::*;
+        let value = vec!["some".to_string(), "strings".to_string()];
+        let info = DumpInfo { lines_include_newline: Some(true) };
+        let mut bin = Vec::new();
+        dump_to_writer_with_info(&value, info, &mut bin).unwrap();
+
+        let (loaded, loaded_info): (Vec<String>, DumpInfo) = from_reader_with_info(&bin[..]).unwrap();
+        assert_eq!(loaded, value);
+        assert_eq!(loaded_info, info);
+        let (_, default_info): (Vec<String>, DumpInfo) = from_reader_with_info(&dump_binary(&value)[..]).unwrap();
+        assert_eq!(default_info.lines_include_newline, None);
+
+        let header = read_header(&bin).unwrap();
+        assert_eq!(header.info, info);
+        assert_eq!(header.features, DumpFeatures::current());
+        let version = env!("CARGO_PKG_VERSION");
+        assert_eq!(format!("{}.{}.{}", header.syntect_version.0, header.syntect_version.1, header.syntect_version.2), version);
+        assert!(!header.lazy_syntax_set);
+    }
+
+    #[cfg(all(any(feature = "dump-create", feature = "dump-create-rs"), any(feature = "dump-load", feature = "dump-load-rs")))]
+    #[test]
+    fn invalid_dumps_return_errors() {
+        use super::*;
+        let bin = dump_binary(&vec![1u32, 2, 3]);
+
+        let mut damaged = bin.clone();
+        let last = damaged.len() - 1;
+        damaged[last] ^= 0xff;
+        match try_from_binary::<Vec<u32>>(&damaged) {
+            Err(DumpError::ChecksumMismatch) => {}
+            other => panic!("expected checksum mismatch, got {:?}", other),
+        }
+
+        let mut newer = bin.clone();
+        newer[8] = newer[8].wrapping_add(1);
+        match try_from_binary::<Vec<u32>>(&newer) {
+            Err(DumpError::UnsupportedVersion(v)) => assert_eq!(v, DUMP_FORMAT_VERSION + 1),
+            other => panic!("expected unsupported version, got {:?}", other),
+        }
+
+        assert!(try_from_binary::<Vec<u32>>(&bin[..HEADER_LEN - 1]).is_err());
+        match try_from_binary::<Vec<u32>>(b"not a dump at all") {
+            Err(DumpError::MissingHeader) => {}
+            other => panic!("expected missing header, got {:?}", other),
+        }
+    }
+
+    #[cfg(all(feature = "dump-create", any(feature = "dump-load", feature = "dump-load-rs")))]
+    #[test]
+    fn dumps_without_header_are_only_loaded_explicitly() {
+        use super::*;
+        use bincode::serialize_into;
+        use flate2::write::ZlibEncoder;
+        use flate2::Compression;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        serialize_into(&mut encoder, &vec![1u32, 2, 3]).unwrap();
+        let bin = encoder.finish().unwrap();
+        match from_reader::<Vec<u32>, _>(&bin[..]) {
+            Err(DumpError::MissingHeader) => {}
+            other => panic!("expected missing header, got {:?}", other),
+        }
+        assert_eq!(from_legacy_binary::<Vec<u32>>(&bin).unwrap(), vec![1, 2, 3]);
+        assert!(from_legacy_binary::<Vec<u32>>(&dump_binary(&vec![1u32, 2, 3])).is_err());
+    }
+
+    #[cfg(all(feature = "parsing", feature = "assets", any(feature = "dump-load", feature = "dump-load-rs")))]
+    #[test]
+    fn lazy_default_dumps_match_eager_ones() {
+        use super::*;
+
+        let header = read_header(include_bytes!("../assets/default_newlines_lazy.packdump")).unwrap();
+        assert!(header.lazy_syntax_set);
+        assert_eq!(header.info.lines_include_newline, Some(true));
+        let header = read_header(include_bytes!("../assets/default_nonewlines_lazy.packdump")).unwrap();
+        assert!(header.lazy_syntax_set);
+        assert_eq!(header.info.lines_include_newline, Some(false));
+
+        let pairs = [
+            (SyntaxSet::load_defaults_newlines(), SyntaxSet::load_defaults_newlines_lazy()),
+            (SyntaxSet::load_defaults_nonewlines(), SyntaxSet::load_defaults_nonewlines_lazy()),
+        ];
+        for (eager, lazy) in pairs.iter() {
+            assert!(lazy.lazy_contexts.is_some());
+            assert_eq!(lazy.fingerprint(), eager.fingerprint());
+        }
+    }
+
+    #[cfg(all(feature = "yaml-load", any(feature = "dump-create", feature = "dump-create-rs"), any(feature = "dump-load", feature = "dump-load-rs")))]
+    #[test]
+    fn can_lazily_load_syntax_set() {
+        use super::*;
+        use parsing::{SyntaxSetBuilder, ParseState};
+
+        let mut builder = SyntaxSetBuilder::new();
+        builder.add_from_folder("testdata/Packages", false).unwrap();
+        let ss = builder.build();
+        let mut bin = Vec::new();
+        dump_lazy_syntax_set(&ss, DumpInfo::default(), &mut bin).unwrap();
+
+        let lazy = lazy_syntax_set_from_binary(&bin).unwrap();
+        assert_eq!(lazy.syntaxes().len(), ss.syntaxes().len());
+        let cells = &lazy.lazy_contexts.as_ref().unwrap().contexts;
+        assert!(cells.iter().all(|context| !context.filled()));
+
+        let line = "fn main() { let x = \"string\"; }";
+        let rust = ss.find_syntax_by_extension("rs").unwrap();
+        let lazy_rust = lazy.find_syntax_by_extension("rs").unwrap();
+        let expected = ParseState::new(rust).parse_line(line, &ss);
+        assert_eq!(ParseState::new(lazy_rust).parse_line(line, &lazy), expected);
+
+        let loaded = cells.iter().filter(|context| context.filled()).count();
+        assert!(loaded > 0 && loaded < cells.len());
+        assert_eq!(lazy.fingerprint(), ss.fingerprint());
+    }
+
+    #[cfg(all(feature = "yaml-load", any(feature = "dump-create", feature = "dump-create-rs"), any(feature = "dump-load", feature = "dump-load-rs")))]
+    #[test]
+    fn lazy_dumps_are_not_mixed_up_with_other_dumps() {
+        use super::*;
+        use parsing::SyntaxSetBuilder;
+
+        let mut builder = SyntaxSetBuilder::new();
+        builder.add_from_folder("testdata/Packages", false).unwrap();
+        let ss = builder.build();
+        let mut lazy_bin = Vec::new();
+        dump_lazy_syntax_set(&ss, DumpInfo::default(), &mut lazy_bin).unwrap();
+
+        match try_from_binary::<SyntaxSet>(&lazy_bin) {
+            Err(DumpError::WrongLayout) => {}
+            other => panic!("expected wrong layout, got {:?}", other.map(|_| ())),
+        }
+        match lazy_syntax_set_from_binary(&dump_binary(&ss)) {
+            Err(DumpError::WrongLayout) => {}
+            other => panic!("expected wrong layout, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[cfg(all(feature = "parsing", feature = "assets", any(feature = "dump-create", feature = "dump-create-rs"), any(feature = "dump-load", feature = "dump-load-rs")))]
+    #[test]
+    fn lazy_dumps_with_missing_contexts_return_errors() {
+        use super::*;
+
+        let ss = SyntaxSet::load_defaults_newlines();
+        let mut bin = Vec::new();
+        dump_lazy_syntax_set(&ss, DumpInfo::default(), &mut bin).unwrap();
+        let (flags, payload) = read_dump(&bin).unwrap();
+        let mut dump: LazySyntaxSetDump = bincode::deserialize(payload).unwrap();
+        dump.blobs.remove(1);
+        let mut damaged = Vec::new();
+        write_dump(&mut damaged, flags, &bincode::serialize(&dump).unwrap()).unwrap();
+
+        match lazy_syntax_set_from_binary(&damaged) {
+            Err(DumpError::Decode(_)) => {}
+            other => panic!("expected decode error, got {:?}", other.map(|_| ())),
+        }
+    }
+
     #[cfg(all(feature = "assets", any(feature = "dump-load", feature = "dump-load-rs")))]
     #[test]
     fn has_default_themes() {
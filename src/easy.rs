@@ -2,7 +2,7 @@
 //! files without caring about intermediate semantic representation
 //! and caching.
 
-use parsing::{ScopeStack, ParseState, SyntaxReference, SyntaxSet, ScopeStackOp};
+use parsing::{ScopeStack, ParseState, SyntaxReference, SyntaxSet, ScopeStackOp, CancellationToken, Cancelled};
 use highlighting::{Highlighter, HighlightState, HighlightIterator, Theme, Style};
 use std::io::{self, BufReader};
 use std::fs::File;
@@ -64,6 +64,21 @@ impl<'a> HighlightLines<'a> {
             HighlightIterator::new(&mut self.highlight_state, &ops[..], line, &self.highlighter);
         iter.collect()
     }
+
+    /// Like `highlight` but stops early with `Cancelled` once `cancellation` is cancelled.
+    ///
+    /// A cancelled line leaves the state as it was after the last line that finished,
+    /// so you can keep using this to highlight that line again later.
+    pub fn highlight_cancellable<'b>(&mut self,
+                                     line: &'b str,
+                                     syntax_set: &SyntaxSet,
+                                     cancellation: &CancellationToken)
+                                     -> Result<Vec<(Style, &'b str)>, Cancelled> {
+        let (ops, _) = self.parse_state.parse_line_cancellable(line, syntax_set, cancellation)?;
+        let iter =
+            HighlightIterator::new(&mut self.highlight_state, &ops[..], line, &self.highlighter);
+        Ok(iter.collect())
+    }
 }
 
 /// Convenience struct containing everything you need to highlight a file.
@@ -218,6 +233,25 @@ mod tests {
         assert!(ranges.len() > 4);
     }
 
+    #[test]
+    fn can_cancel_highlighting() {
+        let ss = SyntaxSet::load_defaults_nonewlines();
+        let ts = ThemeSet::load_defaults();
+        let syntax = ss.find_syntax_by_extension("rs").unwrap();
+        let mut h = HighlightLines::new(syntax, &ts.themes["base16-ocean.dark"]);
+        let token = CancellationToken::new();
+        h.highlight_cancellable("/* a comment", &ss, &token).unwrap();
+
+        token.cancel();
+        assert_eq!(h.highlight_cancellable("still in it */ struct", &ss, &token), Err(Cancelled));
+
+        // the cancelled line didn't change the state, so the comment is still open
+        let ranges = h.highlight("still in it */ struct", &ss);
+        let mut plain = HighlightLines::new(syntax, &ts.themes["base16-ocean.dark"]);
+        plain.highlight("/* a comment", &ss);
+        assert_eq!(ranges, plain.highlight("still in it */ struct", &ss));
+    }
+
     #[test]
     fn can_highlight_file() {
         let ss = SyntaxSet::load_defaults_nonewlines();
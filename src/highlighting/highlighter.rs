@@ -42,9 +42,12 @@ pub struct Highlighter<'a> {
 /// then re-create the `HighlightState` when needed by passing that stack as the `initial_stack`
 /// parameter to the `new` method. This takes less space but a small amount of time to re-create the style stack.
 ///
+/// It can also be serialized with serde. The cached styles come from the theme the state was
+/// created with, so a stored state should only be used with that same theme.
+///
 /// **Note:** Caching is for advanced users who have tons of time to maximize performance or want to do so eventually.
 /// It is not recommended that you try caching the first time you implement highlighting.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct HighlightState {
     styles: Vec<Style>,
     single_caches: Vec<ScoredStyle>,
@@ -156,7 +159,7 @@ impl<'a, 'b> Iterator for HighlightIterator<'a, 'b> {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ScoredStyle {
     pub foreground: (MatchPower, Color),
     pub background: (MatchPower, Color),
@@ -8,6 +8,7 @@ mod style;
 mod theme;
 mod highlighter;
 mod theme_set;
+mod overlay;
 
 pub use self::selector::*;
 pub use self::settings::SettingsError;
@@ -15,3 +16,4 @@ pub use self::style::*;
 pub use self::theme::*;
 pub use self::highlighter::*;
 pub use self::theme_set::*;
+pub use self::overlay::*;
@@ -0,0 +1,293 @@
+//! Layers of byte ranges drawn over highlighted text, like selections, search
+//! matches and lint squiggles.
+use std::ops::Range;
+use super::style::{Color, FontStyle, Style, StyleModifier};
+use super::theme::{ThemeSettings, UnderlineOption};
+
+/// How an `OverlayLayer` changes the text it covers
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OverlayStyle {
+    /// Applied to the style of the text like a theme rule
+    pub modifier: StyleModifier,
+    /// Replaces the underline of the text
+    pub underline: Option<UnderlineOption>,
+    /// The color of the underline, if it shouldn't be the foreground color
+    pub underline_color: Option<Color>,
+}
+
+impl OverlayStyle {
+    /// The theme's colors for selected text
+    pub fn selection(settings: &ThemeSettings) -> OverlayStyle {
+        OverlayStyle {
+            modifier: StyleModifier {
+                foreground: settings.selection_foreground,
+                background: settings.selection,
+                font_style: None,
+            },
+            ..OverlayStyle::default()
+        }
+    }
+
+    /// The theme's colors for matches of the current search
+    pub fn find_highlight(settings: &ThemeSettings) -> OverlayStyle {
+        OverlayStyle {
+            modifier: StyleModifier {
+                foreground: settings.find_highlight_foreground,
+                background: settings.find_highlight,
+                font_style: None,
+            },
+            ..OverlayStyle::default()
+        }
+    }
+
+    /// A squiggly underline in the theme's color for misspelled words, which suits
+    /// other diagnostics too
+    pub fn misspelling(settings: &ThemeSettings) -> OverlayStyle {
+        OverlayStyle::underline(UnderlineOption::SquigglyUnderline, settings.misspelling)
+    }
+
+    /// Just an underline
+    pub fn underline(underline: UnderlineOption, color: Option<Color>) -> OverlayStyle {
+        OverlayStyle {
+            underline: Some(underline),
+            underline_color: color,
+            ..OverlayStyle::default()
+        }
+    }
+}
+
+/// The style of a piece of text after the overlays have been applied.
+///
+/// `style` is usable on its own, its `FontStyle::UNDERLINE` is set if there is
+/// any kind of underline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverlaidStyle {
+    pub style: Style,
+    pub underline: UnderlineOption,
+    /// The color of the underline, `None` for the foreground color
+    pub underline_color: Option<Color>,
+}
+
+impl OverlaidStyle {
+    fn from_style(style: Style) -> OverlaidStyle {
+        let underline = if style.font_style.contains(FontStyle::UNDERLINE) {
+            UnderlineOption::Underline
+        } else {
+            UnderlineOption::None
+        };
+        OverlaidStyle { style, underline, underline_color: None }
+    }
+
+    fn apply(&mut self, overlay: &OverlayStyle) {
+        self.style = self.style.apply(overlay.modifier);
+        if let Some(underline) = overlay.underline {
+            self.underline = underline;
+            self.underline_color = overlay.underline_color;
+            if underline == UnderlineOption::None {
+                self.style.font_style.remove(FontStyle::UNDERLINE);
+            } else {
+                self.style.font_style.insert(FontStyle::UNDERLINE);
+            }
+        }
+    }
+}
+
+/// A set of byte ranges drawn in the same way
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlayLayer {
+    /// Layers with a higher priority are applied later, so they win where
+    /// layers change the same things. Layers with the same priority are applied
+    /// in the order they were added.
+    pub priority: i32,
+    pub style: OverlayStyle,
+    pub ranges: Vec<Range<usize>>,
+}
+
+/// Layers of ranges to apply to highlighted lines, see `Overlay::apply`.
+///
+/// This replaces calling `util::modify_range` once for every range, which splits
+/// the line again every time.
+///
+/// # Examples
+///
+/// ```
+/// use syntect::highlighting::{Overlay, OverlayStyle, Style, StyleModifier, Color, UnderlineOption};
+///
+/// let plain = Style::default();
+/// let red = Color { r: 0xff, g: 0, b: 0, a: 0xff };
+/// let blue = Color { r: 0, g: 0, b: 0xff, a: 0xff };
+///
+/// let mut overlay = Overlay::new();
+/// let selection = overlay.add_layer(10, OverlayStyle {
+///     modifier: StyleModifier { background: Some(blue), ..StyleModifier::default() },
+///     ..OverlayStyle::default()
+/// });
+/// overlay.add_range(selection, 2..5);
+/// let lint = overlay.add_layer(0, OverlayStyle::underline(UnderlineOption::SquigglyUnderline, Some(red)));
+/// overlay.add_range(lint, 0..3);
+///
+/// let regions = overlay.apply(&[(plain, "abc"), (plain, "def")]);
+/// let texts: Vec<&str> = regions.iter().map(|&(_, text)| text).collect();
+/// assert_eq!(texts, vec!["ab", "c", "de", "f"]);
+/// assert_eq!(regions[1].0.underline, UnderlineOption::SquigglyUnderline);
+/// assert_eq!(regions[1].0.style.background, blue);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Overlay {
+    pub layers: Vec<OverlayLayer>,
+}
+
+impl Overlay {
+    pub fn new() -> Overlay {
+        Overlay::default()
+    }
+
+    /// Adds an empty layer and returns its index in `layers`
+    pub fn add_layer(&mut self, priority: i32, style: OverlayStyle) -> usize {
+        self.layers.push(OverlayLayer {
+            priority,
+            style,
+            ranges: Vec::new(),
+        });
+        self.layers.len() - 1
+    }
+
+    /// Adds a byte range of the line to a layer. The ranges of a layer may overlap.
+    pub fn add_range(&mut self, layer: usize, range: Range<usize>) {
+        self.layers[layer].ranges.push(range);
+    }
+
+    /// Applies the layers to a highlighted line, as returned by `HighlightLines::highlight`,
+    /// in a single pass over the regions and the ends of the ranges.
+    ///
+    /// Regions are split where ranges start or end, which must be at character boundaries.
+    /// Empty regions are left out.
+    pub fn apply<'a>(&self, v: &[(Style, &'a str)]) -> Vec<(OverlaidStyle, &'a str)> {
+        let mut order: Vec<usize> = (0..self.layers.len()).collect();
+        order.sort_by_key(|&i| self.layers[i].priority);
+
+        // where ranges start and end, and which layer they belong to
+        let mut boundaries: Vec<(usize, usize, bool)> = Vec::new();
+        for (i, layer) in self.layers.iter().enumerate() {
+            for range in layer.ranges.iter().filter(|r| r.start < r.end) {
+                boundaries.push((range.start, i, true));
+                boundaries.push((range.end, i, false));
+            }
+        }
+        boundaries.sort_by_key(|&(pos, _, _)| pos);
+
+        // how many ranges of every layer cover the current position
+        let mut active = vec![0usize; self.layers.len()];
+        let mut next_boundary = 0;
+        let mut result = Vec::with_capacity(v.len() + boundaries.len());
+        let mut offset = 0;
+        for &(style, text) in v {
+            let end = offset + text.len();
+            let mut pos = offset;
+            while pos < end {
+                while next_boundary < boundaries.len() && boundaries[next_boundary].0 <= pos {
+                    let (_, layer, starts) = boundaries[next_boundary];
+                    if starts {
+                        active[layer] += 1;
+                    } else {
+                        active[layer] -= 1;
+                    }
+                    next_boundary += 1;
+                }
+                let next = boundaries.get(next_boundary).map_or(end, |&(p, _, _)| p.min(end));
+
+                let mut overlaid = OverlaidStyle::from_style(style);
+                for &i in order.iter().filter(|&&i| active[i] > 0) {
+                    overlaid.apply(&self.layers[i].style);
+                }
+                result.push((overlaid, &text[pos - offset..next - offset]));
+                pos = next;
+            }
+            offset = end;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn background(c: Color) -> OverlayStyle {
+        OverlayStyle {
+            modifier: StyleModifier { background: Some(c), ..StyleModifier::default() },
+            ..OverlayStyle::default()
+        }
+    }
+
+    #[test]
+    fn applies_layers_by_priority() {
+        let plain = Style::default();
+        let red = Color { r: 0xff, g: 0, b: 0, a: 0xff };
+        let blue = Color { r: 0, g: 0, b: 0xff, a: 0xff };
+        let mut overlay = Overlay::new();
+        let high = overlay.add_layer(5, background(blue));
+        let low = overlay.add_layer(1, background(red));
+        overlay.add_range(high, 3..4);
+        overlay.add_range(low, 1..6);
+        overlay.add_range(low, 2..3);
+        // empty and backwards ranges do nothing
+        overlay.add_range(high, 0..0);
+        overlay.add_range(high, Range { start: 6, end: 5 });
+
+        let regions = overlay.apply(&[(plain, "abc"), (plain, ""), (plain, "defgh")]);
+        let result: Vec<(Color, &str)> = regions.iter().map(|&(s, text)| (s.style.background, text)).collect();
+        assert_eq!(result, vec![
+            (Color::WHITE, "a"),
+            (red, "b"),
+            (red, "c"),
+            (blue, "d"),
+            (red, "ef"),
+            (Color::WHITE, "gh"),
+        ]);
+        assert!(regions.iter().all(|&(s, _)| s.underline == UnderlineOption::None));
+    }
+
+    #[test]
+    fn replaces_underlines() {
+        let underlined = Style { font_style: FontStyle::UNDERLINE | FontStyle::BOLD, ..Style::default() };
+        let green = Color { r: 0, g: 0xff, b: 0, a: 0xff };
+        let mut overlay = Overlay::new();
+        let squiggle = overlay.add_layer(0, OverlayStyle::underline(UnderlineOption::SquigglyUnderline, Some(green)));
+        overlay.add_range(squiggle, 0..2);
+        let none = overlay.add_layer(0, OverlayStyle::underline(UnderlineOption::None, None));
+        overlay.add_range(none, 1..3);
+
+        let regions = overlay.apply(&[(underlined, "abcd")]);
+        assert_eq!(regions.len(), 4);
+        assert_eq!((regions[0].0.underline, regions[0].0.underline_color), (UnderlineOption::SquigglyUnderline, Some(green)));
+        assert_eq!(regions[1].0.underline, UnderlineOption::None);
+        assert_eq!(regions[1].0.style.font_style, FontStyle::BOLD);
+        assert_eq!(regions[3], (OverlaidStyle {
+            style: underlined,
+            underline: UnderlineOption::Underline,
+            underline_color: None,
+        }, "d"));
+    }
+
+    #[test]
+    fn uses_theme_settings() {
+        let blue = Color { r: 0, g: 0, b: 0xff, a: 0xff };
+        let settings = ThemeSettings {
+            selection: Some(blue),
+            misspelling: Some(Color::BLACK),
+            ..ThemeSettings::default()
+        };
+        let mut overlay = Overlay::new();
+        let selection = overlay.add_layer(1, OverlayStyle::selection(&settings));
+        overlay.add_range(selection, 0..1);
+        let misspelling = overlay.add_layer(0, OverlayStyle::misspelling(&settings));
+        overlay.add_range(misspelling, 0..1);
+
+        let regions = overlay.apply(&[(Style::default(), "x")]);
+        assert_eq!(regions[0].0.style.background, blue);
+        assert_eq!(regions[0].0.style.foreground, Color::BLACK);
+        assert_eq!(regions[0].0.underline, UnderlineOption::SquigglyUnderline);
+        assert_eq!(regions[0].0.underline_color, Some(Color::BLACK));
+    }
+}
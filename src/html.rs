@@ -1,12 +1,17 @@
 //! Rendering highlighted code as HTML+CSS
 use std::fmt::Write;
-use parsing::{ScopeStackOp, BasicScopeStackOp, Scope, ScopeStack, SyntaxReference, ParseState, SyntaxSet, SCOPE_REPO};
+use parsing::{ScopeStackOp, Scope, ScopeStack, SyntaxReference, ParseState, SyntaxSet, SCOPE_REPO};
+use parsing::{HighlightEvent, push_line_events};
+use parsing::{CancellationToken, Cancelled};
 use easy::{HighlightLines, HighlightFile};
 use highlighting::{Color, FontStyle, Style, Theme};
 use util::LinesWithEndings;
 use escape::Escape;
+pub use util::IncludeBackground;
 use std::io::{self, BufRead};
 use std::path::Path;
+use std::error::Error;
+use std::fmt;
 
 /// Output HTML for a line of code with `<span>` elements using class names
 /// As this has to keep track of open and closed `<span>` tags, it is a `struct`
@@ -15,6 +20,9 @@ use std::path::Path;
 /// There is a `finalize()` function that has to be called in the end in order
 /// to close all open `<span>` tags.
 ///
+/// Unlike `tokens_to_classed_html` it keeps track of the scope stack across lines,
+/// so scopes cleared by a syntax are closed and reopened correctly.
+///
 /// The lines returned don't include a newline at the end.
 /// # Example
 ///
@@ -30,7 +38,7 @@ use std::path::Path;
 ///
 /// let syntax_set = SyntaxSet::load_defaults_newlines();
 /// let syntax = syntax_set.find_syntax_by_name("R").unwrap();
-/// let mut html_generator = ClassedHTMLGenerator::new(&syntax, &syntax_set);
+/// let mut html_generator = ClassedHTMLGenerator::new(&syntax, &syntax_set, None);
 /// for line in current_code.lines() {
 ///     html_generator.parse_html_for_line(&line);
 /// }
@@ -39,7 +47,7 @@ use std::path::Path;
 pub struct ClassedHTMLGenerator<'a> {
     syntax_set: &'a SyntaxSet,
     class_prefix: Option<&'a str>,
-    open_spans: usize,
+    stack: ScopeStack,
     parse_state: ParseState,
     html: String
 }
@@ -47,12 +55,12 @@ pub struct ClassedHTMLGenerator<'a> {
 impl<'a> ClassedHTMLGenerator<'a> {
     pub fn new(syntax_reference: &'a SyntaxReference, syntax_set: &'a SyntaxSet, class_prefix: Option<&'a str>) -> ClassedHTMLGenerator<'a> {
         let parse_state = ParseState::new(syntax_reference);
-        let open_spans = 0;
+        let stack = ScopeStack::new();
         let html = String::new();
         ClassedHTMLGenerator {
             syntax_set,
             class_prefix,
-            open_spans,
+            stack,
             parse_state,
             html
         }
@@ -61,50 +69,32 @@ impl<'a> ClassedHTMLGenerator<'a> {
     /// Parse the line of code and update the internal HTML buffer with tagged HTML
     pub fn parse_html_for_line(&mut self, line: &str) {
         let parsed_line = self.parse_state.parse_line(line, &self.syntax_set);
-        let formatted_line = self.tokens_to_classed_html(
-            line,
-            parsed_line.as_slice(),
-            ClassStyle::Spaced);
-        self.html.push_str(formatted_line.as_str());
+        self.append_classed_html(line, parsed_line.as_slice());
+    }
+
+    /// Like `parse_html_for_line` but gives up with `Cancelled` once `cancellation` is cancelled.
+    /// Nothing is added to the HTML buffer for a cancelled line.
+    pub fn parse_html_for_line_cancellable(&mut self,
+                                           line: &str,
+                                           cancellation: &CancellationToken)
+                                           -> Result<(), Cancelled> {
+        let (parsed_line, _) = self.parse_state.parse_line_cancellable(line, self.syntax_set, cancellation)?;
+        self.append_classed_html(line, parsed_line.as_slice());
+        Ok(())
     }
 
     /// Close all open `<span>` tags and return the finished HTML string
     pub fn finalize(mut self) -> String {
-        for _ in 0..self.open_spans {
+        for _ in 0..self.stack.len() {
             self.html.push_str("</span>");
         }
         self.html
     }
 
-    fn tokens_to_classed_html(&mut self, line: &str,
-                                  ops: &[(usize, ScopeStackOp)],
-                                  style: ClassStyle)
-                                  -> String {
-        let mut s = String::with_capacity(line.len() + ops.len() * 8); // a guess
-        let mut cur_index = 0;
-        let mut stack = ScopeStack::new();
-        for &(i, ref op) in ops {
-            if i > cur_index {
-                write!(s, "{}", Escape(&line[cur_index..i])).unwrap();
-                cur_index = i
-            }
-            stack.apply_with_hook(op, |basic_op, _| {
-                match basic_op {
-                    BasicScopeStackOp::Push(scope) => {
-                        s.push_str("<span class=\"");
-                        scope_to_classes(&mut s, scope, style, self.class_prefix);
-                        s.push_str("\">");
-                        self.open_spans += 1;
-                    }
-                    BasicScopeStackOp::Pop => {
-                        s.push_str("</span>");
-                        self.open_spans -= 1;
-                    }
-                }
-            });
-        }
-        write!(s, "{}", Escape(&line[cur_index..line.len()])).unwrap();
-        s
+    fn append_classed_html(&mut self, line: &str, ops: &[(usize, ScopeStackOp)]) {
+        let mut events = Vec::with_capacity(ops.len() + 1);
+        push_line_events(line, 0, ops, &mut self.stack, &mut events);
+        append_classed_html_for_events(line, events, ClassStyle::Spaced, self.class_prefix, &mut self.html);
     }
 }
 
@@ -178,6 +168,67 @@ pub fn highlighted_html_for_file<P: AsRef<Path>>(path: P,
     Ok(output)
 }
 
+/// Errors that can occur while highlighting a file with `highlighted_html_for_file_cancellable`
+#[derive(Debug)]
+pub enum HtmlForFileError {
+    /// the file couldn't be read
+    Io(io::Error),
+    /// the `CancellationToken` was cancelled before the whole file was highlighted
+    Cancelled,
+}
+
+impl From<io::Error> for HtmlForFileError {
+    fn from(error: io::Error) -> HtmlForFileError {
+        HtmlForFileError::Io(error)
+    }
+}
+
+impl From<Cancelled> for HtmlForFileError {
+    fn from(_: Cancelled) -> HtmlForFileError {
+        HtmlForFileError::Cancelled
+    }
+}
+
+impl fmt::Display for HtmlForFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HtmlForFileError::Io(ref error) => error.fmt(f),
+            HtmlForFileError::Cancelled => write!(f, "Highlighting was cancelled"),
+        }
+    }
+}
+
+impl Error for HtmlForFileError {
+    fn cause(&self) -> Option<&dyn Error> {
+        match *self {
+            HtmlForFileError::Io(ref error) => Some(error),
+            HtmlForFileError::Cancelled => None,
+        }
+    }
+}
+
+/// Like `highlighted_html_for_file` but stops reading and highlighting the file
+/// once `cancellation` is cancelled, in which case `HtmlForFileError::Cancelled` is returned.
+pub fn highlighted_html_for_file_cancellable<P: AsRef<Path>>(path: P,
+                                                             ss: &SyntaxSet,
+                                                             theme: &Theme,
+                                                             cancellation: &CancellationToken)
+                                                             -> Result<String, HtmlForFileError> {
+    let mut highlighter = HighlightFile::new(path, ss, theme)?;
+    let (mut output, bg) = start_highlighted_html_snippet(theme);
+
+    let mut line = String::new();
+    while highlighter.reader.read_line(&mut line)? > 0 {
+        {
+            let regions = highlighter.highlight_lines.highlight_cancellable(&line, ss, cancellation)?;
+            append_highlighted_html_for_styled_line(&regions[..], IncludeBackground::IfDifferent(bg), &mut output);
+        }
+        line.clear();
+    }
+    output.push_str("</pre>\n");
+    Ok(output)
+}
+
 /// Output HTML for a line of code with `<span>` elements
 /// specifying classes for each token. The span elements are nested
 /// like the scope stack and the scopes are mapped to classes based
@@ -191,39 +242,52 @@ pub fn tokens_to_classed_html(line: &str,
                               style: ClassStyle)
                               -> String {
     let mut s = String::with_capacity(line.len() + ops.len() * 8); // a guess
-    let mut cur_index = 0;
-    let mut stack = ScopeStack::new();
-    for &(i, ref op) in ops {
-        if i > cur_index {
-            write!(s, "{}", Escape(&line[cur_index..i])).unwrap();
-            cur_index = i
-        }
-        stack.apply_with_hook(op, |basic_op, _| {
-            match basic_op {
-                BasicScopeStackOp::Push(scope) => {
-                    s.push_str("<span class=\"");
-                    scope_to_classes(&mut s, scope, style, None);
-                    s.push_str("\">");
-                }
-                BasicScopeStackOp::Pop => {
-                    s.push_str("</span>");
-                }
-            }
-        });
-    }
-    write!(s, "{}", Escape(&line[cur_index..line.len()])).unwrap();
+    let mut events = Vec::with_capacity(ops.len() + 1);
+    push_line_events(line, 0, ops, &mut ScopeStack::new(), &mut events);
+    append_classed_html_for_events(line, events, style, None, &mut s);
     s
 }
 
-/// Determines how background color attributes are generated
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub enum IncludeBackground {
-    /// Don't include `background-color`, for performance or so that you can use your own background.
-    No,
-    /// Set background color attributes on every node
-    Yes,
-    /// Only set the `background-color` if it is different than the default (presumably set on a parent element)
-    IfDifferent(Color),
+/// Output HTML for `HighlightEvent`s with a `<span>` element for each scope, with
+/// classes based on the `ClassStyle` and prefixed with `class_prefix` if there is one.
+/// The `Source` ranges of the events index `text`.
+///
+/// # Examples
+///
+/// ```
+/// use syntect::html::{append_classed_html_for_events, ClassStyle};
+/// use syntect::parsing::{HighlightEventIterator, SyntaxSet};
+///
+/// let ss = SyntaxSet::load_defaults_newlines();
+/// let syntax = ss.find_syntax_by_name("R").unwrap();
+/// let text = "x + y\n";
+/// let mut html = String::new();
+/// let events = HighlightEventIterator::new(text, syntax, &ss);
+/// append_classed_html_for_events(text, events, ClassStyle::Spaced, None, &mut html);
+/// assert_eq!(html, "<span class=\"source r\">x <span class=\"keyword operator arithmetic r\">+</span> y\n</span>");
+/// ```
+pub fn append_classed_html_for_events<I>(text: &str,
+                                         events: I,
+                                         style: ClassStyle,
+                                         class_prefix: Option<&str>,
+                                         s: &mut String)
+    where I: IntoIterator<Item = HighlightEvent>
+{
+    for event in events {
+        match event {
+            HighlightEvent::Source(range) => {
+                write!(s, "{}", Escape(&text[range])).unwrap();
+            }
+            HighlightEvent::ScopeStart(scope) => {
+                s.push_str("<span class=\"");
+                scope_to_classes(s, scope, style, class_prefix);
+                s.push_str("\">");
+            }
+            HighlightEvent::ScopeEnd => {
+                s.push_str("</span>");
+            }
+        }
+    }
 }
 
 fn write_css_color(s: &mut String, c: Color) {
@@ -385,6 +449,27 @@ mod tests {
         assert_eq!(html3, include_str!("../testdata/test4.html"));
     }
 
+    #[test]
+    fn can_cancel_html_for_file() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let ts = ThemeSet::load_defaults();
+        let theme = &ts.themes["base16-ocean.dark"];
+        let token = CancellationToken::new();
+        let html = highlighted_html_for_file_cancellable("testdata/highlight_test.erb", &ss, theme, &token)
+            .unwrap();
+        assert_eq!(html, highlighted_html_for_file("testdata/highlight_test.erb", &ss, theme).unwrap());
+
+        token.cancel();
+        match highlighted_html_for_file_cancellable("testdata/highlight_test.erb", &ss, theme, &token) {
+            Err(HtmlForFileError::Cancelled) => {}
+            other => panic!("expected cancellation, got {:?}", other),
+        }
+        match highlighted_html_for_file_cancellable("testdata/missing.erb", &ss, theme, &token) {
+            Err(HtmlForFileError::Io(_)) => {}
+            other => panic!("expected an io error, got {:?}", other),
+        }
+    }
+
     #[test]
     fn tricky_test_syntax() {
         // This syntax I wrote tests edge cases of prototypes
@@ -406,7 +491,7 @@ mod tests {
         let current_code = "x + y".to_string();
         let syntax_set = SyntaxSet::load_defaults_newlines();
         let syntax = syntax_set.find_syntax_by_name("R").unwrap();
-        let mut html_generator = ClassedHTMLGenerator::new(&syntax, &syntax_set);
+        let mut html_generator = ClassedHTMLGenerator::new(syntax, &syntax_set, None);
         for line in current_code.lines() {
             html_generator.parse_html_for_line(&line);
         }
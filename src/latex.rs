@@ -0,0 +1,351 @@
+//! Rendering highlighted code as LaTeX, for typesetting it in PDFs.
+//!
+//! The output uses the `xcolor` package for colours and the `Verbatim` environment
+//! from the `fancyvrb` package, which keeps whitespace and line breaks as they are.
+//! Colours are referred to by name, so the preamble of the document has to define
+//! them with `latex_preamble`:
+//!
+//! ```
+//! use syntect::parsing::SyntaxSet;
+//! use syntect::highlighting::ThemeSet;
+//! use syntect::latex::{highlighted_latex_for_string, latex_preamble, LatexOptions};
+//!
+//! let ss = SyntaxSet::load_defaults_newlines();
+//! let ts = ThemeSet::load_defaults();
+//! let theme = &ts.themes["InspiredGitHub"];
+//! let syntax = ss.find_syntax_by_extension("rs").unwrap();
+//!
+//! let options = LatexOptions { line_numbers: true, ..LatexOptions::default() };
+//! let code = highlighted_latex_for_string("fn main() {}\n", &ss, syntax, theme, options);
+//! let document = format!("\\documentclass{{article}}\n{}\\begin{{document}}\n{}\\end{{document}}\n",
+//!                        latex_preamble(theme), code);
+//! assert!(document.contains("\\begin{Verbatim}[commandchars=\\\\\\{\\}"));
+//! ```
+use std::fmt::Write;
+use easy::HighlightLines;
+use highlighting::{Color, FontStyle, Style, Theme};
+use parsing::{SyntaxReference, SyntaxSet};
+use util::LinesWithEndings;
+
+/// Where the LaTeX is going to be used, which determines how characters are escaped
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LatexContext {
+    /// Inside a `Verbatim` environment with `commandchars=\\\{\}`, like the one from
+    /// `start_highlighted_latex_environment`, where only `\`, `{` and `}` are special.
+    Verbatim,
+    /// In normal text, like inside `\texttt{}`. All special characters are escaped,
+    /// spaces are kept with `~` and line breaks become `\newline`.
+    Text,
+}
+
+/// Options for the `Verbatim` environment
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LatexOptions {
+    /// Number the lines in the left margin
+    pub line_numbers: bool,
+    /// The number of the first line, when they are numbered
+    pub first_line_number: usize,
+}
+
+impl Default for LatexOptions {
+    fn default() -> LatexOptions {
+        LatexOptions {
+            line_numbers: false,
+            first_line_number: 1,
+        }
+    }
+}
+
+/// The name the colour is defined with in `latex_preamble`, like `syntectD08770`.
+/// The alpha channel is ignored.
+pub fn latex_color_name(c: Color) -> String {
+    format!("syntect{:02X}{:02X}{:02X}", c.r, c.g, c.b)
+}
+
+/// `\definecolor` commands for the colours, named with `latex_color_name`.
+///
+/// `latex_preamble` already defines all colours of the theme, this is for
+/// regions with colours that don't come from it.
+pub fn latex_color_definitions(colors: &[Color]) -> String {
+    let mut s = String::new();
+    for &c in colors {
+        writeln!(s, "\\definecolor{{{}}}{{HTML}}{{{:02X}{:02X}{:02X}}}", latex_color_name(c), c.r, c.g, c.b).unwrap();
+    }
+    s
+}
+
+/// Every colour the theme can give a `Style`, each once
+fn theme_colors(t: &Theme) -> Vec<Color> {
+    let mut colors = vec![t.settings.foreground.unwrap_or(Color::BLACK),
+                          t.settings.background.unwrap_or(Color::WHITE)];
+    for item in &t.scopes {
+        colors.extend(item.style.foreground);
+        colors.extend(item.style.background);
+    }
+    colors.sort_by_key(|c| (c.r, c.g, c.b));
+    colors.dedup_by_key(|c| (c.r, c.g, c.b));
+    colors
+}
+
+/// The lines to put in the preamble of a document: the packages the output needs,
+/// every colour of the theme and `syntectforeground` and `syntectbackground` for its
+/// default colours.
+///
+/// `fancyvrb` has no background colour, use `\pagecolor{syntectbackground}` or a
+/// package like `tcolorbox` if you want one.
+pub fn latex_preamble(t: &Theme) -> String {
+    let mut s = String::from("\\usepackage{xcolor}\n\\usepackage{fancyvrb}\n");
+    s.push_str(&latex_color_definitions(&theme_colors(t)));
+    writeln!(s, "\\colorlet{{syntectforeground}}{{{}}}",
+             latex_color_name(t.settings.foreground.unwrap_or(Color::BLACK))).unwrap();
+    writeln!(s, "\\colorlet{{syntectbackground}}{{{}}}",
+             latex_color_name(t.settings.background.unwrap_or(Color::WHITE))).unwrap();
+    s
+}
+
+/// Escapes the LaTeX special characters in `text` for the given context.
+///
+/// ```
+/// use syntect::latex::{escape_latex, LatexContext};
+///
+/// assert_eq!(escape_latex("a{b}\\", LatexContext::Verbatim), "a\\char123{}b\\char125{}\\char92{}");
+/// assert_eq!(escape_latex("50% & $x_1$", LatexContext::Text), "50\\%~\\&~\\$x\\_1\\$");
+/// ```
+pub fn escape_latex(text: &str, context: LatexContext) -> String {
+    let mut s = String::with_capacity(text.len());
+    write_escaped(&mut s, text, context);
+    s
+}
+
+fn write_escaped(s: &mut String, text: &str, context: LatexContext) {
+    for c in text.chars() {
+        let escaped = match (context, c) {
+            (_, '\n') => {
+                s.push_str(newline(context));
+                continue;
+            }
+            // in Verbatim these are the command characters
+            (LatexContext::Verbatim, '\\') => "\\char92{}",
+            (LatexContext::Verbatim, '{') => "\\char123{}",
+            (LatexContext::Verbatim, '}') => "\\char125{}",
+            (LatexContext::Verbatim, c) => {
+                s.push(c);
+                continue;
+            }
+            (LatexContext::Text, '\\') => "\\textbackslash{}",
+            (LatexContext::Text, '{') => "\\{",
+            (LatexContext::Text, '}') => "\\}",
+            (LatexContext::Text, '$') => "\\$",
+            (LatexContext::Text, '&') => "\\&",
+            (LatexContext::Text, '#') => "\\#",
+            (LatexContext::Text, '%') => "\\%",
+            (LatexContext::Text, '_') => "\\_",
+            (LatexContext::Text, '^') => "\\textasciicircum{}",
+            (LatexContext::Text, '~') => "\\textasciitilde{}",
+            (LatexContext::Text, ' ') => "~",
+            (LatexContext::Text, c) => {
+                s.push(c);
+                continue;
+            }
+        };
+        s.push_str(escaped);
+    }
+}
+
+fn newline(context: LatexContext) -> &'static str {
+    match context {
+        LatexContext::Verbatim => "\n",
+        LatexContext::Text => "\\newline\n",
+    }
+}
+
+/// Output LaTeX for a line of code, with `\textcolor`, `\textbf`, `\textit` and
+/// `\underline` for the style of each region. Adjacent regions with the same style
+/// are merged, and whitespace doesn't get any markup unless it's underlined.
+///
+/// The commands are closed before every line break, since `Verbatim` doesn't allow
+/// them to span lines. Background colours aren't included.
+///
+/// # Examples
+///
+/// ```
+/// use syntect::easy::HighlightLines;
+/// use syntect::parsing::SyntaxSet;
+/// use syntect::highlighting::ThemeSet;
+/// use syntect::latex::{styled_line_to_latex, LatexContext};
+///
+/// let ps = SyntaxSet::load_defaults_newlines();
+/// let ts = ThemeSet::load_defaults();
+///
+/// let syntax = ps.find_syntax_by_name("Ruby").unwrap();
+/// let mut h = HighlightLines::new(syntax, &ts.themes["base16-ocean.dark"]);
+/// let regions = h.highlight("5", &ps);
+/// let latex = styled_line_to_latex(&regions[..], LatexContext::Verbatim);
+/// assert_eq!(latex, "\\textcolor{syntectD08770}{5}");
+/// ```
+pub fn styled_line_to_latex(v: &[(Style, &str)], context: LatexContext) -> String {
+    let mut s = String::new();
+    append_latex_for_styled_line(v, context, &mut s);
+    s
+}
+
+/// Like `styled_line_to_latex` but appends to a `String` for increased efficiency.
+pub fn append_latex_for_styled_line(v: &[(Style, &str)], context: LatexContext, s: &mut String) {
+    let mut pending: Option<(Style, String)> = None;
+    for &(style, text) in v {
+        if let Some((pending_style, ref mut pending_text)) = pending {
+            if pending_style == style {
+                pending_text.push_str(text);
+                continue;
+            }
+        }
+        if let Some((pending_style, pending_text)) = pending.take() {
+            write_styled(s, pending_style, &pending_text, context);
+        }
+        pending = Some((style, text.to_owned()));
+    }
+    if let Some((pending_style, pending_text)) = pending {
+        write_styled(s, pending_style, &pending_text, context);
+    }
+}
+
+fn write_styled(s: &mut String, style: Style, text: &str, context: LatexContext) {
+    let underline = style.font_style.contains(FontStyle::UNDERLINE);
+    for (i, piece) in text.split('\n').enumerate() {
+        if i > 0 {
+            s.push_str(newline(context));
+        }
+        if piece.is_empty() {
+            continue;
+        }
+        if piece.trim().is_empty() && !underline {
+            write_escaped(s, piece, context);
+            continue;
+        }
+        let mut closing = 1;
+        write!(s, "\\textcolor{{{}}}{{", latex_color_name(style.foreground)).unwrap();
+        if style.font_style.contains(FontStyle::BOLD) {
+            s.push_str("\\textbf{");
+            closing += 1;
+        }
+        if style.font_style.contains(FontStyle::ITALIC) {
+            s.push_str("\\textit{");
+            closing += 1;
+        }
+        if underline {
+            s.push_str("\\underline{");
+            closing += 1;
+        }
+        write_escaped(s, piece, context);
+        for _ in 0..closing {
+            s.push('}');
+        }
+    }
+}
+
+/// Returns the `\begin{Verbatim}` line for the output of `styled_line_to_latex` with
+/// `LatexContext::Verbatim`, with the theme's foreground colour as the default.
+///
+/// You're responsible for ending it with `\end{Verbatim}` on its own line.
+pub fn start_highlighted_latex_environment(options: LatexOptions) -> String {
+    let mut s = String::from("\\begin{Verbatim}[commandchars=\\\\\\{\\},formatcom=\\color{syntectforeground}");
+    if options.line_numbers {
+        write!(s, ",numbers=left,firstnumber={}", options.first_line_number).unwrap();
+    }
+    s.push_str("]\n");
+    s
+}
+
+/// Convenience method that combines `start_highlighted_latex_environment`,
+/// `styled_line_to_latex` and `HighlightLines` from `syntect::easy` to create a
+/// `Verbatim` environment with the highlighted string (which can contain many lines).
+/// The colours it uses are defined by `latex_preamble`.
+///
+/// Note that the `syntax` passed in must be from a `SyntaxSet` compiled for newline characters.
+pub fn highlighted_latex_for_string(s: &str,
+                                    ss: &SyntaxSet,
+                                    syntax: &SyntaxReference,
+                                    theme: &Theme,
+                                    options: LatexOptions)
+                                    -> String {
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let mut output = start_highlighted_latex_environment(options);
+
+    for line in LinesWithEndings::from(s) {
+        let regions = highlighter.highlight(line, ss);
+        append_latex_for_styled_line(&regions[..], LatexContext::Verbatim, &mut output);
+    }
+    // \end{Verbatim} has to be on its own line
+    if !output.ends_with('\n') {
+        output.push('\n');
+    }
+    output.push_str("\\end{Verbatim}\n");
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use highlighting::ThemeSet;
+
+    fn style(foreground: Color, font_style: FontStyle) -> Style {
+        Style { foreground, background: Color::WHITE, font_style }
+    }
+
+    #[test]
+    fn escapes_special_characters() {
+        assert_eq!(escape_latex("#include <x> // 100% ~ ^_^", LatexContext::Verbatim),
+                   "#include <x> // 100% ~ ^_^");
+        assert_eq!(escape_latex("\\n{}", LatexContext::Verbatim), "\\char92{}n\\char123{}\\char125{}");
+        assert_eq!(escape_latex("#a ~^\\{}\n", LatexContext::Text),
+                   "\\#a~\\textasciitilde{}\\textasciicircum{}\\textbackslash{}\\{\\}\\newline\n");
+    }
+
+    #[test]
+    fn styles_regions() {
+        let red = Color { r: 0xff, g: 0, b: 0, a: 0xff };
+        let blue = Color { r: 0, g: 0, b: 0x80, a: 0xff };
+        let regions = [
+            (style(red, FontStyle::BOLD), "fn"),
+            (style(red, FontStyle::BOLD), "{"),
+            (style(blue, FontStyle::empty()), "  "),
+            (style(blue, FontStyle::ITALIC | FontStyle::UNDERLINE), "x\n"),
+            (style(blue, FontStyle::empty()), "}\n"),
+        ];
+        assert_eq!(styled_line_to_latex(&regions, LatexContext::Verbatim),
+                   "\\textcolor{syntectFF0000}{\\textbf{fn\\char123{}}}  \
+                    \\textcolor{syntect000080}{\\textit{\\underline{x}}}\n\
+                    \\textcolor{syntect000080}{\\char125{}}\n");
+    }
+
+    #[test]
+    fn preamble_defines_theme_colors() {
+        let ts = ThemeSet::load_defaults();
+        let theme = &ts.themes["base16-ocean.dark"];
+        let preamble = latex_preamble(theme);
+        assert!(preamble.starts_with("\\usepackage{xcolor}\n\\usepackage{fancyvrb}\n"));
+        assert!(preamble.contains("\\definecolor{syntectD08770}{HTML}{D08770}\n"));
+        assert!(preamble.contains("\\colorlet{syntectbackground}{syntect2B303B}\n"));
+        assert_eq!(preamble.matches("{syntectD08770}").count(), 1);
+    }
+
+    #[test]
+    fn strings() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let ts = ThemeSet::load_defaults();
+        let theme = &ts.themes["base16-ocean.dark"];
+        let syntax = ss.find_syntax_by_extension("rs").unwrap();
+        let options = LatexOptions { line_numbers: true, first_line_number: 10 };
+        let latex = highlighted_latex_for_string("let x = \"{}\";\nx", &ss, syntax, theme, options);
+        assert!(latex.starts_with("\\begin{Verbatim}[commandchars=\\\\\\{\\},formatcom=\\color{syntectforeground},\
+                                   numbers=left,firstnumber=10]\n"));
+        assert!(latex.contains("\\char123{}\\char125{}"));
+        assert!(latex.ends_with("}\n\\end{Verbatim}\n"));
+
+        // every colour used is defined in the preamble
+        let preamble = latex_preamble(theme);
+        for name in latex.split("\\textcolor{").skip(1).map(|rest| &rest[..rest.find('}').unwrap()]) {
+            assert!(preamble.contains(&format!("\\definecolor{{{}}}", name)), "{} is not defined", name);
+        }
+    }
+}
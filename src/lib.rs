@@ -49,11 +49,31 @@ pub mod parsing;
 pub mod util;
 #[cfg(any(feature = "dump-load-rs", feature = "dump-load", feature = "dump-create", feature = "dump-create-rs"))]
 pub mod dumps;
+#[cfg(any(feature = "dump-load-rs", feature = "dump-load", feature = "dump-create", feature = "dump-create-rs"))]
+pub mod packs;
 #[cfg(feature = "parsing")]
 pub mod easy;
+#[cfg(feature = "parsing")]
+pub mod syntax_test;
 #[cfg(feature = "html")]
 pub mod html;
+#[cfg(feature = "parsing")]
+pub mod latex;
+#[cfg(feature = "parsing")]
+pub mod rtf;
+#[cfg(feature = "parsing")]
+pub mod svg;
+#[cfg(feature = "parsing")]
+pub mod pango;
+#[cfg(feature = "parsing")]
+pub mod semantic_tokens;
+#[cfg(feature = "parsing")]
+pub mod token_dump;
+#[cfg(feature = "parsing")]
+pub mod diff;
 #[cfg(feature = "html")]
+pub mod markdown;
+#[cfg(feature = "parsing")]
 mod escape;
 
 use std::io::Error as IoError;
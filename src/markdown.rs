@@ -0,0 +1,211 @@
+//! Highlighting the fenced code blocks of Markdown documents as HTML.
+//!
+//! `highlight_fenced_code_blocks` replaces the fenced code blocks of a Markdown
+//! document with highlighted HTML and leaves everything else alone, so the result can
+//! be given to any Markdown renderer, since Markdown allows HTML blocks. If you already
+//! use a CommonMark parser, use `highlighted_html_for_code_block` on its code block
+//! events instead.
+//!
+//! Only fences at the top level of the document are found, not ones nested in block
+//! quotes or list items.
+//!
+//! ```
+//! use syntect::markdown::{highlight_fenced_code_blocks, CodeBlockHtml};
+//! use syntect::parsing::SyntaxSet;
+//!
+//! let ss = SyntaxSet::load_defaults_newlines();
+//! let markdown = "# Example\n\n```rs,ignore\nlet x = 5;\n```\n";
+//! let html = highlight_fenced_code_blocks(markdown, &ss, CodeBlockHtml::Classed(None));
+//! assert!(html.starts_with("# Example\n\n<pre><code class=\"language-rs\"><span class=\"source rust\">"));
+//! ```
+use std::fmt::Write;
+use escape::Escape;
+use highlighting::Theme;
+use html::{highlighted_html_for_string, ClassedHTMLGenerator};
+use parsing::{SyntaxReference, SyntaxSet};
+use util::LinesWithEndings;
+
+/// What kind of HTML code blocks become
+#[derive(Debug, Clone, Copy)]
+pub enum CodeBlockHtml<'a> {
+    /// `<span>` elements with classes for the scopes, like `ClassedHTMLGenerator`, with
+    /// an optional prefix for the classes. The block is a `<pre><code>` with a
+    /// `language-` class if the info string has a language.
+    Classed(Option<&'a str>),
+    /// `<span>` elements with inline styles from the theme, like `highlighted_html_for_string`
+    Inline(&'a Theme),
+}
+
+/// The language of a fenced code block's info string, like `rust` for `rust,ignore`
+/// or `python` for `{.python .numberLines}`
+pub fn language_from_info_string(info: &str) -> Option<&str> {
+    let info = info.trim();
+    let language = if info.starts_with('{') {
+        // Pandoc style attributes, where the first class is the language
+        info.trim_start_matches('{')
+            .trim_end_matches('}')
+            .split_whitespace()
+            .find(|attribute| attribute.starts_with('.'))
+            .map(|class| &class[1..])
+    } else {
+        info.split_whitespace().next()
+            .and_then(|word| word.split(&[',', '{'][..]).next())
+    };
+    language.and_then(|l| if l.is_empty() { None } else { Some(l) })
+}
+
+/// The syntax for a fenced code block's info string, found with `find_syntax_by_token`,
+/// or plain text if there's no language or it's unknown.
+pub fn syntax_for_info_string<'a>(info: &str, ss: &'a SyntaxSet) -> &'a SyntaxReference {
+    language_from_info_string(info)
+        .and_then(|language| ss.find_syntax_by_token(language))
+        .unwrap_or_else(|| ss.find_syntax_plain_text())
+}
+
+/// Highlights the contents of a code block with the syntax for its info string.
+///
+/// Note that the `SyntaxSet` must be compiled for newline characters.
+pub fn highlighted_html_for_code_block(info: &str, code: &str, ss: &SyntaxSet, html: CodeBlockHtml) -> String {
+    let syntax = syntax_for_info_string(info, ss);
+    match html {
+        CodeBlockHtml::Inline(theme) => highlighted_html_for_string(code, ss, syntax, theme),
+        CodeBlockHtml::Classed(class_prefix) => {
+            let mut generator = ClassedHTMLGenerator::new(syntax, ss, class_prefix);
+            for line in LinesWithEndings::from(code) {
+                generator.parse_html_for_line(line);
+            }
+            let mut s = String::from("<pre><code");
+            if let Some(language) = language_from_info_string(info) {
+                write!(s, " class=\"language-{}\"", Escape(language)).unwrap();
+            }
+            s.push('>');
+            s.push_str(&generator.finalize());
+            s.push_str("</code></pre>\n");
+            s
+        }
+    }
+}
+
+/// An opening or closing code fence
+struct Fence<'a> {
+    indent: usize,
+    marker: char,
+    len: usize,
+    info: &'a str,
+}
+
+/// Parses a line as a code fence: up to 3 spaces, at least 3 backticks or tildes and
+/// the info string. Info strings of backtick fences can't contain backticks.
+fn parse_fence(line: &str) -> Option<Fence<'_>> {
+    let rest = line.trim_start_matches(' ');
+    let indent = line.len() - rest.len();
+    let marker = rest.chars().next()?;
+    if indent > 3 || (marker != '`' && marker != '~') {
+        return None;
+    }
+    let info = rest.trim_start_matches(marker);
+    let len = rest.len() - info.len();
+    if len < 3 || (marker == '`' && info.contains('`')) {
+        return None;
+    }
+    Some(Fence { indent, marker, len, info: info.trim() })
+}
+
+/// Replaces every fenced code block in a Markdown document with highlighted HTML,
+/// see the module docs. A block that isn't closed goes on to the end of the document.
+///
+/// Note that the `SyntaxSet` must be compiled for newline characters.
+pub fn highlight_fenced_code_blocks(markdown: &str, ss: &SyntaxSet, html: CodeBlockHtml) -> String {
+    let mut output = String::with_capacity(markdown.len());
+    let mut lines = LinesWithEndings::from(markdown);
+    while let Some(line) = lines.next() {
+        let opening = match parse_fence(line) {
+            Some(fence) => fence,
+            None => {
+                output.push_str(line);
+                continue;
+            }
+        };
+        let mut code = String::new();
+        for line in lines.by_ref() {
+            let closed = match parse_fence(line) {
+                Some(fence) => fence.marker == opening.marker && fence.len >= opening.len && fence.info.is_empty(),
+                None => false,
+            };
+            if closed {
+                break;
+            }
+            // the contents are unindented by as much as the opening fence is
+            let unindented = line.trim_start_matches(' ');
+            let spaces = line.len() - unindented.len();
+            code.push_str(&line[spaces.min(opening.indent)..]);
+        }
+        output.push_str(&highlighted_html_for_code_block(opening.info, &code, ss, html));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use highlighting::ThemeSet;
+
+    #[test]
+    fn parses_info_strings() {
+        assert_eq!(language_from_info_string("rust"), Some("rust"));
+        assert_eq!(language_from_info_string(" rust,ignore "), Some("rust"));
+        assert_eq!(language_from_info_string("python title=\"x.py\""), Some("python"));
+        assert_eq!(language_from_info_string("{.python .numberLines startFrom=\"10\"}"), Some("python"));
+        assert_eq!(language_from_info_string("{ #id .rb }"), Some("rb"));
+        assert_eq!(language_from_info_string("js{1,3}"), Some("js"));
+        assert_eq!(language_from_info_string("{#id}"), None);
+        assert_eq!(language_from_info_string(""), None);
+    }
+
+    #[test]
+    fn parses_fences() {
+        let fence = parse_fence("  ~~~~ rust\n").unwrap();
+        assert_eq!((fence.indent, fence.marker, fence.len, fence.info), (2, '~', 4, "rust"));
+        assert!(parse_fence("``\n").is_none());
+        assert!(parse_fence("    ```\n").is_none());
+        assert!(parse_fence("``` a`b\n").is_none());
+        assert!(parse_fence("~~~ a`b\n").is_some());
+    }
+
+    #[test]
+    fn replaces_code_blocks() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let markdown = "Text with `code`.\n\
+                        \n\
+                        ~~~~ {.nosuchlanguage}\n\
+                        ~~~\n\
+                        ~~~~~\n\
+                        \x20``` rs,ignore\n\
+                        \x20\x20let x = 5;\n\
+                        \x20```\n\
+                        After\n\
+                        ```\n\
+                        <b>\n";
+        let html = highlight_fenced_code_blocks(markdown, &ss, CodeBlockHtml::Classed(Some("hl-")));
+        // unknown languages are plain text, and shorter fences are part of the code
+        assert!(html.starts_with("Text with `code`.\n\n\
+                                  <pre><code class=\"language-nosuchlanguage\"><span class=\"hl-text hl-plain\">~~~\n</span></code></pre>\n\
+                                  <pre><code class=\"language-rs\"><span class=\"hl-source hl-rust\">"));
+        // the contents are unindented like the fence
+        assert!(html.contains("\"> <span class=\"hl-storage"));
+        assert!(html.contains("</code></pre>\nAfter\n"));
+        // unclosed blocks go on to the end
+        assert!(html.ends_with("<pre><code><span class=\"hl-text hl-plain\">&lt;b&gt;\n</span></code></pre>\n"));
+    }
+
+    #[test]
+    fn inline_styles() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let ts = ThemeSet::load_defaults();
+        let html = highlight_fenced_code_blocks("```rs\nfn x() {}\n```\n", &ss,
+                                                CodeBlockHtml::Inline(&ts.themes["base16-ocean.dark"]));
+        assert!(html.starts_with("<pre style=\"background-color:#2b303b;\">\n"));
+        assert!(html.ends_with("</pre>\n"));
+        assert!(!html.contains("```"));
+    }
+}
@@ -0,0 +1,216 @@
+//! Helpers for compiling your own syntax and theme packs in a build script and
+//! embedding them in your binary, the same way syntect embeds its default packs.
+//!
+//! In `build.rs`, with the `dump-create` feature enabled for the build dependency:
+//!
+//! ```ignore
+//! extern crate syntect;
+//!
+//! fn main() {
+//!     syntect::packs::compile_syntaxes("grammars", true, "grammars.packdump").unwrap();
+//!     syntect::packs::compile_themes("themes", "themes.themedump").unwrap();
+//! }
+//! ```
+//!
+//! Then in your crate, with the `dump-load` feature enabled:
+//!
+//! ```ignore
+//! #[macro_use]
+//! extern crate syntect;
+//!
+//! let syntax_set = include_syntax_set!("grammars.packdump");
+//! let theme_set = include_theme_set!("themes.themedump");
+//! ```
+//!
+//! The folders are searched recursively for `.sublime-syntax`, `.tmPreferences` and
+//! `.tmTheme` files, and Cargo is told to run the build script again when they change.
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+use bincode;
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+use dumps::dump_to_file;
+#[cfg(all(feature = "yaml-load", any(feature = "dump-create", feature = "dump-create-rs")))]
+use dumps::{dump_to_file_with_info, DumpInfo};
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+use highlighting::ThemeSet;
+#[cfg(all(feature = "yaml-load", any(feature = "dump-create", feature = "dump-create-rs")))]
+use parsing::SyntaxSetBuilder;
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+use std::env;
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+use std::error::Error;
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+use std::fmt;
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+use std::path::{Path, PathBuf};
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+use LoadingError;
+
+/// Error type for compiling packs
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+#[derive(Debug)]
+pub enum PackError {
+    /// the `OUT_DIR` environment variable isn't set, which means this isn't
+    /// running in a build script
+    OutDirNotSet,
+    /// the syntaxes or themes couldn't be loaded
+    Loading(LoadingError),
+    /// the dump couldn't be written
+    Dump(bincode::Error),
+}
+
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+impl From<LoadingError> for PackError {
+    fn from(error: LoadingError) -> PackError {
+        PackError::Loading(error)
+    }
+}
+
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+impl From<bincode::Error> for PackError {
+    fn from(error: bincode::Error) -> PackError {
+        PackError::Dump(error)
+    }
+}
+
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::PackError::*;
+
+        match *self {
+            OutDirNotSet => write!(f, "OUT_DIR is not set, packs can only be compiled in a build script"),
+            Loading(ref error) => write!(f, "Error loading pack: {}", error),
+            Dump(ref error) => write!(f, "Error writing pack: {}", error),
+        }
+    }
+}
+
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+impl Error for PackError {
+    fn cause(&self) -> Option<&dyn Error> {
+        use self::PackError::*;
+
+        match *self {
+            Loading(ref error) => Some(error),
+            Dump(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Compiles the syntaxes and metadata in `folder` into `$OUT_DIR/<name>`, to be
+/// embedded with `include_syntax_set!(name)`.
+///
+/// The plain text syntax is always included. See `SyntaxSetBuilder::add_from_folder`
+/// for what `lines_include_newline` means; the lines you parse with the embedded set
+/// have to match it.
+#[cfg(all(feature = "yaml-load", any(feature = "dump-create", feature = "dump-create-rs")))]
+pub fn compile_syntaxes<P: AsRef<Path>>(folder: P,
+                                        lines_include_newline: bool,
+                                        name: &str)
+                                        -> Result<(), PackError> {
+    let out_file = out_dir_file(name)?;
+    rerun_if_changed(folder.as_ref());
+    dump_syntax_folder(folder, lines_include_newline, out_file)
+}
+
+/// Compiles the themes in `folder` into `$OUT_DIR/<name>`, to be embedded
+/// with `include_theme_set!(name)`.
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+pub fn compile_themes<P: AsRef<Path>>(folder: P, name: &str) -> Result<(), PackError> {
+    let out_file = out_dir_file(name)?;
+    rerun_if_changed(folder.as_ref());
+    dump_theme_folder(folder, out_file)
+}
+
+/// Same as `compile_syntaxes`, but writes the dump to any path.
+/// The dump can be loaded with `dumps::from_dump_file`.
+#[cfg(all(feature = "yaml-load", any(feature = "dump-create", feature = "dump-create-rs")))]
+pub fn dump_syntax_folder<P: AsRef<Path>, Q: AsRef<Path>>(folder: P,
+                                                          lines_include_newline: bool,
+                                                          out_file: Q)
+                                                          -> Result<(), PackError> {
+    let mut builder = SyntaxSetBuilder::new();
+    builder.add_plain_text_syntax();
+    builder.add_from_folder(folder, lines_include_newline)?;
+    let info = DumpInfo { lines_include_newline: Some(lines_include_newline) };
+    dump_to_file_with_info(&builder.build(), info, out_file)?;
+    Ok(())
+}
+
+/// Same as `compile_themes`, but writes the dump to any path.
+/// The dump can be loaded with `dumps::from_dump_file`.
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+pub fn dump_theme_folder<P: AsRef<Path>, Q: AsRef<Path>>(folder: P, out_file: Q) -> Result<(), PackError> {
+    let theme_set = ThemeSet::load_from_folder(folder)?;
+    dump_to_file(&theme_set, out_file)?;
+    Ok(())
+}
+
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+fn out_dir_file(name: &str) -> Result<PathBuf, PackError> {
+    let out_dir = env::var_os("OUT_DIR").ok_or(PackError::OutDirNotSet)?;
+    Ok(Path::new(&out_dir).join(name))
+}
+
+#[cfg(any(feature = "dump-create", feature = "dump-create-rs"))]
+fn rerun_if_changed(folder: &Path) {
+    // Cargo checks every file in a folder for changes
+    println!("cargo:rerun-if-changed={}", folder.display());
+}
+
+/// Loads a `SyntaxSet` compiled with `packs::compile_syntaxes` in a build script.
+///
+/// The dump is embedded in the binary with `include_bytes!`, so this requires
+/// the `dump-load` or `dump-load-rs` feature.
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+#[macro_export]
+macro_rules! include_syntax_set {
+    ($name:expr) => {
+        $crate::dumps::from_binary::<$crate::parsing::SyntaxSet>(
+            include_bytes!(concat!(env!("OUT_DIR"), "/", $name)))
+    };
+}
+
+/// Loads a `ThemeSet` compiled with `packs::compile_themes` in a build script.
+///
+/// The dump is embedded in the binary with `include_bytes!`, so this requires
+/// the `dump-load` or `dump-load-rs` feature.
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+#[macro_export]
+macro_rules! include_theme_set {
+    ($name:expr) => {
+        $crate::dumps::from_binary::<$crate::highlighting::ThemeSet>(
+            include_bytes!(concat!(env!("OUT_DIR"), "/", $name)))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(all(feature = "yaml-load", any(feature = "dump-create", feature = "dump-create-rs"), any(feature = "dump-load", feature = "dump-load-rs")))]
+    #[test]
+    fn can_compile_and_load_packs() {
+        use super::*;
+        use dumps::{from_dump_file, from_reader_with_info};
+        use parsing::SyntaxSet;
+        use std::fs::{self, File};
+        use std::io::BufReader;
+
+        let dir = env::temp_dir().join(format!("syntect-packs-test-{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let syntax_file = dir.join("test.packdump");
+        let theme_file = dir.join("test.themedump");
+
+        dump_syntax_folder("testdata/Packages/Rust", true, &syntax_file).unwrap();
+        let (ss, info): (SyntaxSet, DumpInfo) = from_reader_with_info(BufReader::new(File::open(&syntax_file).unwrap())).unwrap();
+        assert_eq!(info.lines_include_newline, Some(true));
+        assert!(ss.find_syntax_by_extension("rs").is_some());
+        assert!(ss.find_syntax_by_name("Plain Text").is_some());
+
+        dump_theme_folder("testdata/spacegray", &theme_file).unwrap();
+        let ts: ThemeSet = from_dump_file(&theme_file).unwrap();
+        assert!(ts.themes.contains_key("base16-ocean.dark"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,196 @@
+//! Rendering highlighted code as [Pango markup](https://developer.gnome.org/pango/stable/PangoMarkupFormat.html),
+//! for GTK labels and text views.
+//!
+//! For a `GtkTextBuffer` it's usually better to create a `GtkTextTag` for every style
+//! once and apply the tags to ranges of the buffer, `distinct_styles` and
+//! `text_tag_name` help with that.
+//!
+//! ```
+//! use syntect::parsing::SyntaxSet;
+//! use syntect::highlighting::ThemeSet;
+//! use syntect::pango::highlighted_pango_markup_for_string;
+//!
+//! let ss = SyntaxSet::load_defaults_newlines();
+//! let ts = ThemeSet::load_defaults();
+//! let syntax = ss.find_syntax_by_extension("rs").unwrap();
+//! let markup = highlighted_pango_markup_for_string("let x = 5;\n", &ss, syntax,
+//!                                                  &ts.themes["base16-ocean.dark"]);
+//! assert!(markup.contains("<span foreground=\"#d08770\">5</span>"));
+//! ```
+use std::fmt::Write;
+use easy::HighlightLines;
+use escape::Escape;
+use highlighting::{Color, FontStyle, Style, Theme, UnderlineOption};
+use parsing::{SyntaxReference, SyntaxSet};
+use util::{IncludeBackground, LinesWithEndings};
+
+/// The value of Pango's `underline` attribute for a theme's `UnderlineOption`.
+///
+/// Pango has no stippled underline, so it becomes `low`, which is drawn below descenders.
+/// Squiggly underlines become `error`, which is how spelling mistakes are shown.
+pub fn pango_underline(option: UnderlineOption) -> &'static str {
+    match option {
+        UnderlineOption::None => "none",
+        UnderlineOption::Underline => "single",
+        UnderlineOption::StippledUnderline => "low",
+        UnderlineOption::SquigglyUnderline => "error",
+    }
+}
+
+/// Writes a colour attribute and, if the colour isn't opaque, its alpha attribute
+fn write_pango_color(s: &mut String, attr: &str, alpha_attr: &str, c: Color) {
+    write!(s, " {}=\"#{:02x}{:02x}{:02x}\"", attr, c.r, c.g, c.b).unwrap();
+    if c.a != 0xFF {
+        write!(s, " {}=\"{}%\"", alpha_attr, (c.a as u32 * 100 + 127) / 255).unwrap();
+    }
+}
+
+/// Output Pango markup for a line of code with a `<span>` element for every style.
+/// The `bg` attribute determines if the spans will have the `background` attribute set,
+/// see the `IncludeBackground` enum's docs.
+///
+/// Newlines are kept, which Pango shows as line breaks.
+pub fn styled_line_to_pango_markup(v: &[(Style, &str)], bg: IncludeBackground) -> String {
+    let mut s = String::new();
+    append_pango_markup_for_styled_line(v, bg, &mut s);
+    s
+}
+
+/// Like `styled_line_to_pango_markup` but appends to a `String` for increased efficiency.
+pub fn append_pango_markup_for_styled_line(v: &[(Style, &str)], bg: IncludeBackground, s: &mut String) {
+    let mut prev_style: Option<&Style> = None;
+    for &(ref style, text) in v.iter() {
+        if prev_style == Some(style) {
+            write!(s, "{}", Escape(text)).unwrap();
+            continue;
+        }
+        if prev_style.is_some() {
+            s.push_str("</span>");
+        }
+        prev_style = Some(style);
+        s.push_str("<span");
+        write_pango_color(s, "foreground", "fgalpha", style.foreground);
+        let include_bg = match bg {
+            IncludeBackground::Yes => true,
+            IncludeBackground::No => false,
+            IncludeBackground::IfDifferent(c) => style.background != c,
+        };
+        if include_bg {
+            write_pango_color(s, "background", "bgalpha", style.background);
+        }
+        if style.font_style.contains(FontStyle::BOLD) {
+            s.push_str(" weight=\"bold\"");
+        }
+        if style.font_style.contains(FontStyle::ITALIC) {
+            s.push_str(" style=\"italic\"");
+        }
+        if style.font_style.contains(FontStyle::UNDERLINE) {
+            write!(s, " underline=\"{}\"", pango_underline(UnderlineOption::Underline)).unwrap();
+        }
+        write!(s, ">{}", Escape(text)).unwrap();
+    }
+    if prev_style.is_some() {
+        s.push_str("</span>");
+    }
+}
+
+/// Returns every `Style` used in the highlighted lines once, in order of first use,
+/// so that a `GtkTextTag` can be created for each of them.
+pub fn distinct_styles(lines: &[Vec<(Style, &str)>]) -> Vec<Style> {
+    let mut styles: Vec<Style> = Vec::new();
+    for &(style, _) in lines.iter().flat_map(|line| line.iter()) {
+        if !styles.contains(&style) {
+            styles.push(style);
+        }
+    }
+    styles
+}
+
+/// A name for the `GtkTextTag` of a style, which is the same for equal styles.
+///
+/// The name looks like `syntect-d08770ff-2b303bff-1`, the foreground and background
+/// colours followed by the bits of the font style.
+pub fn text_tag_name(style: &Style) -> String {
+    let (f, b) = (style.foreground, style.background);
+    format!("syntect-{:02x}{:02x}{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}-{}",
+            f.r, f.g, f.b, f.a, b.r, b.g, b.b, b.a, style.font_style.bits())
+}
+
+/// Convenience method that combines `append_pango_markup_for_styled_line` and
+/// `HighlightLines` from `syntect::easy` to create Pango markup for a string
+/// (which can contain many lines).
+///
+/// Backgrounds are only set where they differ from the theme's, which is left to
+/// the widget showing the markup.
+///
+/// Note that the `syntax` passed in must be from a `SyntaxSet` compiled for newline characters.
+pub fn highlighted_pango_markup_for_string(s: &str, ss: &SyntaxSet, syntax: &SyntaxReference, theme: &Theme) -> String {
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let bg = theme.settings.background.unwrap_or(Color::WHITE);
+    let mut output = String::new();
+    for line in LinesWithEndings::from(s) {
+        let regions = highlighter.highlight(line, ss);
+        append_pango_markup_for_styled_line(&regions[..], IncludeBackground::IfDifferent(bg), &mut output);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use highlighting::ThemeSet;
+
+    fn style(foreground: Color, background: Color, font_style: FontStyle) -> Style {
+        Style { foreground, background, font_style }
+    }
+
+    #[test]
+    fn maps_underline_options() {
+        assert_eq!(pango_underline(UnderlineOption::None), "none");
+        assert_eq!(pango_underline(UnderlineOption::Underline), "single");
+        assert_eq!(pango_underline(UnderlineOption::StippledUnderline), "low");
+        assert_eq!(pango_underline(UnderlineOption::SquigglyUnderline), "error");
+    }
+
+    #[test]
+    fn writes_spans() {
+        let red = Color { r: 0xff, g: 0, b: 0, a: 0xff };
+        let faint = Color { r: 0, g: 0, b: 0, a: 0x80 };
+        let v = [
+            (style(red, Color::WHITE, FontStyle::BOLD | FontStyle::ITALIC), "a <"),
+            (style(red, Color::WHITE, FontStyle::BOLD | FontStyle::ITALIC), " b"),
+            (style(red, faint, FontStyle::UNDERLINE), "&\n"),
+        ];
+        assert_eq!(styled_line_to_pango_markup(&v, IncludeBackground::IfDifferent(Color::WHITE)),
+                   "<span foreground=\"#ff0000\" weight=\"bold\" style=\"italic\">a &lt; b</span>\
+                    <span foreground=\"#ff0000\" background=\"#000000\" bgalpha=\"50%\" underline=\"single\">&amp;\n</span>");
+        assert_eq!(styled_line_to_pango_markup(&v[..1], IncludeBackground::Yes),
+                   "<span foreground=\"#ff0000\" background=\"#ffffff\" weight=\"bold\" style=\"italic\">a &lt;</span>");
+        assert_eq!(styled_line_to_pango_markup(&[], IncludeBackground::No), "");
+    }
+
+    #[test]
+    fn lists_distinct_styles() {
+        let red = style(Color { r: 0xff, g: 0, b: 0, a: 0xff }, Color::WHITE, FontStyle::empty());
+        let bold = style(Color::BLACK, Color::WHITE, FontStyle::BOLD);
+        let lines = vec![
+            vec![(red, "a"), (bold, "b"), (red, "c")],
+            vec![(bold, "d"), (red, "e")],
+        ];
+        assert_eq!(distinct_styles(&lines), vec![red, bold]);
+        assert_eq!(text_tag_name(&red), "syntect-ff0000ff-ffffffff-0");
+        assert_eq!(text_tag_name(&bold), "syntect-000000ff-ffffffff-1");
+    }
+
+    #[test]
+    fn strings() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let ts = ThemeSet::load_defaults();
+        let syntax = ss.find_syntax_by_extension("rs").unwrap();
+        let markup = highlighted_pango_markup_for_string("let x = 5;\nlet y = x;\n", &ss, syntax,
+                                                         &ts.themes["base16-ocean.dark"]);
+        assert!(markup.contains("<span foreground=\"#d08770\">5</span>"));
+        assert!(!markup.contains("background="));
+        assert_eq!(markup.matches('\n').count(), 2);
+    }
+}
@@ -0,0 +1,193 @@
+//! Highlighting as a stream of events, for renderers that nest elements like the scopes.
+use std::collections::VecDeque;
+use std::ops::Range;
+use parsing::{BasicScopeStackOp, ParseState, Scope, ScopeStack, ScopeStackOp, SyntaxReference, SyntaxSet};
+use util::LinesWithEndings;
+
+/// Something that happens while going through highlighted text.
+///
+/// Every `ScopeStart` has a matching `ScopeEnd`, so a renderer can open an element for
+/// each `ScopeStart` and close the innermost open one for each `ScopeEnd`. Scopes that
+/// are cleared by `clear_scopes` in a syntax end when they're cleared and start again
+/// when they're restored, so renderers never have to know about clearing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HighlightEvent {
+    /// Text inside all the scopes that have started and not ended, as a byte range
+    Source(Range<usize>),
+    /// A scope starts, inside all the scopes that have started and not ended
+    ScopeStart(Scope),
+    /// The innermost scope ends
+    ScopeEnd,
+}
+
+/// Adds the events for a line, given the operations `ParseState::parse_line` returned
+/// for it and the scope stack from the end of the previous line, which is updated.
+///
+/// The `Source` ranges are offset by `offset`, which is where the line starts in the text
+/// the ranges should index.
+pub fn push_line_events(line: &str,
+                        offset: usize,
+                        ops: &[(usize, ScopeStackOp)],
+                        stack: &mut ScopeStack,
+                        events: &mut Vec<HighlightEvent>) {
+    let mut cur_index = 0;
+    for &(i, ref op) in ops {
+        if i > cur_index {
+            events.push(HighlightEvent::Source(offset + cur_index..offset + i));
+            cur_index = i;
+        }
+        stack.apply_with_hook(op, |basic_op, _| {
+            events.push(match basic_op {
+                BasicScopeStackOp::Push(scope) => HighlightEvent::ScopeStart(scope),
+                BasicScopeStackOp::Pop => HighlightEvent::ScopeEnd,
+            });
+        });
+    }
+    if line.len() > cur_index {
+        events.push(HighlightEvent::Source(offset + cur_index..offset + line.len()));
+    }
+}
+
+/// Parses a whole document and yields its `HighlightEvent`s, with `Source` ranges
+/// that index the document. The scopes still open at the end of the document are ended,
+/// so the events are always balanced.
+///
+/// Lines are parsed as they are needed. Note that the `syntax` passed in must be
+/// from a `SyntaxSet` compiled for newline characters.
+///
+/// ```
+/// use syntect::parsing::{HighlightEvent, HighlightEventIterator, SyntaxSet};
+///
+/// let ss = SyntaxSet::load_defaults_newlines();
+/// let syntax = ss.find_syntax_by_extension("rs").unwrap();
+/// let text = "fn main() {}\n";
+///
+/// let mut depth = 0;
+/// let mut source = String::new();
+/// for event in HighlightEventIterator::new(text, syntax, &ss) {
+///     match event {
+///         HighlightEvent::ScopeStart(_) => depth += 1,
+///         HighlightEvent::ScopeEnd => depth -= 1,
+///         HighlightEvent::Source(range) => source.push_str(&text[range]),
+///     }
+/// }
+/// assert_eq!(depth, 0);
+/// assert_eq!(source, text);
+/// ```
+pub struct HighlightEventIterator<'a> {
+    syntax_set: &'a SyntaxSet,
+    parse_state: ParseState,
+    stack: ScopeStack,
+    lines: LinesWithEndings<'a>,
+    offset: usize,
+    events: VecDeque<HighlightEvent>,
+    done: bool,
+}
+
+impl<'a> HighlightEventIterator<'a> {
+    pub fn new(text: &'a str, syntax: &SyntaxReference, syntax_set: &'a SyntaxSet) -> HighlightEventIterator<'a> {
+        HighlightEventIterator {
+            syntax_set,
+            parse_state: ParseState::new(syntax),
+            stack: ScopeStack::new(),
+            lines: LinesWithEndings::from(text),
+            offset: 0,
+            events: VecDeque::new(),
+            done: false,
+        }
+    }
+}
+
+impl<'a> Iterator for HighlightEventIterator<'a> {
+    type Item = HighlightEvent;
+
+    fn next(&mut self) -> Option<HighlightEvent> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(event);
+            }
+            if self.done {
+                return None;
+            }
+            match self.lines.next() {
+                Some(line) => {
+                    let ops = self.parse_state.parse_line(line, self.syntax_set);
+                    let mut events = Vec::new();
+                    push_line_events(line, self.offset, &ops, &mut self.stack, &mut events);
+                    self.events.extend(events);
+                    self.offset += line.len();
+                }
+                None => {
+                    for _ in 0..self.stack.len() {
+                        self.events.push_back(HighlightEvent::ScopeEnd);
+                    }
+                    self.done = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parsing::ClearAmount;
+
+    #[test]
+    fn normalises_clears() {
+        let a = Scope::new("a").unwrap();
+        let b = Scope::new("b").unwrap();
+        let c = Scope::new("c").unwrap();
+        let mut stack = ScopeStack::new();
+        let mut events = Vec::new();
+        let ops = vec![
+            (0, ScopeStackOp::Push(a)),
+            (0, ScopeStackOp::Push(b)),
+            (1, ScopeStackOp::Clear(ClearAmount::All)),
+            (1, ScopeStackOp::Push(c)),
+            (2, ScopeStackOp::Pop(1)),
+            (2, ScopeStackOp::Restore),
+            (4, ScopeStackOp::Pop(1)),
+        ];
+        push_line_events("xyzw\n", 10, &ops, &mut stack, &mut events);
+        assert_eq!(events, vec![
+            HighlightEvent::ScopeStart(a),
+            HighlightEvent::ScopeStart(b),
+            HighlightEvent::Source(10..11),
+            HighlightEvent::ScopeEnd,
+            HighlightEvent::ScopeEnd,
+            HighlightEvent::ScopeStart(c),
+            HighlightEvent::Source(11..12),
+            HighlightEvent::ScopeEnd,
+            HighlightEvent::ScopeStart(a),
+            HighlightEvent::ScopeStart(b),
+            HighlightEvent::Source(12..14),
+            HighlightEvent::ScopeEnd,
+            HighlightEvent::Source(14..15),
+        ]);
+        assert_eq!(stack.as_slice(), &[a]);
+    }
+
+    #[cfg(all(feature = "assets", any(feature = "dump-load", feature = "dump-load-rs")))]
+    #[test]
+    fn iterates_documents() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let syntax = ss.find_syntax_by_extension("rs").unwrap();
+        let text = "/* a\nb */ fn main() {\n}";
+        let events: Vec<HighlightEvent> = HighlightEventIterator::new(text, syntax, &ss).collect();
+
+        let starts = events.iter().filter(|e| match **e { HighlightEvent::ScopeStart(_) => true, _ => false }).count();
+        let ends = events.iter().filter(|e| **e == HighlightEvent::ScopeEnd).count();
+        assert_eq!(starts, ends);
+        assert_eq!(events[0], HighlightEvent::ScopeStart(Scope::new("source.rust").unwrap()));
+        assert_eq!(events.last(), Some(&HighlightEvent::ScopeEnd));
+
+        let mut source = String::new();
+        for event in &events {
+            if let HighlightEvent::Source(ref range) = *event {
+                source.push_str(&text[range.clone()]);
+            }
+        }
+        assert_eq!(source, text);
+    }
+}
@@ -0,0 +1,834 @@
+//! Checks for problems in syntax definitions that would otherwise only show up
+//! while parsing, if at all.
+//!
+//! Use `lint_syntax_set` for a whole set, which can follow references between
+//! syntaxes, `lint_syntax_definition` for a single definition, or
+//! `lint_sublime_syntax` for the source of a `.sublime-syntax` file, which can also
+//! find regexes using undefined variables.
+//!
+//! The results are `Diagnostic`s, which can be serialized for other tools.
+use super::scope::*;
+use super::syntax_definition::*;
+use super::syntax_set::SyntaxSet;
+#[cfg(feature = "yaml-load")]
+use super::ParseSyntaxError;
+use onig::{Regex, RegexOptions, Syntax};
+#[cfg(feature = "yaml-load")]
+use yaml_rust::{Yaml, YamlLoader};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// How bad a problem is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Severity {
+    /// Something that is probably a mistake, but the syntax still works
+    Warning,
+    /// Something that makes the syntax fail to load or parse
+    Error,
+}
+
+/// The kind of problem a `Diagnostic` is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LintKind {
+    /// The file isn't a valid `.sublime-syntax`
+    InvalidSyntax,
+    /// A regex that doesn't compile. Regexes with backreferences to an earlier
+    /// match are compiled while parsing, so these aren't caught when loading.
+    InvalidRegex,
+    /// A named context that can't be reached from the main context
+    UnreachableContext,
+    /// A `{{variable}}` that isn't defined, which is replaced with nothing
+    UndefinedVariable,
+    /// A pattern that can never match, because an earlier pattern in the same
+    /// context always matches
+    ShadowedPattern,
+    /// A push without consuming any text into a context that can pop again without
+    /// consuming anything, which the parser has to break by skipping a character
+    NonConsumingLoop,
+    /// A scope that doesn't follow the scope naming conventions of Sublime Text
+    ScopeNaming,
+}
+
+impl LintKind {
+    /// How bad problems of this kind are. Ones that make the syntax fail to load,
+    /// like invalid regexes, are errors and everything else is a warning.
+    pub fn severity(self) -> Severity {
+        match self {
+            LintKind::InvalidSyntax | LintKind::InvalidRegex | LintKind::UndefinedVariable => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+/// A problem found in a syntax definition
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub kind: LintKind,
+    /// The name of the syntax
+    pub syntax: String,
+    /// The name of the context, or `None` for problems with the syntax as a whole
+    pub context: Option<String>,
+    /// The index of the pattern in the context
+    pub pattern: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{} [{:?}] {}", severity, self.kind, self.syntax)?;
+        if let Some(ref context) = self.context {
+            write!(f, " > {}", context)?;
+        }
+        if let Some(pattern) = self.pattern {
+            write!(f, " > pattern {}", pattern)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// The scopes that all scope names should start with, see
+/// https://www.sublimetext.com/docs/3/scope_naming.html
+const TOP_LEVEL_SCOPES: &[&str] = &[
+    "comment", "constant", "entity", "invalid", "keyword", "markup", "meta", "punctuation",
+    "source", "storage", "string", "support", "text", "variable",
+];
+
+/// Checks all syntaxes in a set. References between syntaxes are followed, so unlike
+/// `lint_syntax_definition`, contexts that are only used by other syntaxes aren't
+/// reported as unreachable.
+pub fn lint_syntax_set(syntax_set: &SyntaxSet) -> Vec<Diagnostic> {
+    let mut owners = vec![("", ""); syntax_set.context_count()];
+    let mut roots = Vec::new();
+    for syntax in syntax_set.syntaxes() {
+        for (name, context_id) in &syntax.contexts {
+            owners[context_id.index()] = (syntax.name.as_str(), name.as_str());
+        }
+        roots.extend(syntax.contexts.get("__start").map(|id| id.index()));
+    }
+    let nodes = owners.iter().enumerate().map(|(i, &(syntax, name))| {
+        Node { syntax, name, context: syntax_set.get_context(&ContextId::new(i)) }
+    }).collect();
+    let graph = Graph {
+        nodes,
+        roots,
+        resolve: Box::new(|reference: &ContextReference| match *reference {
+            ContextReference::Direct(id) => Some(id.index()),
+            _ => None,
+        }),
+    };
+
+    let mut diagnostics = Vec::new();
+    for syntax in syntax_set.syntaxes() {
+        lint_syntax_info(&syntax.name, syntax.scope, &syntax.first_line_match, &syntax.variables, &mut diagnostics);
+    }
+    graph.lint(&mut diagnostics);
+    diagnostics
+}
+
+/// Checks a single syntax definition. References to other syntaxes can't be followed,
+/// so contexts that are only used by other syntaxes are reported as unreachable.
+pub fn lint_syntax_definition(defn: &SyntaxDefinition) -> Vec<Diagnostic> {
+    let mut names: Vec<&String> = defn.contexts.keys().collect();
+    names.sort();
+    let indices: HashMap<&str, usize> = names.iter().enumerate().map(|(i, name)| (name.as_str(), i)).collect();
+    let roots = ["__start", "__main", "main", "prototype"].iter()
+        .filter_map(|name| indices.get(name).cloned())
+        .collect();
+    let nodes = names.iter().map(|name| {
+        Node { syntax: defn.name.as_str(), name: name.as_str(), context: &defn.contexts[*name] }
+    }).collect();
+    let graph = Graph {
+        nodes,
+        roots,
+        resolve: Box::new(move |reference: &ContextReference| match *reference {
+            ContextReference::Named(ref name) | ContextReference::Inline(ref name) => {
+                let name = if name == "$top_level_main" { "main" } else { name.as_str() };
+                indices.get(name).cloned()
+            }
+            _ => None,
+        }),
+    };
+
+    let mut diagnostics = Vec::new();
+    lint_syntax_info(&defn.name, defn.scope, &defn.first_line_match, &defn.variables, &mut diagnostics);
+    graph.lint(&mut diagnostics);
+    diagnostics
+}
+
+/// Checks the source of a `.sublime-syntax` file. If it can't be loaded, the
+/// reason is returned as a diagnostic.
+#[cfg(feature = "yaml-load")]
+pub fn lint_sublime_syntax(source: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    // The loader replaces variables, so look for undefined ones in the source
+    if let Some(doc) = YamlLoader::load_from_str(source).ok().and_then(|docs| docs.into_iter().next()) {
+        let syntax = doc["name"].as_str().unwrap_or("Unnamed");
+        let variables: HashSet<&str> = doc["variables"].as_hash()
+            .map(|h| h.keys().filter_map(|k| k.as_str()).collect())
+            .unwrap_or_default();
+        if let Some(contexts) = doc["contexts"].as_hash() {
+            for (name, items) in contexts {
+                let mut regexes = Vec::new();
+                raw_regexes(items, &mut regexes);
+                for regex in regexes {
+                    for variable in variable_names(regex) {
+                        if !variables.contains(variable.as_str()) {
+                            diagnostics.push(Diagnostic {
+                                severity: Severity::Error,
+                                kind: LintKind::UndefinedVariable,
+                                syntax: syntax.to_owned(),
+                                context: name.as_str().map(|s| s.to_owned()),
+                                pattern: None,
+                                message: format!("regex '{}' uses undefined variable '{}'", regex, variable),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    match SyntaxDefinition::load_from_str(source, true, None) {
+        Ok(defn) => diagnostics.extend(lint_syntax_definition(&defn)),
+        Err(error) => {
+            let kind = match error {
+                ParseSyntaxError::RegexCompileError(..) => LintKind::InvalidRegex,
+                _ => LintKind::InvalidSyntax,
+            };
+            diagnostics.push(Diagnostic {
+                severity: kind.severity(),
+                kind,
+                syntax: String::new(),
+                context: None,
+                pattern: None,
+                message: error.to_string(),
+            });
+        }
+    }
+    diagnostics
+}
+
+#[cfg(feature = "yaml-load")]
+fn raw_regexes<'a>(yaml: &'a Yaml, regexes: &mut Vec<&'a str>) {
+    match *yaml {
+        Yaml::Array(ref items) => {
+            for item in items {
+                raw_regexes(item, regexes);
+            }
+        }
+        Yaml::Hash(ref hash) => {
+            for (key, value) in hash {
+                match (key.as_str(), value.as_str()) {
+                    (Some("match"), Some(regex)) | (Some("escape"), Some(regex)) => regexes.push(regex),
+                    _ => raw_regexes(value, regexes),
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn variable_names(s: &str) -> Vec<String> {
+    lazy_static! {
+        static ref VARIABLE: Regex = Regex::new(r"\{\{([A-Za-z0-9_]+)\}\}").unwrap();
+    }
+    VARIABLE.captures_iter(s).filter_map(|caps| caps.at(1).map(|name| name.to_owned())).collect()
+}
+
+/// Checks the parts of a syntax outside its contexts
+fn lint_syntax_info(syntax: &str,
+                    scope: Scope,
+                    first_line_match: &Option<String>,
+                    variables: &HashMap<String, String>,
+                    diagnostics: &mut Vec<Diagnostic>) {
+    let diagnostic = |kind: LintKind, message: String| Diagnostic {
+        severity: kind.severity(),
+        kind,
+        syntax: syntax.to_owned(),
+        context: None,
+        pattern: None,
+        message,
+    };
+
+    let scope_name = scope.build_string();
+    if !scope_name.starts_with("source.") && !scope_name.starts_with("text.") {
+        diagnostics.push(diagnostic(LintKind::ScopeNaming,
+                                    format!("syntax scope '{}' should start with 'source.' or 'text.'", scope_name)));
+    }
+    if let Some(ref regex) = *first_line_match {
+        if let Err(error) = Regex::new(regex) {
+            diagnostics.push(diagnostic(LintKind::InvalidRegex,
+                                        format!("first line regex '{}' doesn't compile: {}", regex, error.description())));
+        }
+    }
+
+    let mut names: Vec<&String> = variables.keys().collect();
+    names.sort();
+    for name in names {
+        for variable in variable_names(&variables[name]) {
+            if !variables.contains_key(&variable) {
+                diagnostics.push(diagnostic(LintKind::UndefinedVariable,
+                                            format!("variable '{}' uses undefined variable '{}'", name, variable)));
+            }
+        }
+    }
+}
+
+struct Node<'a> {
+    syntax: &'a str,
+    name: &'a str,
+    context: &'a Context,
+}
+
+/// Finds the node a reference points to
+type Resolver<'a> = Box<dyn Fn(&ContextReference) -> Option<usize> + 'a>;
+
+/// The contexts to check and how references between them are resolved, which is
+/// different for linked and unlinked contexts
+struct Graph<'a> {
+    nodes: Vec<Node<'a>>,
+    roots: Vec<usize>,
+    resolve: Resolver<'a>,
+}
+
+/// A match pattern reached from a context, possibly through includes
+struct FlatPattern<'a> {
+    /// The index of the pattern or include in the context itself
+    item: usize,
+    node: usize,
+    index: usize,
+    pattern: &'a MatchPattern,
+}
+
+impl<'a> Graph<'a> {
+    fn lint(&self, diagnostics: &mut Vec<Diagnostic>) {
+        self.lint_regexes(diagnostics);
+        self.lint_reachability(diagnostics);
+        self.lint_shadowing(diagnostics);
+        self.lint_loops(diagnostics);
+        self.lint_scopes(diagnostics);
+    }
+
+    fn diagnostic(&self, kind: LintKind, node: usize, pattern: Option<usize>, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: kind.severity(),
+            kind,
+            syntax: self.nodes[node].syntax.to_owned(),
+            context: Some(self.nodes[node].name.to_owned()),
+            pattern,
+            message,
+        }
+    }
+
+    fn own_patterns(&self, node: usize) -> Vec<(usize, &'a MatchPattern)> {
+        self.nodes[node].context.patterns.iter().enumerate().filter_map(|(i, pattern)| match *pattern {
+            Pattern::Match(ref match_pat) => Some((i, match_pat)),
+            Pattern::Include(_) => None,
+        }).collect()
+    }
+
+    fn references(&self, node: usize) -> Vec<usize> {
+        let context = self.nodes[node].context;
+        let mut references: Vec<usize> = context.prototype
+            .and_then(|id| (self.resolve)(&ContextReference::Direct(id)))
+            .into_iter()
+            .collect();
+        for pattern in &context.patterns {
+            match *pattern {
+                Pattern::Include(ref reference) => references.extend((self.resolve)(reference)),
+                Pattern::Match(ref match_pat) => {
+                    match match_pat.operation {
+                        MatchOperation::Push(ref refs) | MatchOperation::Set(ref refs) => {
+                            references.extend(refs.iter().filter_map(|r| (self.resolve)(r)));
+                        }
+                        MatchOperation::Pop | MatchOperation::None => {}
+                    }
+                    references.extend(match_pat.with_prototype.as_ref().and_then(|r| (self.resolve)(r)));
+                }
+            }
+        }
+        references
+    }
+
+    /// The match patterns of a context in the order the parser tries them
+    fn flattened(&self, node: usize) -> Vec<FlatPattern<'a>> {
+        let mut patterns = Vec::new();
+        let mut seen = HashSet::new();
+        seen.insert(node);
+        for (item, pattern) in self.nodes[node].context.patterns.iter().enumerate() {
+            match *pattern {
+                Pattern::Match(ref match_pat) => {
+                    patterns.push(FlatPattern { item, node, index: item, pattern: match_pat });
+                }
+                Pattern::Include(ref reference) => self.flatten_include(item, reference, &mut seen, &mut patterns),
+            }
+        }
+        patterns
+    }
+
+    fn flatten_include(&self,
+                       item: usize,
+                       reference: &ContextReference,
+                       seen: &mut HashSet<usize>,
+                       patterns: &mut Vec<FlatPattern<'a>>) {
+        let included = match (self.resolve)(reference) {
+            Some(included) => included,
+            None => return,
+        };
+        if !seen.insert(included) {
+            return;
+        }
+        for (index, pattern) in self.nodes[included].context.patterns.iter().enumerate() {
+            match *pattern {
+                Pattern::Match(ref match_pat) => {
+                    patterns.push(FlatPattern { item, node: included, index, pattern: match_pat });
+                }
+                Pattern::Include(ref reference) => self.flatten_include(item, reference, seen, patterns),
+            }
+        }
+    }
+
+    fn lint_regexes(&self, diagnostics: &mut Vec<Diagnostic>) {
+        for node in 0..self.nodes.len() {
+            for (i, match_pat) in self.own_patterns(node) {
+                // Backreferences are replaced with the escaped text of the match while parsing
+                let regex_str = substitute_backrefs_in_regex(&match_pat.regex_str, |i| Some(format!("<placeholder_{}>", i)));
+                let result = Regex::with_options(&regex_str, RegexOptions::REGEX_OPTION_CAPTURE_GROUP, Syntax::default());
+                if let Err(error) = result {
+                    diagnostics.push(self.diagnostic(LintKind::InvalidRegex, node, Some(i),
+                        format!("regex '{}' doesn't compile: {}", match_pat.regex_str, error.description())));
+                }
+            }
+        }
+    }
+
+    fn lint_reachability(&self, diagnostics: &mut Vec<Diagnostic>) {
+        let mut reached = vec![false; self.nodes.len()];
+        let mut queue = self.roots.clone();
+        while let Some(node) = queue.pop() {
+            if !reached[node] {
+                reached[node] = true;
+                queue.extend(self.references(node));
+            }
+        }
+        for (node, reached) in reached.into_iter().enumerate() {
+            // Anonymous contexts are unreachable because the context containing them is
+            if !reached && !self.nodes[node].name.starts_with("#anon_") {
+                diagnostics.push(self.diagnostic(LintKind::UnreachableContext, node, None,
+                                                 "context is never used".to_owned()));
+            }
+        }
+    }
+
+    fn lint_shadowing(&self, diagnostics: &mut Vec<Diagnostic>) {
+        for node in 0..self.nodes.len() {
+            let patterns = self.flattened(node);
+            let shadowing = match patterns.iter().find(|p| analyze_regex(&p.pattern.regex_str).always_matches) {
+                Some(p) => p,
+                None => continue,
+            };
+            let source = if shadowing.node == node {
+                format!("pattern {}", shadowing.index)
+            } else {
+                format!("pattern {} of included context '{}'", shadowing.index, self.nodes[shadowing.node].name)
+            };
+            let items = self.nodes[node].context.patterns.len();
+            for item in shadowing.item + 1..items {
+                diagnostics.push(self.diagnostic(LintKind::ShadowedPattern, node, Some(item),
+                    format!("never matches, because {} with regex '{}' always matches first", source, shadowing.pattern.regex_str)));
+            }
+        }
+    }
+
+    fn lint_loops(&self, diagnostics: &mut Vec<Diagnostic>) {
+        for node in 0..self.nodes.len() {
+            // The push into the main context is generated and fine
+            if self.nodes[node].name == "__start" {
+                continue;
+            }
+            for (i, match_pat) in self.own_patterns(node) {
+                let target = match match_pat.operation {
+                    MatchOperation::Push(ref refs) => refs.last().and_then(|r| (self.resolve)(r)),
+                    _ => None,
+                };
+                let target = match target {
+                    Some(target) if analyze_regex(&match_pat.regex_str).non_consuming => target,
+                    _ => continue,
+                };
+                if let Some(popping) = self.non_consuming_pop(target) {
+                    diagnostics.push(self.diagnostic(LintKind::NonConsumingLoop, node, Some(i),
+                        format!("pushes '{}' without consuming anything, and '{}' can pop again without consuming anything",
+                                self.nodes[target].name, self.nodes[popping].name)));
+                }
+            }
+        }
+    }
+
+    /// Finds a context with a non-consuming pop reachable from `start` through
+    /// non-consuming sets, which don't change the stack depth
+    fn non_consuming_pop(&self, start: usize) -> Option<usize> {
+        let mut queue = vec![start];
+        let mut seen = HashSet::new();
+        while let Some(node) = queue.pop() {
+            if !seen.insert(node) {
+                continue;
+            }
+            for p in self.flattened(node) {
+                if !analyze_regex(&p.pattern.regex_str).non_consuming {
+                    continue;
+                }
+                match p.pattern.operation {
+                    MatchOperation::Pop => return Some(node),
+                    MatchOperation::Set(ref refs) => queue.extend(refs.last().and_then(|r| (self.resolve)(r))),
+                    _ => {}
+                }
+            }
+        }
+        None
+    }
+
+    fn lint_scopes(&self, diagnostics: &mut Vec<Diagnostic>) {
+        let mut checked = HashSet::new();
+        for node in 0..self.nodes.len() {
+            let context = self.nodes[node].context;
+            let mut scopes: Vec<(Option<usize>, Scope)> = context.meta_scope.iter()
+                .chain(&context.meta_content_scope)
+                .map(|&scope| (None, scope))
+                .collect();
+            for (i, match_pat) in self.own_patterns(node) {
+                scopes.extend(match_pat.scope.iter().map(|&scope| (Some(i), scope)));
+                if let Some(ref captures) = match_pat.captures {
+                    for (_, capture_scopes) in captures {
+                        scopes.extend(capture_scopes.iter().map(|&scope| (Some(i), scope)));
+                    }
+                }
+            }
+
+            for (pattern, scope) in scopes {
+                let name = scope.build_string();
+                if !checked.insert(name.clone()) {
+                    continue;
+                }
+                if let Some(problem) = scope_naming_problem(&name) {
+                    diagnostics.push(self.diagnostic(LintKind::ScopeNaming, node, pattern,
+                                                     format!("scope '{}' {}", name, problem)));
+                }
+            }
+        }
+    }
+}
+
+fn scope_naming_problem(name: &str) -> Option<&'static str> {
+    if name.chars().any(|c| c.is_uppercase()) {
+        Some("should be lowercase")
+    } else if !name.chars().all(|c| c.is_alphanumeric() || "._-+".contains(c)) {
+        Some("should only contain letters, digits, '.', '_', '-' and '+'")
+    } else if !TOP_LEVEL_SCOPES.contains(&name.split('.').next().unwrap_or("")) {
+        Some("should start with one of the standard top level scopes")
+    } else {
+        None
+    }
+}
+
+/// What a regex can match, found by looking at its syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RegexInfo {
+    /// Matches the empty string at any position
+    always_matches: bool,
+    /// Never matches any characters, e.g. only lookaheads and anchors
+    non_consuming: bool,
+}
+
+const CONSUMING: RegexInfo = RegexInfo { always_matches: false, non_consuming: false };
+const ASSERTION: RegexInfo = RegexInfo { always_matches: false, non_consuming: true };
+const EMPTY: RegexInfo = RegexInfo { always_matches: true, non_consuming: true };
+
+fn analyze_regex(regex: &str) -> RegexInfo {
+    RegexScanner { bytes: regex.as_bytes(), index: 0 }.alternatives()
+}
+
+/// A very small regex parser that only knows enough to tell assertions,
+/// optional parts and things that consume characters apart
+struct RegexScanner<'a> {
+    bytes: &'a [u8],
+    index: usize,
+}
+
+impl<'a> RegexScanner<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.index).cloned()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek();
+        self.index += 1;
+        c
+    }
+
+    fn alternatives(&mut self) -> RegexInfo {
+        let mut info = self.sequence();
+        while self.peek() == Some(b'|') {
+            self.index += 1;
+            let alternative = self.sequence();
+            info.always_matches |= alternative.always_matches;
+            info.non_consuming &= alternative.non_consuming;
+        }
+        info
+    }
+
+    fn sequence(&mut self) -> RegexInfo {
+        let mut info = EMPTY;
+        while let Some(c) = self.peek() {
+            if c == b'|' || c == b')' {
+                break;
+            }
+            let mut atom = self.atom();
+            if self.optional_quantifier() {
+                atom.always_matches = true;
+            }
+            info.always_matches &= atom.always_matches;
+            info.non_consuming &= atom.non_consuming;
+        }
+        info
+    }
+
+    fn atom(&mut self) -> RegexInfo {
+        match self.next() {
+            Some(b'^') | Some(b'$') => ASSERTION,
+            Some(b'\\') => match self.next() {
+                Some(b'b') | Some(b'B') | Some(b'A') | Some(b'z') | Some(b'Z') | Some(b'G') => ASSERTION,
+                _ => CONSUMING,
+            },
+            Some(b'[') => {
+                self.skip_class();
+                CONSUMING
+            }
+            Some(b'(') => self.group(),
+            _ => CONSUMING,
+        }
+    }
+
+    fn group(&mut self) -> RegexInfo {
+        if self.peek() == Some(b'?') {
+            self.index += 1;
+            let lookbehind = self.peek() == Some(b'<') &&
+                (self.bytes.get(self.index + 1) == Some(&b'=') || self.bytes.get(self.index + 1) == Some(&b'!'));
+            match self.peek() {
+                Some(b'=') | Some(b'!') => {
+                    self.skip_group();
+                    return ASSERTION;
+                }
+                _ if lookbehind => {
+                    self.skip_group();
+                    return ASSERTION;
+                }
+                Some(b'#') => {
+                    self.skip_group();
+                    return EMPTY;
+                }
+                _ => {}
+            }
+            // Flags like `(?i)` and `(?i:`, named groups and atomic groups
+            while let Some(c) = self.next() {
+                match c {
+                    b')' => return EMPTY,
+                    b':' | b'>' => break,
+                    _ => {}
+                }
+            }
+        }
+        let info = self.alternatives();
+        if self.peek() == Some(b')') {
+            self.index += 1;
+        }
+        info
+    }
+
+    /// Skips to after the `)` closing the current group
+    fn skip_group(&mut self) {
+        let mut depth = 1;
+        while let Some(c) = self.next() {
+            match c {
+                b'\\' => {
+                    self.index += 1;
+                }
+                b'[' => self.skip_class(),
+                b'(' => depth += 1,
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Skips to after the `]` closing the current character class
+    fn skip_class(&mut self) {
+        if self.peek() == Some(b'^') {
+            self.index += 1;
+        }
+        // A `]` right at the start is a literal
+        if self.peek() == Some(b']') {
+            self.index += 1;
+        }
+        while let Some(c) = self.next() {
+            match c {
+                b'\\' => {
+                    self.index += 1;
+                }
+                b'[' => self.skip_class(),
+                b']' => return,
+                _ => {}
+            }
+        }
+    }
+
+    /// Consumes a quantifier and returns whether it allows zero repetitions
+    fn optional_quantifier(&mut self) -> bool {
+        let optional = match self.peek() {
+            Some(b'*') | Some(b'?') => true,
+            Some(b'+') => false,
+            Some(b'{') => {
+                let rest = &self.bytes[self.index + 1..];
+                let end = match rest.iter().position(|&c| c == b'}') {
+                    Some(end) => end,
+                    None => return false,
+                };
+                let bounds = &rest[..end];
+                if bounds.is_empty() || !bounds.iter().all(|&c| c.is_ascii_digit() || c == b',') {
+                    return false;
+                }
+                self.index += end + 1;
+                bounds[0] == b',' || bounds.iter().take_while(|c| c.is_ascii_digit()).all(|&c| c == b'0')
+            }
+            _ => return false,
+        };
+        self.index += 1;
+        // Lazy and possessive quantifiers
+        if self.peek() == Some(b'?') || self.peek() == Some(b'+') {
+            self.index += 1;
+        }
+        optional
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(diagnostics: &[Diagnostic]) -> Vec<(LintKind, Option<String>, Option<usize>)> {
+        diagnostics.iter().map(|d| (d.kind, d.context.clone(), d.pattern)).collect()
+    }
+
+    #[test]
+    fn can_analyze_regexes() {
+        let optional = RegexInfo { always_matches: true, non_consuming: false };
+        assert_eq!(analyze_regex(""), EMPTY);
+        assert_eq!(analyze_regex(r"(?i)(?#comment)"), EMPTY);
+        assert_eq!(analyze_regex(r"\s*"), optional);
+        assert_eq!(analyze_regex(r"(?i)(?:a|)"), optional);
+        assert_eq!(analyze_regex(r"(?=\S)"), ASSERTION);
+        assert_eq!(analyze_regex(r"^(?!(?:[)]|x))$"), ASSERTION);
+        assert_eq!(analyze_regex(r"(?<=a)\b"), ASSERTION);
+        assert_eq!(analyze_regex(r"a{0,3}"), optional);
+        assert_eq!(analyze_regex(r"a{1,3}"), CONSUMING);
+        assert_eq!(analyze_regex(r"\w+"), CONSUMING);
+        assert_eq!(analyze_regex(r"(?=a)|b"), CONSUMING);
+        assert_eq!(analyze_regex(r"[(]"), CONSUMING);
+    }
+
+    #[cfg(feature = "yaml-load")]
+    #[test]
+    fn can_lint_syntax() {
+        let diagnostics = lint_sublime_syntax(r#"
+name: Lint Test
+scope: source.lint
+variables:
+  ident: '[a-z]+{{missing}}'
+contexts:
+  main:
+    - match: '{{ident}}{{undefined}}'
+      scope: variable.other
+    - match: (?=x)
+      push: looping
+    - match: '"'
+      push: string
+  string:
+    - match: '\s*'
+    - match: '"'
+      scope: Punctuation.definition
+      pop: true
+  looping:
+    - match: (?=\S)
+      set: looping2
+  looping2:
+    - match: (?=.)
+      pop: true
+  unused:
+    - match: 'a'
+      scope: custom.scope
+"#);
+        let found = kinds(&diagnostics);
+        let main = Some("main".to_owned());
+        assert!(found.contains(&(LintKind::UndefinedVariable, main.clone(), None)));
+        assert!(found.contains(&(LintKind::UndefinedVariable, None, None)));
+        assert!(found.contains(&(LintKind::NonConsumingLoop, main.clone(), Some(1))));
+        assert!(found.contains(&(LintKind::ShadowedPattern, Some("string".to_owned()), Some(1))));
+        assert!(found.contains(&(LintKind::UnreachableContext, Some("unused".to_owned()), None)));
+        assert!(found.contains(&(LintKind::ScopeNaming, Some("string".to_owned()), Some(1))));
+        assert!(found.contains(&(LintKind::ScopeNaming, Some("unused".to_owned()), Some(0))));
+        assert!(!found.contains(&(LintKind::ShadowedPattern, main.clone(), Some(1))));
+        assert!(diagnostics.iter().all(|d| d.syntax == "Lint Test"));
+    }
+
+    #[cfg(feature = "yaml-load")]
+    #[test]
+    fn reports_invalid_regexes() {
+        let diagnostics = lint_sublime_syntax("name: A\nscope: source.a\ncontexts: {main: [{match: '(a'}]}");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, LintKind::InvalidRegex);
+
+        // Only compiled with the text of the backreference while parsing
+        let mut defn = SyntaxDefinition::load_from_str(
+            "name: A\nscope: source.a\ncontexts: {main: [{match: '(a)', push: b}], b: [{match: 'x', pop: true}]}",
+            true, None).unwrap();
+        if let Pattern::Match(ref mut match_pat) = defn.contexts.get_mut("b").unwrap().patterns[0] {
+            match_pat.regex_str = r"(\1".to_owned();
+        }
+        let diagnostics = lint_syntax_definition(&defn);
+        assert_eq!(kinds(&diagnostics), vec![(LintKind::InvalidRegex, Some("b".to_owned()), Some(0))]);
+    }
+
+    #[cfg(feature = "yaml-load")]
+    #[test]
+    fn rust_syntax_has_no_errors() {
+        use parsing::SyntaxSetBuilder;
+
+        let mut builder = SyntaxSetBuilder::new();
+        builder.add_from_folder("testdata/Packages/Rust", true).unwrap();
+        let diagnostics = lint_syntax_set(&builder.build());
+        let errors: Vec<&Diagnostic> = diagnostics.iter().filter(|d| d.severity == Severity::Error).collect();
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(!diagnostics.iter().any(|d| d.kind == LintKind::UnreachableContext && d.context.as_ref().map(|c| c.as_str()) == Some("main")));
+    }
+
+    #[cfg(all(feature = "assets", feature = "dump-create", any(feature = "dump-load", feature = "dump-load-rs")))]
+    #[test]
+    fn can_lint_lazily_loaded_set() {
+        use dumps::{dump_lazy_syntax_set, lazy_syntax_set_from_binary, DumpInfo};
+
+        let ss = SyntaxSet::load_defaults_newlines();
+        let mut bin = Vec::new();
+        dump_lazy_syntax_set(&ss, DumpInfo::default(), &mut bin).unwrap();
+        let lazy = lazy_syntax_set_from_binary(&bin).unwrap();
+        assert_eq!(lint_syntax_set(&lazy), lint_syntax_set(&ss));
+    }
+}
@@ -60,6 +60,12 @@ pub struct MetadataItems {
     pub disable_indent_next_line_pattern: Option<Pattern>,
     pub unindented_line_pattern: Option<Pattern>,
     pub indent_parens: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub show_in_symbol_list: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub show_in_indexed_symbol_list: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub show_in_indexed_reference_list: Option<bool>,
     #[serde(default)]
     pub shell_variables: BTreeMap<String, String>,
     /// For convenience; this is the first value in `shell_variables`
@@ -98,6 +104,9 @@ const KEYS_WE_USE: &[&str] = &[
     "disableIndentNextLinePattern",
     "unIndentedLinePattern",
     "indentParens",
+    "showInSymbolList",
+    "showInIndexedSymbolList",
+    "showInIndexedReferenceList",
     "shellVariables",
 ];
 
@@ -340,6 +349,18 @@ impl<'a> ScopedMetadata<'a> {
             .unwrap_or(false)
     }
 
+    pub fn show_in_symbol_list(&self) -> bool {
+        self.best_match(|ind| ind.show_in_symbol_list).unwrap_or(false)
+    }
+
+    pub fn show_in_indexed_symbol_list(&self) -> bool {
+        self.best_match(|ind| ind.show_in_indexed_symbol_list).unwrap_or(false)
+    }
+
+    pub fn show_in_indexed_reference_list(&self) -> bool {
+        self.best_match(|ind| ind.show_in_indexed_reference_list).unwrap_or(false)
+    }
+
     pub fn line_comment(&self) -> Option<&str> {
         let idx = self.items.iter().position(|m| m.1.items.line_comment.is_some())?;
         self.items[idx].1.items.line_comment.as_ref().map(|s| s.as_str())
@@ -364,6 +385,12 @@ impl<'a> ScopedMetadata<'a> {
     }
 }
 
+/// Symbol list settings are usually written as `<integer>1</integer>` rather than booleans
+fn deserialize_flag<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error> where D: Deserializer<'de> {
+    let value = Option::<Settings>::deserialize(deserializer)?;
+    Ok(value.and_then(|v| v.as_bool().or_else(|| v.as_i64().map(|i| i != 0))))
+}
+
 impl RawMetadataEntry {
     pub fn load<P: Into<PathBuf>>(path: P) -> Result<Self, LoadingError> {
         let path: PathBuf = path.into();
@@ -526,6 +553,20 @@ mod tests {
         assert!(metadata.items.increase_indent_pattern.is_none());
     }
 
+    #[test]
+    fn load_symbol_list() {
+        let raw: Dict = serde_json::from_str(r#"{
+            "showInSymbolList": 1,
+            "showInIndexedSymbolList": true,
+            "showInIndexedReferenceList": 0
+        }"#).unwrap();
+        let metadata = MetadataSet::from_raw(("entity.name.function".into(), raw)).unwrap();
+        assert_eq!(metadata.items.show_in_symbol_list, Some(true));
+        assert_eq!(metadata.items.show_in_indexed_symbol_list, Some(true));
+        assert_eq!(metadata.items.show_in_indexed_reference_list, Some(false));
+        assert!(metadata.items.increase_indent_pattern.is_none());
+    }
+
     #[test]
     fn serde_pattern() {
         let pattern: Pattern = serde_json::from_str("\"just a string\"").unwrap();
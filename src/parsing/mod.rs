@@ -4,12 +4,20 @@
 pub mod syntax_definition;
 #[cfg(all( feature = "parsing", feature = "yaml-load"))]
 mod yaml_load;
+#[cfg(all( feature = "parsing", feature = "yaml-load"))]
+mod yaml_dump;
 #[cfg(feature = "parsing")]
 mod syntax_set;
 #[cfg(feature = "parsing")]
 mod parser;
+#[cfg(feature = "parsing")]
+mod syntax_detector;
+#[cfg(feature = "parsing")]
+mod events;
 #[cfg(feature = "metadata")]
 pub mod metadata;
+#[cfg(feature = "parsing")]
+pub mod lint;
 
 mod scope;
 
@@ -17,10 +25,16 @@ mod scope;
 pub use self::syntax_definition::SyntaxDefinition;
 #[cfg(all( feature = "parsing", feature = "yaml-load"))]
 pub use self::yaml_load::*;
+#[cfg(all( feature = "parsing", feature = "yaml-load"))]
+pub use self::yaml_dump::*;
 #[cfg(feature = "parsing")]
 pub use self::syntax_set::*;
 #[cfg(feature = "parsing")]
 pub use self::parser::*;
+#[cfg(feature = "parsing")]
+pub use self::syntax_detector::*;
+#[cfg(feature = "parsing")]
+pub use self::events::*;
 #[cfg(feature = "metadata")]
 pub use self::metadata::*;
 
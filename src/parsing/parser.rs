@@ -5,6 +5,11 @@ use std::usize;
 use std::collections::HashMap;
 use std::i32;
 use std::hash::BuildHasherDefault;
+use std::time::{Duration, Instant};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::error::Error;
+use std::fmt;
 use fnv::FnvHasher;
 use parsing::syntax_set::{SyntaxSet, SyntaxReference};
 
@@ -22,24 +27,132 @@ use parsing::syntax_set::{SyntaxSet, SyntaxReference};
 /// so if you want to construct scope stacks you'll need to keep track of that as well.
 /// Note that `HighlightState` contains exactly this as a public field that you can use.
 ///
+/// Parse states can also be serialized, for example to keep a cache on disk between sessions.
+/// Contexts are referenced by their index in the `SyntaxSet`, so a stored state is only valid
+/// with the exact same syntax set. Store `SyntaxSet::fingerprint` next to it and throw the
+/// state away if that changed. The `ParseLimits` aren't serialized and need to be set again.
+///
+/// Two states are equal if they're at the same point in parsing, regardless of their `ParseLimits`.
+///
 /// **Note:** Caching is for advanced users who have tons of time to maximize performance or want to do so eventually.
 /// It is not recommended that you try caching the first time you implement highlighting.
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ParseState {
     stack: Vec<StateLevel>,
     first_line: bool,
     // See issue #101. Contains indices of frames pushed by `with_prototype`s.
     // Doesn't look at `with_prototype`s below top of stack.
     proto_starts: Vec<usize>,
+    #[serde(skip)]
+    limits: ParseLimits,
+}
+
+/// Bounds on how much work `ParseState::parse_line` may do for a single line.
+///
+/// Sublime Text doesn't highlight lines that are too long, because a single huge line
+/// (think minified JavaScript) or a regex with catastrophic backtracking can otherwise
+/// make parsing take practically forever. By default there are no limits, set some
+/// with `ParseState::set_limits` if you parse untrusted input.
+///
+/// When a limit is hit the line falls back to plain text: no operations are returned
+/// for it (so it keeps the scopes that were on the stack at the start of the line) and
+/// the parser state is left as it was before the line, so following lines continue
+/// parsing as if the line wasn't there. Use `ParseState::parse_line_with_status`
+/// to find out whether that happened.
+///
+/// Note that the limits are only checked between regex searches, a single search
+/// can still take as long as onig's retry limit allows.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct ParseLimits {
+    /// Lines longer than this many bytes aren't parsed at all
+    pub max_line_length: Option<usize>,
+    /// Maximum number of regex searches (cache hits aren't counted) done for one line
+    pub max_searches_per_line: Option<usize>,
+    /// Maximum wall-clock time spent parsing one line
+    pub max_time_per_line: Option<Duration>,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+/// What happened when parsing a line, returned by `ParseState::parse_line_with_status`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ParseLineStatus {
+    /// The line was parsed normally
+    Complete,
+    /// The line was longer than `ParseLimits::max_line_length` and wasn't parsed
+    LineTooLong,
+    /// Parsing the line needed more than `ParseLimits::max_searches_per_line` regex searches
+    TooManySearches,
+    /// Parsing the line took longer than `ParseLimits::max_time_per_line`
+    TimedOut,
+}
+
+/// A flag shared between threads that tells a long running parse or highlight job to stop.
+///
+/// Clones share the same flag, so keep one and hand a clone to the code doing the work.
+/// Cancelling is sticky, create a new token for the next job.
+///
+/// # Examples
+///
+/// ```
+/// use syntect::parsing::{CancellationToken, ParseState, SyntaxSet};
+///
+/// let ss = SyntaxSet::load_defaults_newlines();
+/// let mut state = ParseState::new(ss.find_syntax_by_extension("rs").unwrap());
+/// let token = CancellationToken::new();
+/// assert!(state.parse_line_cancellable("fn main() {}\n", &ss, &token).is_ok());
+///
+/// token.cancel();
+/// assert!(state.parse_line_cancellable("fn main() {}\n", &ss, &token).is_err());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    flag: Arc<AtomicBool>,
+}
+
+/// Returned instead of a result when a job was stopped through its `CancellationToken`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Cancelled;
+
+/// What parsing a line keeps between tokens: the searches done so far and the budget
+/// they're checked against.
+struct LineSearch<'c> {
+    cache: SearchCache,
+    regions: Region,
+    budget: LineBudget<'c>,
+}
+
+/// Keeps track of the work done on the current line, checked against the `ParseLimits`.
+#[derive(Debug)]
+struct LineBudget<'c> {
+    searches: usize,
+    max_searches: Option<usize>,
+    deadline: Option<Instant>,
+    exceeded: Option<ParseLineStatus>,
+    cancellation: Option<&'c CancellationToken>,
+    cancelled: bool,
+}
+
+// The limits only affect how the following lines are parsed, not where parsing is
+impl PartialEq for ParseState {
+    fn eq(&self, other: &ParseState) -> bool {
+        self.stack == other.stack &&
+            self.first_line == other.first_line &&
+            self.proto_starts == other.proto_starts
+    }
+}
+
+impl Eq for ParseState {}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 struct StateLevel {
     context: ContextId,
     prototypes: Vec<ContextId>,
-    captures: Option<(Region, String)>,
+    captures: Option<(CapturePositions, String)>,
 }
 
+/// Positions of the capture groups of the match that pushed a context, kept
+/// for contexts that use backreferences to it. Unlike `Region` this can be serialized.
+type CapturePositions = Vec<Option<(usize, usize)>>;
+
 #[derive(Debug)]
 struct RegexMatch<'a> {
     regions: Region,
@@ -157,9 +270,49 @@ impl ParseState {
             stack: vec![start_state],
             first_line: true,
             proto_starts: Vec::new(),
+            limits: ParseLimits::default(),
         }
     }
 
+    /// Create a state for a syntax as it is after the first line, when everything pushed
+    /// since has been popped again. This is the state `is_clean` checks for.
+    ///
+    /// A file can be split at lines that start in a clean state and the parts parsed
+    /// independently, for example in parallel. See `examples/parsyncat.rs`.
+    pub fn new_clean(syntax: &SyntaxReference) -> ParseState {
+        let mut state = ParseState::new(syntax);
+        state.stack.push(StateLevel {
+            context: syntax.contexts["__main"],
+            prototypes: Vec::new(),
+            captures: None,
+        });
+        state.first_line = false;
+        state
+    }
+
+    /// Returns true if this state is back at the `main` context of the syntax with nothing
+    /// else on the stack, in which case it's equal to `ParseState::new_clean(syntax)`.
+    ///
+    /// This is cheap, so it can be used to find points to split a file at while parsing.
+    pub fn is_clean(&self, syntax: &SyntaxReference) -> bool {
+        !self.first_line &&
+            self.proto_starts.is_empty() &&
+            self.stack.len() == 2 &&
+            self.stack[0].context == syntax.contexts["__start"] &&
+            self.stack[1].context == syntax.contexts["__main"] &&
+            self.stack.iter().all(|level| level.prototypes.is_empty() && level.captures.is_none())
+    }
+
+    /// Sets the limits used when parsing the following lines, see `ParseLimits`.
+    pub fn set_limits(&mut self, limits: ParseLimits) {
+        self.limits = limits;
+    }
+
+    /// The limits currently used when parsing lines.
+    pub fn limits(&self) -> &ParseLimits {
+        &self.limits
+    }
+
     /// Parses a single line of the file. Because of the way regex engines work you unfortunately
     /// have to pass in a single line contiguous in memory. This can be bad for really long lines.
     /// Sublime Text avoids this by just not highlighting lines that are too long (thousands of characters).
@@ -176,12 +329,62 @@ impl ParseState {
     /// Otherwise the parsing would return the wrong result or even panic. The
     /// reason for this is that contexts within the `SyntaxSet` are referenced
     /// via indexes.
+    ///
+    /// If `ParseLimits` are set and the line exceeds them, the line is treated as plain text,
+    /// see `parse_line_with_status` if you need to know when that happens.
     pub fn parse_line(&mut self, line: &str, syntax_set: &SyntaxSet) -> Vec<(usize, ScopeStackOp)> {
+        self.parse_line_with_status(line, syntax_set).0
+    }
+
+    /// Like `parse_line` but also returns whether the line was fully parsed or
+    /// fell back to plain text because it exceeded one of the `ParseLimits`.
+    ///
+    /// In the latter case the returned operations leave the scope stack as it was at the
+    /// start of the line and this state is unchanged, apart from no longer being at the first line.
+    pub fn parse_line_with_status(&mut self,
+                                  line: &str,
+                                  syntax_set: &SyntaxSet)
+                                  -> (Vec<(usize, ScopeStackOp)>, ParseLineStatus) {
+        match self.parse_line_inner(line, syntax_set, None) {
+            Ok(res) => res,
+            Err(Cancelled) => unreachable!("parsing can't be cancelled without a token"),
+        }
+    }
+
+    /// Like `parse_line_with_status` but gives up as soon as the `CancellationToken` is cancelled,
+    /// for example because the text being highlighted has changed in the meantime.
+    ///
+    /// If parsing was cancelled this state is left exactly as it was before the call,
+    /// so it can be used to parse the same line again or resume from a cached earlier line.
+    pub fn parse_line_cancellable(&mut self,
+                                  line: &str,
+                                  syntax_set: &SyntaxSet,
+                                  cancellation: &CancellationToken)
+                                  -> Result<(Vec<(usize, ScopeStackOp)>, ParseLineStatus), Cancelled> {
+        self.parse_line_inner(line, syntax_set, Some(cancellation))
+    }
+
+    fn parse_line_inner(&mut self,
+                        line: &str,
+                        syntax_set: &SyntaxSet,
+                        cancellation: Option<&CancellationToken>)
+                        -> Result<(Vec<(usize, ScopeStackOp)>, ParseLineStatus), Cancelled> {
         assert!(!self.stack.is_empty(),
                 "Somehow main context was popped from the stack");
+        if is_cancelled(cancellation) {
+            return Err(Cancelled);
+        }
+
         let mut match_start = 0;
         let mut res = Vec::new();
 
+        // Only pay for copying the stack if parsing the line can be aborted
+        let snapshot = if !self.limits.can_abort_line() && cancellation.is_none() {
+            None
+        } else {
+            Some((self.stack.clone(), self.proto_starts.clone(), self.first_line))
+        };
+
         if self.first_line {
             let cur_level = &self.stack[self.stack.len() - 1];
             let context = syntax_set.get_context(&cur_level.context);
@@ -191,9 +394,20 @@ impl ParseState {
             self.first_line = false;
         }
 
-        let mut regions = Region::with_capacity(8);
+        if let Some(max_line_length) = self.limits.max_line_length {
+            if line.len() > max_line_length {
+                return Ok((res, ParseLineStatus::LineTooLong));
+            }
+        }
+
+        let ops_before_line = res.len();
+
         let fnv = BuildHasherDefault::<FnvHasher>::default();
-        let mut search_cache: SearchCache = HashMap::with_capacity_and_hasher(128, fnv);
+        let mut search = LineSearch {
+            cache: HashMap::with_capacity_and_hasher(128, fnv),
+            regions: Region::with_capacity(8),
+            budget: LineBudget::new(&self.limits, cancellation),
+        };
         // Used for detecting loops with push/pop, see long comment above.
         let mut non_consuming_push_at = (0, 0);
 
@@ -201,13 +415,32 @@ impl ParseState {
             line,
             syntax_set,
             &mut match_start,
-            &mut search_cache,
-            &mut regions,
+            &mut search,
             &mut non_consuming_push_at,
             &mut res
         ) {}
 
-        res
+        let budget = search.budget;
+        if budget.cancelled {
+            if let Some((stack, proto_starts, first_line)) = snapshot {
+                self.stack = stack;
+                self.proto_starts = proto_starts;
+                self.first_line = first_line;
+            }
+            return Err(Cancelled);
+        }
+
+        if let Some(status) = budget.exceeded {
+            // Fall back to plain text for this line and forget everything it did to the state
+            res.truncate(ops_before_line);
+            if let Some((stack, proto_starts, _)) = snapshot {
+                self.stack = stack;
+                self.proto_starts = proto_starts;
+            }
+            return Ok((res, status));
+        }
+
+        Ok((res, ParseLineStatus::Complete))
     }
 
     fn parse_next_token(
@@ -215,11 +448,14 @@ impl ParseState {
         line: &str,
         syntax_set: &SyntaxSet,
         start: &mut usize,
-        search_cache: &mut SearchCache,
-        regions: &mut Region,
+        search: &mut LineSearch,
         non_consuming_push_at: &mut (usize, usize),
         ops: &mut Vec<(usize, ScopeStackOp)>,
     ) -> bool {
+        if !search.budget.check() {
+            return false;
+        }
+
         let check_pop_loop = {
             let (pos, stack_depth) = *non_consuming_push_at;
             pos == *start && stack_depth == self.stack.len()
@@ -230,7 +466,11 @@ impl ParseState {
             self.proto_starts.pop();
         }
 
-        let best_match = self.find_best_match(line, *start, syntax_set, search_cache, regions, check_pop_loop);
+        let best_match = self.find_best_match(line, *start, syntax_set, search, check_pop_loop);
+
+        if !search.budget.check() {
+            return false;
+        }
 
         if let Some(reg_match) = best_match {
             if reg_match.would_loop {
@@ -293,8 +533,7 @@ impl ParseState {
         line: &str,
         start: usize,
         syntax_set: &'a SyntaxSet,
-        search_cache: &mut SearchCache,
-        regions: &mut Region,
+        search: &mut LineSearch,
         check_pop_loop: bool,
     ) -> Option<RegexMatch<'a>> {
         let cur_level = &self.stack[self.stack.len() - 1];
@@ -326,9 +565,11 @@ impl ParseState {
             for (pat_context, pat_index) in context_iter(syntax_set, syntax_set.get_context(ctx)) {
                 let match_pat = pat_context.match_at(pat_index);
 
-                if let Some(match_region) = self.search(
-                    line, start, match_pat, captures, search_cache, regions
-                ) {
+                let maybe_region = self.search(line, start, match_pat, captures, search);
+                if !search.budget.check() {
+                    return None;
+                }
+                if let Some(match_region) = maybe_region {
                     let (match_start, match_end) = match_region.pos(0).unwrap();
 
                     // println!("matched pattern {:?} at start {} end {}", match_pat.regex_str, match_start, match_end);
@@ -372,14 +613,13 @@ impl ParseState {
               line: &str,
               start: usize,
               match_pat: &MatchPattern,
-              captures: Option<&(Region, String)>,
-              search_cache: &mut SearchCache,
-              regions: &mut Region)
+              captures: Option<&(CapturePositions, String)>,
+              search: &mut LineSearch)
               -> Option<Region> {
         // println!("{} - {:?} - {:?}", match_pat.regex_str, match_pat.has_captures, cur_level.captures.is_some());
         let match_ptr = match_pat as *const MatchPattern;
 
-        if let Some(maybe_region) = search_cache.get(&match_ptr) {
+        if let Some(maybe_region) = search.cache.get(&match_ptr) {
             if let Some(ref region) = *maybe_region {
                 let match_start = region.pos(0).unwrap().0;
                 if match_start >= start {
@@ -393,15 +633,15 @@ impl ParseState {
             }
         }
 
-        let (matched, can_cache) = if match_pat.has_captures && captures.is_some() {
-            let &(ref region, ref s) = captures.unwrap();
-            let regex = match_pat.regex_with_refs(region, s);
+        search.budget.searches += 1;
+        let (matched, can_cache) = if let (true, Some((positions, s))) = (match_pat.has_captures, captures) {
+            let regex = match_pat.regex_with_capture_positions(positions, s);
             let matched = regex.search_with_param(
                 line,
                 start,
                 line.len(),
                 SearchOptions::SEARCH_OPTION_NONE,
-                Some(regions),
+                Some(&mut search.regions),
                 MatchParam::default(),
             );
             (matched, false)
@@ -412,7 +652,7 @@ impl ParseState {
                 start,
                 line.len(),
                 SearchOptions::SEARCH_OPTION_NONE,
-                Some(regions),
+                Some(&mut search.regions),
                 MatchParam::default()
             );
             (matched, true)
@@ -422,21 +662,21 @@ impl ParseState {
         // For example, in case of catastrophic backtracking, onig should
         // fail with a "retry-limit-in-match over" error eventually.
         if let Ok(Some(match_start)) = matched {
-            let match_end = regions.pos(0).unwrap().1;
+            let match_end = search.regions.pos(0).unwrap().1;
             // this is necessary to avoid infinite looping on dumb patterns
             let does_something = match match_pat.operation {
                 MatchOperation::None => match_start != match_end,
                 _ => true,
             };
             if can_cache && does_something {
-                search_cache.insert(match_pat, Some(regions.clone()));
+                search.cache.insert(match_pat, Some(search.regions.clone()));
             }
             if does_something {
                 // print!("catch {} at {} on {}", match_pat.regex_str, match_start, line);
-                return Some(regions.clone());
+                return Some(search.regions.clone());
             }
         } else if can_cache {
-            search_cache.insert(match_pat, None);
+            search.cache.insert(match_pat, None);
         }
         None
     }
@@ -644,7 +884,7 @@ impl ParseState {
                     uses_backrefs = uses_backrefs || proto_ids.iter().any(|id| syntax_set.get_context(id).uses_backrefs);
                 }
                 if uses_backrefs {
-                    Some((regions.clone(), line.to_owned()))
+                    Some((capture_positions(regions), line.to_owned()))
                 } else {
                     None
                 }
@@ -659,6 +899,84 @@ impl ParseState {
     }
 }
 
+impl CancellationToken {
+    /// A token that isn't cancelled yet
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Asks all jobs using this token (or a clone of it) to stop
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether `cancel` has been called on this token or a clone of it
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+fn is_cancelled(cancellation: Option<&CancellationToken>) -> bool {
+    match cancellation {
+        Some(token) => token.is_cancelled(),
+        None => false,
+    }
+}
+
+impl ParseLimits {
+    /// Whether a line can be given up on halfway through, the line length is checked
+    /// before parsing starts
+    fn can_abort_line(&self) -> bool {
+        self.max_searches_per_line.is_some() || self.max_time_per_line.is_some()
+    }
+}
+
+impl<'c> LineBudget<'c> {
+    fn new(limits: &ParseLimits, cancellation: Option<&'c CancellationToken>) -> LineBudget<'c> {
+        LineBudget {
+            searches: 0,
+            max_searches: limits.max_searches_per_line,
+            deadline: limits.max_time_per_line.map(|d| Instant::now() + d),
+            exceeded: None,
+            cancellation,
+            cancelled: false,
+        }
+    }
+
+    /// Returns false, and remembers why, once the line has used up its budget
+    /// or parsing was cancelled
+    fn check(&mut self) -> bool {
+        if self.exceeded.is_some() || self.cancelled {
+            return false;
+        }
+        if is_cancelled(self.cancellation) {
+            self.cancelled = true;
+            return false;
+        }
+        if let Some(max_searches) = self.max_searches {
+            if self.searches > max_searches {
+                self.exceeded = Some(ParseLineStatus::TooManySearches);
+                return false;
+            }
+        }
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                self.exceeded = Some(ParseLineStatus::TimedOut);
+                return false;
+            }
+        }
+        true
+    }
+}
+
 #[cfg(feature = "yaml-load")]
 #[cfg(test)]
 mod tests {
@@ -768,6 +1086,94 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn can_serialize_state_with_backrefs() {
+        use serde_json;
+
+        let ss = SyntaxSet::load_from_folder("testdata/Packages").unwrap();
+        let mut state = {
+            let syntax = ss.find_syntax_by_name("Ruby on Rails").unwrap();
+            ParseState::new(syntax)
+        };
+        ops(&mut state, "lol = <<-SQL.strip", &ss);
+
+        let json = serde_json::to_string(&state).unwrap();
+        let mut restored: ParseState = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, state);
+
+        // the restored state still knows which marker ends the HEREDOC
+        assert_eq!(ops(&mut restored, "wow", &ss), vec![]);
+        assert_eq!(ops(&mut restored, "SQL", &ss), vec![
+            (0, Pop(1)),
+            (0, Push(Scope::new("punctuation.definition.string.end.ruby").unwrap())),
+            (3, Pop(1)),
+            (3, Pop(1)),
+        ]);
+    }
+
+    #[test]
+    fn can_resume_parsing_from_clean_state() {
+        let ss = SyntaxSet::load_from_folder("testdata/Packages").unwrap();
+        let syntax = ss.find_syntax_by_name("Rust").unwrap();
+        let lines: Vec<&str> = include_str!("../../testdata/parser.rs").lines().collect();
+
+        let mut state = ParseState::new(syntax);
+        assert!(!state.is_clean(syntax));
+        let mut expected = Vec::new();
+        let mut split = None;
+        for (i, line) in lines.iter().enumerate() {
+            expected.push(state.parse_line(line, &ss));
+            // only split after a non-trivial part of the file
+            if split.is_none() && i > 10 && state.is_clean(syntax) {
+                split = Some(i + 1);
+            }
+        }
+        let split = split.expect("no clean state in file");
+
+        let mut state = ParseState::new_clean(syntax);
+        assert!(state.is_clean(syntax));
+        for (line, expected_ops) in lines[split..].iter().zip(&expected[split..]) {
+            assert_eq!(&state.parse_line(line, &ss), expected_ops);
+        }
+    }
+
+    #[test]
+    fn chunked_parsing_matches_sequential_parsing() {
+        use util::LinesWithEndings;
+
+        let ss = SyntaxSet::load_defaults_newlines();
+        let syntax = ss.find_syntax_by_extension("rs").unwrap();
+        let lines: Vec<&str> = LinesWithEndings::from(include_str!("../../testdata/parser.rs")).collect();
+
+        let mut state = ParseState::new(syntax);
+        let expected: Vec<_> = lines.iter().map(|line| state.parse_line(line, &ss)).collect();
+
+        // Parse every chunk on its own as if it started in a clean state, then parse the
+        // ones where that guess was wrong again, like `examples/parsyncat.rs` does
+        let chunks: Vec<&[&str]> = lines.chunks(200).collect();
+        let parse_chunk = |mut state: ParseState, chunk: &[&str]| {
+            let ops: Vec<_> = chunk.iter().map(|line| state.parse_line(line, &ss)).collect();
+            (ops, state)
+        };
+        let mut parsed: Vec<_> = chunks.iter().enumerate().map(|(i, chunk)| {
+            let state = if i == 0 { ParseState::new(syntax) } else { ParseState::new_clean(syntax) };
+            parse_chunk(state, chunk)
+        }).collect();
+        let mut clean_guesses = 0;
+        for i in 1..chunks.len() {
+            if parsed[i - 1].1.is_clean(syntax) {
+                clean_guesses += 1;
+            } else {
+                let state = parsed[i - 1].1.clone();
+                parsed[i] = parse_chunk(state, chunks[i]);
+            }
+        }
+        assert!(clean_guesses > 0, "no chunk started in a clean state");
+
+        let actual: Vec<_> = parsed.into_iter().flat_map(|(ops, _)| ops).collect();
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn can_parse_preprocessor_rules() {
         let ss = SyntaxSet::load_from_folder("testdata/Packages").unwrap();
@@ -846,6 +1252,52 @@ mod tests {
         assert_ne!(state1, state2);
     }
 
+    #[test]
+    fn can_skip_lines_over_length_limit() {
+        let ss = limits_test_syntax_set();
+        let mut state = ParseState::new(&ss.syntaxes()[0]);
+        state.set_limits(ParseLimits { max_line_length: Some(10), ..ParseLimits::default() });
+
+        let (ops, status) = state.parse_line_with_status("\"a line that is too long", &ss);
+        assert_eq!(status, ParseLineStatus::LineTooLong);
+        assert_eq!(ops, vec![(0, Push(Scope::new("source.test").unwrap()))]);
+
+        // the string wasn't started by the skipped line
+        let (ops, status) = state.parse_line_with_status("word", &ss);
+        assert_eq!(status, ParseLineStatus::Complete);
+        assert_eq!(ops, vec![(0, Push(Scope::new("word").unwrap())), (4, Pop(1))]);
+    }
+
+    #[test]
+    fn can_abort_lines_over_search_limit() {
+        let ss = limits_test_syntax_set();
+        let mut state = ParseState::new(&ss.syntaxes()[0]);
+        state.set_limits(ParseLimits { max_searches_per_line: Some(1), ..ParseLimits::default() });
+
+        let (ops, status) = state.parse_line_with_status("\"abc def", &ss);
+        assert_eq!(status, ParseLineStatus::TooManySearches);
+        assert_eq!(ops, vec![(0, Push(Scope::new("source.test").unwrap()))]);
+
+        // the state is left as it was before the aborted line
+        state.set_limits(ParseLimits::default());
+        let (ops, status) = state.parse_line_with_status("word", &ss);
+        assert_eq!(status, ParseLineStatus::Complete);
+        assert_eq!(ops, vec![(0, Push(Scope::new("word").unwrap())), (4, Pop(1))]);
+    }
+
+    #[test]
+    fn limits_dont_affect_state_equality() {
+        let ss = limits_test_syntax_set();
+        let mut limited = ParseState::new(&ss.syntaxes()[0]);
+        limited.set_limits(ParseLimits { max_line_length: Some(100), ..ParseLimits::default() });
+        let mut unlimited = ParseState::new(&ss.syntaxes()[0]);
+        assert_eq!(limited, unlimited);
+
+        limited.parse_line("\"abc", &ss);
+        unlimited.parse_line("\"abc", &ss);
+        assert_eq!(limited, unlimited);
+    }
+
     #[test]
     fn can_parse_non_nested_clear_scopes() {
         let line = "'hello #simple_cleared_scopes_test world test \\n '";
@@ -1600,6 +2052,26 @@ contexts:
         ops(&mut state, line, &syntax_set)
     }
 
+    fn limits_test_syntax_set() -> SyntaxSet {
+        let syntax = SyntaxDefinition::load_from_str(r#"
+        name: Limits Test
+        scope: source.test
+        contexts:
+          main:
+            - match: '"'
+              push: string
+            - match: \w+
+              scope: word
+          string:
+            - meta_scope: string
+            - match: '"'
+              pop: true
+        "#, true, None).unwrap();
+        let mut builder = SyntaxSetBuilder::new();
+        builder.add(syntax);
+        builder.build()
+    }
+
     fn ops(state: &mut ParseState, line: &str, syntax_set: &SyntaxSet) -> Vec<(usize, ScopeStackOp)> {
         let ops = state.parse_line(line, syntax_set);
         debug_print_ops(line, &ops);
@@ -326,7 +326,7 @@ impl<'de> Deserialize<'de> for Scope {
 
 /// Wrapper to get around the fact Rust f64 doesn't implement Ord
 /// and there is no non-NaN float type
-#[derive(Debug, Copy, Clone, PartialOrd, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq, Serialize, Deserialize)]
 pub struct MatchPower(pub f64);
 impl Eq for MatchPower {}
 impl Ord for MatchPower {
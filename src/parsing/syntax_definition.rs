@@ -203,6 +203,11 @@ impl ContextReference {
     }
 }
 
+/// The start and end of every capture group in a region, as a `ParseState` keeps them
+pub(crate) fn capture_positions(region: &Region) -> Vec<Option<(usize, usize)>> {
+    (0..region.len()).map(|i| region.pos(i)).collect()
+}
+
 pub(crate) fn substitute_backrefs_in_regex<F>(regex_str: &str, substituter: F) -> String
     where F: Fn(usize) -> Option<String>
 {
@@ -263,16 +268,26 @@ impl MatchPattern {
     /// used for match patterns which refer to captures from the pattern
     /// that pushed them.
     pub fn regex_with_substitutes(&self, region: &Region, s: &str) -> String {
+        self.substitutes_with_capture_positions(&capture_positions(region), s)
+    }
+
+    fn substitutes_with_capture_positions(&self, positions: &[Option<(usize, usize)>], s: &str) -> String {
         substitute_backrefs_in_regex(&self.regex_str, |i| {
-            region.pos(i).map(|(start, end)| escape(&s[start..end]))
+            positions.get(i).and_then(|pos| *pos).map(|(start, end)| escape(&s[start..end]))
         })
     }
 
     /// Used by the parser to compile a regex which needs to reference
     /// regions from another matched pattern.
     pub fn regex_with_refs(&self, region: &Region, s: &str) -> Regex {
+        self.regex_with_capture_positions(&capture_positions(region), s)
+    }
+
+    /// Like `regex_with_refs`, but with the capture positions as saved in a `ParseState`
+    /// instead of an onig `Region`, so that states can be serialized.
+    pub(crate) fn regex_with_capture_positions(&self, positions: &[Option<(usize, usize)>], s: &str) -> Regex {
         // TODO don't panic on invalid regex
-        Regex::with_options(&self.regex_with_substitutes(region, s),
+        Regex::with_options(&self.substitutes_with_capture_positions(positions, s),
                             RegexOptions::REGEX_OPTION_CAPTURE_GROUP,
                             Syntax::default())
             .unwrap()
@@ -0,0 +1,465 @@
+//! Guessing the syntax of a file from its name and contents, for files where
+//! the extension isn't enough (or there is none).
+use super::syntax_set::{SyntaxSet, SyntaxReference};
+use super::parser::{ParseState, ParseLimits};
+use super::scope::ScopeStack;
+use util::LinesWithEndings;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::ptr;
+
+use onig::Regex;
+
+/// Where a `SyntaxCandidate` was found, ordered from least to most reliable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DetectionSource {
+    /// Parsing a sample of the content with the syntax highlighted a lot of it
+    Heuristic,
+    /// The `first_line_match` of the syntax matched
+    FirstLine,
+    /// The extension of the file name
+    Extension,
+    /// The interpreter in a `#!` line
+    Shebang,
+    /// A well-known file name like `Dockerfile`, or a syntax that lists the full name
+    FileName,
+    /// A Vim or Emacs modeline
+    Modeline,
+}
+
+/// A syntax that might be the right one for a file, see `SyntaxDetector::detect`
+#[derive(Debug, Clone)]
+pub struct SyntaxCandidate<'a> {
+    pub syntax: &'a SyntaxReference,
+    /// Between 0 and 1, higher is more likely
+    pub confidence: f64,
+    /// The most reliable way this syntax was found
+    pub source: DetectionSource,
+}
+
+/// Detects the syntax of files using more clues than `SyntaxSet::find_syntax_for_file`:
+///
+/// * Vim (`vim: set ft=python:`) and Emacs (`-*- mode: python -*-`) modelines in the
+///   first or last few lines
+/// * the interpreter of a shebang line, like `#!/usr/bin/env python3`
+/// * well-known file names without an extension, like `Dockerfile` or `Makefile.am`
+/// * the extension and `first_line_match` of the syntaxes, like `find_syntax_for_file`
+/// * optionally, how much of a sample of the content each syntax highlights
+///
+/// Interpreters and file names are mapped to a token that is looked up with
+/// `SyntaxSet::find_syntax_by_token`, so either an extension or a syntax name.
+/// Mappings to syntaxes that aren't in the set are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use syntect::parsing::{SyntaxSet, SyntaxDetector};
+///
+/// let ss = SyntaxSet::load_defaults_newlines();
+/// let detector = SyntaxDetector::new(&ss);
+/// let candidates = detector.detect(Some("upload"), "#!/usr/bin/env python3\nprint('hi')\n");
+/// assert_eq!(candidates[0].syntax.name, "Python");
+/// ```
+#[derive(Debug)]
+pub struct SyntaxDetector<'a> {
+    syntax_set: &'a SyntaxSet,
+    file_names: HashMap<String, String>,
+    interpreters: HashMap<String, String>,
+    modeline_lines: usize,
+    heuristic_lines: Option<usize>,
+    vim_modeline: Regex,
+    emacs_modeline: Regex,
+}
+
+const DEFAULT_FILE_NAMES: &[(&str, &str)] = &[
+    ("Dockerfile", "Dockerfile"),
+    ("Containerfile", "Dockerfile"),
+    ("Makefile", "Makefile"),
+    ("makefile", "Makefile"),
+    ("GNUmakefile", "Makefile"),
+    ("Makefile.am", "Makefile"),
+    ("Makefile.in", "Makefile"),
+    ("CMakeLists.txt", "cmake"),
+    ("Rakefile", "rb"),
+    ("Gemfile", "rb"),
+    ("Guardfile", "rb"),
+    ("Podfile", "rb"),
+    ("Vagrantfile", "rb"),
+    ("Jenkinsfile", "groovy"),
+    ("PKGBUILD", "sh"),
+    ("APKBUILD", "sh"),
+    (".bashrc", "sh"),
+    (".bash_profile", "sh"),
+    (".bash_logout", "sh"),
+    (".profile", "sh"),
+    (".zshrc", "sh"),
+    (".zprofile", "sh"),
+    (".vimrc", "vim"),
+    (".gitconfig", "gitconfig"),
+];
+
+const DEFAULT_INTERPRETERS: &[(&str, &str)] = &[
+    ("sh", "sh"),
+    ("bash", "sh"),
+    ("zsh", "sh"),
+    ("dash", "sh"),
+    ("ksh", "sh"),
+    ("ash", "sh"),
+    ("python", "py"),
+    ("pypy", "py"),
+    ("ruby", "rb"),
+    ("jruby", "rb"),
+    ("perl", "pl"),
+    ("node", "js"),
+    ("nodejs", "js"),
+    ("php", "php"),
+    ("lua", "lua"),
+    ("Rscript", "r"),
+    ("groovy", "groovy"),
+    ("scala", "scala"),
+    ("tclsh", "tcl"),
+    ("awk", "awk"),
+    ("gawk", "awk"),
+    ("make", "Makefile"),
+    ("runhaskell", "hs"),
+    ("ocaml", "ml"),
+];
+
+/// Lines longer than this aren't used for the content heuristic
+const MAX_SAMPLE_LINE_LENGTH: usize = 1000;
+/// The content heuristic is less reliable than any other source
+const MAX_HEURISTIC_CONFIDENCE: f64 = 0.5;
+/// Syntaxes that highlight less of the sample than this aren't suggested
+const MIN_HEURISTIC_COVERAGE: f64 = 0.2;
+
+impl DetectionSource {
+    /// How much a match from this source is trusted on its own
+    fn confidence(self) -> f64 {
+        match self {
+            DetectionSource::Modeline => 0.95,
+            DetectionSource::FileName => 0.9,
+            DetectionSource::Shebang => 0.85,
+            DetectionSource::Extension => 0.8,
+            DetectionSource::FirstLine => 0.6,
+            DetectionSource::Heuristic => MAX_HEURISTIC_CONFIDENCE,
+        }
+    }
+}
+
+impl<'a> SyntaxDetector<'a> {
+    /// Creates a detector with a default set of file names and interpreters,
+    /// looking at modelines in the first and last 5 lines and without the content heuristic.
+    pub fn new(syntax_set: &'a SyntaxSet) -> SyntaxDetector<'a> {
+        let to_map = |pairs: &[(&str, &str)]| {
+            pairs.iter().map(|&(k, v)| (k.to_owned(), v.to_owned())).collect()
+        };
+        SyntaxDetector {
+            syntax_set,
+            file_names: to_map(DEFAULT_FILE_NAMES),
+            interpreters: to_map(DEFAULT_INTERPRETERS),
+            modeline_lines: 5,
+            heuristic_lines: None,
+            vim_modeline: Regex::new(r"(?:^|\s)(?:vi|vim|ex)(?:[<=>]?\d+)?:.*?\b(?:ft|filetype|syntax|syn)=([\w+#.-]+)").unwrap(),
+            emacs_modeline: Regex::new(r"-\*-(.*?)-\*-").unwrap(),
+        }
+    }
+
+    /// Maps a file name (without directories) to a syntax token like `rb` or `Makefile`,
+    /// replacing any existing mapping for that name.
+    pub fn add_file_name(&mut self, file_name: &str, token: &str) {
+        self.file_names.insert(file_name.to_owned(), token.to_owned());
+    }
+
+    /// Maps the name of an interpreter used in shebang lines to a syntax token.
+    /// Version suffixes are handled already, `python3.7` uses the mapping for `python`.
+    pub fn add_interpreter(&mut self, interpreter: &str, token: &str) {
+        self.interpreters.insert(interpreter.to_owned(), token.to_owned());
+    }
+
+    /// Sets how many lines at the start and end of the content are searched for modelines.
+    pub fn set_modeline_lines(&mut self, lines: usize) {
+        self.modeline_lines = lines;
+    }
+
+    /// Enables scoring all visible syntaxes by parsing the first `sample_lines` lines of the
+    /// content with each of them, or disables that with `None`.
+    ///
+    /// This is a lot slower than the other methods and only a rough guess, so candidates
+    /// found this way always have a lower confidence than any other. It expects the syntax set
+    /// to be one that was loaded for lines including newlines.
+    pub fn set_heuristic(&mut self, sample_lines: Option<usize>) {
+        self.heuristic_lines = sample_lines;
+    }
+
+    /// Returns the candidates for a file with the given name (if any) and content,
+    /// most likely first. If the same syntax is found in multiple ways its confidence
+    /// is higher than that of each of them.
+    ///
+    /// The file name may also be a path, only the part after the last separator is used.
+    pub fn detect(&self, file_name: Option<&str>, content: &str) -> Vec<SyntaxCandidate<'a>> {
+        let mut candidates: Vec<SyntaxCandidate<'a>> = Vec::new();
+        {
+            let mut add = |syntax: Option<&'a SyntaxReference>, source: DetectionSource, confidence: f64| {
+                let syntax = match syntax {
+                    Some(syntax) => syntax,
+                    None => return,
+                };
+                if let Some(existing) = candidates.iter_mut().find(|c| ptr::eq(c.syntax, syntax)) {
+                    // treat the clues as independent evidence
+                    existing.confidence = 1.0 - (1.0 - existing.confidence) * (1.0 - confidence);
+                    if source > existing.source {
+                        existing.source = source;
+                    }
+                    return;
+                }
+                candidates.push(SyntaxCandidate { syntax, confidence, source });
+            };
+
+            if let Some(file_name) = file_name.map(base_name) {
+                add(self.file_names.get(file_name).and_then(|token| self.syntax_set.find_syntax_by_token(token)),
+                    DetectionSource::FileName, DetectionSource::FileName.confidence());
+                add(self.syntax_set.find_syntax_by_extension(file_name),
+                    DetectionSource::FileName, DetectionSource::FileName.confidence());
+                if let Some(dot) = file_name.rfind('.') {
+                    if dot > 0 {
+                        add(self.syntax_set.find_syntax_by_extension(&file_name[dot + 1..]),
+                            DetectionSource::Extension, DetectionSource::Extension.confidence());
+                    }
+                }
+            }
+
+            for syntax in self.find_by_modelines(content) {
+                add(Some(syntax), DetectionSource::Modeline, DetectionSource::Modeline.confidence());
+            }
+
+            let first_line = content.lines().next().unwrap_or("");
+            add(self.find_by_shebang(first_line), DetectionSource::Shebang, DetectionSource::Shebang.confidence());
+            add(self.syntax_set.find_syntax_by_first_line(first_line),
+                DetectionSource::FirstLine, DetectionSource::FirstLine.confidence());
+
+            if let Some(sample_lines) = self.heuristic_lines {
+                for (syntax, coverage) in self.score_by_content(content, sample_lines) {
+                    add(Some(syntax), DetectionSource::Heuristic, coverage * MAX_HEURISTIC_CONFIDENCE);
+                }
+            }
+        }
+
+        candidates.sort_by(|a, b| {
+            b.confidence.partial_cmp(&a.confidence).unwrap().then(b.source.cmp(&a.source))
+        });
+        candidates
+    }
+
+    /// Reads a file and calls `detect` with its name and contents.
+    /// Invalid UTF-8 in the file is replaced, so binary files don't cause an error.
+    pub fn detect_file<P: AsRef<Path>>(&self, path: P) -> io::Result<Vec<SyntaxCandidate<'a>>> {
+        let path = path.as_ref();
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        let content = String::from_utf8_lossy(&bytes);
+        Ok(self.detect(path.file_name().and_then(|n| n.to_str()), &content))
+    }
+
+    fn find_by_modelines(&self, content: &str) -> Vec<&'a SyntaxReference> {
+        let lines: Vec<&str> = content.lines().collect();
+        let head = lines.len().min(self.modeline_lines);
+        let tail_start = lines.len().saturating_sub(self.modeline_lines).max(head);
+        let mut found = Vec::new();
+        for line in lines[..head].iter().chain(&lines[tail_start..]) {
+            let mode = self.vim_modeline.captures(line)
+                .and_then(|caps| caps.at(1))
+                .or_else(|| self.emacs_modeline.captures(line).and_then(|caps| caps.at(1)).and_then(emacs_mode));
+            if let Some(syntax) = mode.and_then(|mode| self.syntax_set.find_syntax_by_token(mode)) {
+                found.push(syntax);
+            }
+        }
+        found
+    }
+
+    fn find_by_shebang(&self, first_line: &str) -> Option<&'a SyntaxReference> {
+        let interpreter = shebang_interpreter(first_line)?;
+        // `python3.7` -> `python3` -> `python`
+        let mut name = interpreter;
+        loop {
+            if let Some(token) = self.interpreters.get(name) {
+                return self.syntax_set.find_syntax_by_token(token);
+            }
+            let trimmed = name.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.' || c == '-');
+            if trimmed.len() == name.len() || trimmed.is_empty() {
+                return self.syntax_set.find_syntax_by_token(interpreter);
+            }
+            name = trimmed;
+        }
+    }
+
+    /// The fraction of non-whitespace characters in the sample each visible syntax
+    /// puts in a scope below its top-level scope, for syntaxes that reach the minimum.
+    fn score_by_content(&self, content: &str, sample_lines: usize) -> Vec<(&'a SyntaxReference, f64)> {
+        let sample: Vec<&str> = LinesWithEndings::from(content)
+            .take(sample_lines)
+            .filter(|line| line.len() <= MAX_SAMPLE_LINE_LENGTH)
+            .collect();
+        let limits = ParseLimits {
+            max_line_length: Some(MAX_SAMPLE_LINE_LENGTH),
+            max_searches_per_line: Some(MAX_SAMPLE_LINE_LENGTH * 10),
+            max_time_per_line: None,
+        };
+
+        let mut scores = Vec::new();
+        for syntax in self.syntax_set.syntaxes().iter().filter(|s| !s.hidden) {
+            let mut state = ParseState::new(syntax);
+            state.set_limits(limits);
+            let mut stack = ScopeStack::new();
+            let mut covered = 0;
+            let mut total = 0;
+            for line in &sample {
+                let ops = state.parse_line(line, self.syntax_set);
+                let mut start = 0;
+                let mut count = |text: &str, stack: &ScopeStack| {
+                    let chars = text.chars().filter(|c| !c.is_whitespace()).count();
+                    total += chars;
+                    if stack.len() > 1 {
+                        covered += chars;
+                    }
+                };
+                for (index, op) in ops {
+                    count(&line[start..index], &stack);
+                    stack.apply(&op);
+                    start = index;
+                }
+                count(&line[start..], &stack);
+            }
+            if total > 0 {
+                let coverage = covered as f64 / total as f64;
+                if coverage >= MIN_HEURISTIC_COVERAGE {
+                    scores.push((syntax, coverage));
+                }
+            }
+        }
+        scores
+    }
+}
+
+fn base_name(path: &str) -> &str {
+    path.rsplit(&['/', '\\'][..]).next().unwrap_or(path)
+}
+
+/// The name of the interpreter of a `#!` line, skipping `env` and its options
+fn shebang_interpreter(line: &str) -> Option<&str> {
+    if !line.starts_with("#!") {
+        return None;
+    }
+    let mut words = line[2..].split_whitespace();
+    let program = base_name(words.next()?);
+    if program != "env" {
+        return Some(program);
+    }
+    words.find(|word| !word.starts_with('-') && !word.contains('='))
+        .map(base_name)
+}
+
+/// The mode of an Emacs modeline, either `-*- python -*-` or `-*- mode: python; ... -*-`
+fn emacs_mode(vars: &str) -> Option<&str> {
+    if !vars.contains(':') {
+        let mode = vars.trim();
+        return if mode.is_empty() { None } else { Some(mode) };
+    }
+    vars.split(';')
+        .filter_map(|var| {
+            let mut parts = var.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) if key.trim().eq_ignore_ascii_case("mode") => Some(value.trim()),
+                _ => None,
+            }
+        })
+        .next()
+}
+
+#[cfg(feature = "yaml-load")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parsing::{SyntaxDefinition, SyntaxSetBuilder};
+
+    #[test]
+    fn can_parse_shebangs() {
+        assert_eq!(shebang_interpreter("#!/bin/sh"), Some("sh"));
+        assert_eq!(shebang_interpreter("#! /usr/bin/python3.7 -u"), Some("python3.7"));
+        assert_eq!(shebang_interpreter("#!/usr/bin/env python3"), Some("python3"));
+        assert_eq!(shebang_interpreter("#!/usr/bin/env -S PATH=/opt node --harmony"), Some("node"));
+        assert_eq!(shebang_interpreter("# not a shebang"), None);
+    }
+
+    #[test]
+    fn can_parse_emacs_modes() {
+        assert_eq!(emacs_mode(" ruby "), Some("ruby"));
+        assert_eq!(emacs_mode(" coding: utf-8; mode: C++; "), Some("C++"));
+        assert_eq!(emacs_mode(" coding: utf-8 "), None);
+    }
+
+    #[test]
+    fn can_detect_by_name_shebang_and_modeline() {
+        let ss = SyntaxSet::load_from_folder("testdata/Packages").unwrap();
+        let detector = SyntaxDetector::new(&ss);
+        let best = |file_name, content| {
+            let candidates = detector.detect(file_name, content);
+            (candidates[0].syntax.name.clone(), candidates[0].source)
+        };
+
+        assert_eq!(best(Some("src/Makefile.am"), "all:\n"), ("Makefile".to_owned(), DetectionSource::FileName));
+        assert_eq!(best(Some("Rakefile"), ""), ("Ruby".to_owned(), DetectionSource::FileName));
+        assert_eq!(best(Some("upload"), "#!/usr/bin/env python3\n"), ("Python".to_owned(), DetectionSource::Shebang));
+        assert_eq!(best(None, "#!/usr/local/bin/ruby2.5 -w\n"), ("Ruby".to_owned(), DetectionSource::Shebang));
+        assert_eq!(best(None, "fn main() {}\n\n// vim: set ts=4 ft=rust:\n"), ("Rust".to_owned(), DetectionSource::Modeline));
+        assert_eq!(best(None, "# -*- coding: utf-8; mode: ruby -*-\nputs 1\n"), ("Ruby".to_owned(), DetectionSource::Modeline));
+        // the modeline wins over the extension
+        assert_eq!(best(Some("script.txt"), "# vim: ft=ruby\n"), ("Ruby".to_owned(), DetectionSource::Modeline));
+        assert!(detector.detect(Some("upload"), "just some text\n").is_empty());
+    }
+
+    #[test]
+    fn agreeing_clues_increase_confidence() {
+        let ss = SyntaxSet::load_from_folder("testdata/Packages").unwrap();
+        let detector = SyntaxDetector::new(&ss);
+        let shebang_only = detector.detect(Some("upload"), "#!/bin/bash\n");
+        let with_extension = detector.detect(Some("upload.sh"), "#!/bin/bash\n");
+        assert_eq!(shebang_only[0].syntax.name, with_extension[0].syntax.name);
+        assert!(with_extension[0].confidence > shebang_only[0].confidence);
+        assert!(with_extension[0].confidence <= 1.0);
+    }
+
+    #[test]
+    fn can_detect_by_content() {
+        let mut builder = SyntaxSetBuilder::new();
+        builder.add(SyntaxDefinition::load_from_str(r#"
+        name: Words
+        scope: source.words
+        contexts:
+          main:
+            - match: '[a-z]+'
+              scope: word
+        "#, true, None).unwrap());
+        builder.add(SyntaxDefinition::load_from_str(r#"
+        name: Numbers
+        scope: source.numbers
+        contexts:
+          main:
+            - match: '\d+'
+              scope: number
+        "#, true, None).unwrap());
+        let ss = builder.build();
+
+        let mut detector = SyntaxDetector::new(&ss);
+        assert!(detector.detect(None, "some words\nand more\n").is_empty());
+
+        detector.set_heuristic(Some(10));
+        let candidates = detector.detect(None, "some words\nand more\n");
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].syntax.name, "Words");
+        assert_eq!(candidates[0].source, DetectionSource::Heuristic);
+        assert!(candidates[0].confidence <= MAX_HEURISTIC_CONFIDENCE);
+    }
+}
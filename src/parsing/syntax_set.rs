@@ -15,11 +15,22 @@ use walkdir::WalkDir;
 use std::io::Read;
 use std::io::{self, BufRead, BufReader};
 use std::fs::File;
-use std::mem;
 
+use std::hash::Hasher;
+use std::fmt;
+
+use fnv::FnvHasher;
 use lazycell::AtomicLazyCell;
 use onig::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::{self, SerializeTuple};
+use serde_json;
 use parsing::syntax_definition::ContextId;
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+use dumps::ContextBlob;
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+use std::thread;
 
 /// A syntax set holds multiple syntaxes that have been linked together.
 ///
@@ -27,24 +38,98 @@ use parsing::syntax_definition::ContextId;
 ///
 /// After building, the syntax set is immutable and can no longer be modified.
 /// But you can convert it back to a builder by using `into_builder`.
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// Serializing a syntax set includes its metadata. Dumps made by older versions,
+/// which didn't include the metadata, can be loaded with `dumps::legacy_syntax_set_from_binary`.
+#[derive(Debug)]
 pub struct SyntaxSet {
-    syntaxes: Vec<SyntaxReference>,
-    contexts: Vec<Context>,
+    pub(crate) syntaxes: Vec<SyntaxReference>,
+    /// Empty for a lazily loaded set, which keeps its contexts in `lazy_contexts`
+    pub(crate) contexts: Vec<Context>,
     /// Stores the syntax index for every path that was loaded
-    path_syntaxes: Vec<(String, usize)>,
+    pub(crate) path_syntaxes: Vec<(String, usize)>,
 
-    #[serde(skip_serializing, skip_deserializing, default = "AtomicLazyCell::new")]
     first_line_cache: AtomicLazyCell<FirstLineCache>,
+    /// Computed by `fingerprint` when it's first called
+    fingerprint: AtomicLazyCell<u64>,
     /// Metadata, e.g. indent and commenting information.
-    /// NOTE: if serializing, you should handle metadata manually; that is,
-    /// you should serialize and deserialize it separately. See
-    /// `examples/gendata.rs` for an example.
     #[cfg(feature = "metadata")]
-    #[serde(skip, default)]
     pub(crate) metadata: Metadata,
+    /// The contexts of a set loaded with `dumps::lazy_syntax_set_from_binary`
+    #[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+    pub(crate) lazy_contexts: Option<LazyContexts>,
+}
+
+/// The contexts of a lazily loaded syntax set, which are decompressed one syntax
+/// at a time when they're first used
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+#[derive(Debug)]
+pub(crate) struct LazyContexts {
+    /// Only empty for contexts that haven't been used yet
+    pub(crate) contexts: Vec<AtomicLazyCell<Context>>,
+    /// The compressed contexts, sorted by the index of their first context
+    blobs: Vec<ContextBlob>,
+}
+
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+impl Clone for LazyContexts {
+    fn clone(&self) -> LazyContexts {
+        // Contexts that haven't been loaded yet stay that way
+        let contexts = self.contexts.iter().map(|cell| {
+            let clone = AtomicLazyCell::new();
+            if let Some(context) = cell.borrow() {
+                clone.fill(context.clone()).ok();
+            }
+            clone
+        }).collect();
+        LazyContexts { contexts, blobs: self.blobs.clone() }
+    }
+}
+
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+impl LazyContexts {
+    #[inline(always)]
+    fn get(&self, index: usize) -> &Context {
+        match self.contexts[index].borrow() {
+            Some(context) => context,
+            None => self.load(index),
+        }
+    }
+
+    /// Decompresses the contexts of the syntax the context at `index` belongs to
+    #[cold]
+    fn load(&self, index: usize) -> &Context {
+        let blob = match self.blobs.binary_search_by_key(&index, |blob| blob.start) {
+            Ok(i) => &self.blobs[i],
+            Err(i) => &self.blobs[i - 1],
+        };
+        for (i, context) in blob.load().into_iter().enumerate() {
+            // Fails if another thread loaded the same syntax in the meantime, which is fine
+            self.contexts[blob.start + i].fill(context).ok();
+        }
+        loop {
+            // The other thread might not be done filling this context yet
+            if let Some(context) = self.contexts[index].borrow() {
+                return context;
+            }
+            thread::yield_now();
+        }
+    }
+
+    fn into_contexts(self) -> Vec<Context> {
+        for i in 0..self.contexts.len() {
+            self.get(i);
+        }
+        self.contexts.into_iter().map(|cell| cell.into_inner().unwrap()).collect()
+    }
 }
 
+/// Serialized syntax sets start with this, where ones from before metadata was included
+/// started with the number of syntaxes, see `LegacySyntaxSet`.
+const SYNTAX_SET_DUMP_MAGIC: u64 = 0x5374_6e74_6365_7453;
+/// The version of the layout following `SYNTAX_SET_DUMP_MAGIC`
+const SYNTAX_SET_DUMP_VERSION: u32 = 1;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SyntaxReference {
     pub name: String,
@@ -101,8 +186,97 @@ impl Clone for SyntaxSet {
             path_syntaxes: self.path_syntaxes.clone(),
             // Will need to be re-initialized
             first_line_cache: AtomicLazyCell::new(),
+            fingerprint: AtomicLazyCell::new(),
             #[cfg(feature = "metadata")]
             metadata: self.metadata.clone(),
+            #[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+            lazy_contexts: self.lazy_contexts.clone(),
+        }
+    }
+}
+
+impl Serialize for SyntaxSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let metadata = self.metadata_json().map_err(ser::Error::custom)?;
+        let mut tuple = serializer.serialize_tuple(6)?;
+        tuple.serialize_element(&SYNTAX_SET_DUMP_MAGIC)?;
+        tuple.serialize_element(&SYNTAX_SET_DUMP_VERSION)?;
+        tuple.serialize_element(&self.syntaxes)?;
+        tuple.serialize_element(&AllContexts(self))?;
+        tuple.serialize_element(&self.path_syntaxes)?;
+        tuple.serialize_element(&metadata)?;
+        tuple.end()
+    }
+}
+
+/// Serializes all contexts of a set, loading them if needed
+struct AllContexts<'a>(&'a SyntaxSet);
+
+impl<'a> Serialize for AllContexts<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let syntax_set = self.0;
+        serializer.collect_seq((0..syntax_set.context_count()).map(|i| syntax_set.get_context(&ContextId::new(i))))
+    }
+}
+
+impl<'de> Deserialize<'de> for SyntaxSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_tuple(6, SyntaxSetVisitor)
+    }
+}
+
+struct SyntaxSetVisitor;
+
+impl<'de> Visitor<'de> for SyntaxSetVisitor {
+    type Value = SyntaxSet;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a syntax set")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<SyntaxSet, A::Error> {
+        let magic: u64 = next_element(&mut seq)?;
+        if magic != SYNTAX_SET_DUMP_MAGIC {
+            return Err(de::Error::custom("not a syntax set, or one serialized by an older version of syntect"));
+        }
+
+        let version: u32 = next_element(&mut seq)?;
+        if version != SYNTAX_SET_DUMP_VERSION {
+            return Err(de::Error::custom(format!("unsupported syntax set version {}", version)));
+        }
+        let mut syntax_set = SyntaxSet {
+            syntaxes: next_element(&mut seq)?,
+            contexts: next_element(&mut seq)?,
+            path_syntaxes: next_element(&mut seq)?,
+            ..SyntaxSet::default()
+        };
+        syntax_set.set_metadata_json(next_element(&mut seq)?).map_err(de::Error::custom)?;
+        Ok(syntax_set)
+    }
+}
+
+fn next_element<'de, T: Deserialize<'de>, A: SeqAccess<'de>>(seq: &mut A) -> Result<T, A::Error> {
+    seq.next_element()?.ok_or_else(|| de::Error::custom("syntax set ended early"))
+}
+
+/// The layout of syntax sets serialized by older versions of syntect, which didn't
+/// include the metadata. Only loaded on request, with `dumps::legacy_syntax_set_from_binary`.
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+#[derive(Deserialize)]
+pub(crate) struct LegacySyntaxSet {
+    syntaxes: Vec<SyntaxReference>,
+    contexts: Vec<Context>,
+    path_syntaxes: Vec<(String, usize)>,
+}
+
+#[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+impl LegacySyntaxSet {
+    pub(crate) fn into_syntax_set(self) -> SyntaxSet {
+        SyntaxSet {
+            syntaxes: self.syntaxes,
+            contexts: self.contexts,
+            path_syntaxes: self.path_syntaxes,
+            ..SyntaxSet::default()
         }
     }
 }
@@ -114,8 +288,11 @@ impl Default for SyntaxSet {
             contexts: Vec::new(),
             path_syntaxes: Vec::new(),
             first_line_cache: AtomicLazyCell::new(),
+            fingerprint: AtomicLazyCell::new(),
             #[cfg(feature = "metadata")]
             metadata: Metadata::default(),
+            #[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+            lazy_contexts: None,
         }
     }
 }
@@ -126,6 +303,22 @@ impl SyntaxSet {
         SyntaxSet::default()
     }
 
+    /// Creates a set whose contexts are decompressed from `blobs` when they're first used
+    #[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+    pub(crate) fn new_lazy(syntaxes: Vec<SyntaxReference>,
+                           path_syntaxes: Vec<(String, usize)>,
+                           context_count: usize,
+                           blobs: Vec<ContextBlob>)
+                           -> SyntaxSet {
+        let contexts = (0..context_count).map(|_| AtomicLazyCell::new()).collect();
+        SyntaxSet {
+            syntaxes,
+            path_syntaxes,
+            lazy_contexts: Some(LazyContexts { contexts, blobs }),
+            ..SyntaxSet::default()
+        }
+    }
+
     /// Convenience constructor for creating a builder, then loading syntax
     /// definitions from a folder and then building the syntax set.
     ///
@@ -155,6 +348,27 @@ impl SyntaxSet {
         &self.metadata
     }
 
+    /// A hash of the syntaxes and contexts in this set, which is the same across runs and
+    /// processes as long as the set was built from the same definitions.
+    ///
+    /// A serialized `ParseState` refers to contexts by their index in this set, so it's only
+    /// valid for the set it was created with. Store the fingerprint along with any states
+    /// you save and discard them if it doesn't match when loading.
+    ///
+    /// The first call serializes the whole set, which isn't cheap, later calls return the
+    /// cached result.
+    pub fn fingerprint(&self) -> u64 {
+        if let Some(&fingerprint) = self.fingerprint.borrow() {
+            return fingerprint;
+        }
+        let mut hasher = FnvHasher::default();
+        let bytes = serde_json::to_vec(self).expect("syntax sets can always be serialized");
+        hasher.write(&bytes);
+        let fingerprint = hasher.finish();
+        self.fingerprint.fill(fingerprint).ok();
+        fingerprint
+    }
+
     /// Finds a syntax by its default scope, for example `source.regexp` finds the regex syntax.
     /// This and all similar methods below do a linear search of syntaxes, this should be fast
     /// because there aren't many syntaxes, but don't think you can call it a bajillion times per second.
@@ -275,6 +489,11 @@ impl SyntaxSet {
         let SyntaxSet { syntaxes, contexts, path_syntaxes, metadata, .. } = self;
         #[cfg(not(feature = "metadata"))]
         let SyntaxSet { syntaxes, contexts, path_syntaxes, .. } = self;
+        #[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+        let contexts = match self.lazy_contexts {
+            Some(lazy_contexts) => lazy_contexts.into_contexts(),
+            None => contexts,
+        };
 
         let mut context_map = HashMap::with_capacity(contexts.len());
         for (i, context) in contexts.into_iter().enumerate() {
@@ -323,11 +542,154 @@ impl SyntaxSet {
         }
     }
 
+    /// Creates a new set with only some of the syntaxes in this one, to save memory
+    /// or to make dumps of the set smaller.
+    ///
+    /// Syntaxes are selected by name or by scope, e.g. `"Rust"` or `"source.rust"`. Other
+    /// syntaxes they refer to, directly or through other syntaxes, are included as well,
+    /// so that e.g. HTML still highlights embedded CSS and JavaScript. The plain text
+    /// syntax is always included. Names that don't match any syntax are ignored.
+    ///
+    /// Only the contexts that parsing with the included syntaxes can reach are kept.
+    /// For a lazily loaded set, only the syntaxes in the new set get loaded.
+    pub fn subset<S: AsRef<str>>(&self, names: &[S]) -> SyntaxSet {
+        let context_count = self.context_count();
+        let mut owners = vec![None; context_count];
+        for (i, syntax) in self.syntaxes.iter().enumerate() {
+            for context_id in syntax.contexts.values() {
+                owners[context_id.index()] = Some(i);
+            }
+        }
+
+        // Every included syntax can be parsed from its start, and with it every
+        // syntax it reaches
+        let mut included = vec![false; self.syntaxes.len()];
+        let mut reachable = vec![false; context_count];
+        let mut queue = Vec::new();
+        let include_syntax = |i: usize, included: &mut Vec<bool>, queue: &mut Vec<usize>| {
+            if !included[i] {
+                included[i] = true;
+                for name in &["__start", "__main", "main"] {
+                    queue.extend(self.syntaxes[i].contexts.get(*name).map(|id| id.index()));
+                }
+            }
+        };
+        for (i, syntax) in self.syntaxes.iter().enumerate() {
+            let scope = syntax.scope.build_string();
+            let selected = syntax.name == "Plain Text" ||
+                names.iter().any(|name| name.as_ref() == syntax.name || name.as_ref() == scope);
+            if selected {
+                include_syntax(i, &mut included, &mut queue);
+            }
+        }
+        while let Some(index) = queue.pop() {
+            if reachable[index] {
+                continue;
+            }
+            reachable[index] = true;
+            let owner = owners[index];
+            if let Some(owner) = owner {
+                include_syntax(owner, &mut included, &mut queue);
+            }
+            let context = self.get_context(&ContextId::new(index));
+            let owner_syntax = owner.map(|owner| &self.syntaxes[owner]);
+            for referenced in referenced_contexts(context, owner_syntax, &self.syntaxes) {
+                if !reachable[referenced.index()] {
+                    queue.push(referenced.index());
+                }
+            }
+        }
+
+        // Contexts keep their order, so the new set is deterministic as well
+        let mut new_ids = vec![None; context_count];
+        let mut contexts = Vec::new();
+        for (i, &keep) in reachable.iter().enumerate() {
+            if keep {
+                new_ids[i] = Some(ContextId::new(contexts.len()));
+                contexts.push(self.get_context(&ContextId::new(i)).clone());
+            }
+        }
+        for context in &mut contexts {
+            reindex_context(context, &new_ids);
+        }
+
+        let mut new_syntax_indices = vec![None; self.syntaxes.len()];
+        let mut syntaxes = Vec::new();
+        for (i, syntax) in self.syntaxes.iter().enumerate() {
+            if included[i] {
+                let mut syntax = syntax.clone();
+                syntax.contexts = syntax.contexts.into_iter()
+                    .filter_map(|(name, id)| new_ids[id.index()].map(|id| (name, id)))
+                    .collect();
+                new_syntax_indices[i] = Some(syntaxes.len());
+                syntaxes.push(syntax);
+            }
+        }
+        let path_syntaxes = self.path_syntaxes.iter()
+            .filter_map(|&(ref path, i)| new_syntax_indices[i].map(|i| (path.clone(), i)))
+            .collect();
+
+        SyntaxSet {
+            syntaxes,
+            contexts,
+            path_syntaxes,
+            first_line_cache: AtomicLazyCell::new(),
+            fingerprint: AtomicLazyCell::new(),
+            #[cfg(feature = "metadata")]
+            metadata: self.metadata.clone(),
+            #[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+            lazy_contexts: None,
+        }
+    }
+
     #[inline(always)]
     pub(crate) fn get_context(&self, context_id: &ContextId) -> &Context {
+        #[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+        {
+            if let Some(ref lazy_contexts) = self.lazy_contexts {
+                return lazy_contexts.get(context_id.index());
+            }
+        }
         &self.contexts[context_id.index()]
     }
 
+    /// The number of contexts in the set, including ones that aren't loaded yet
+    pub(crate) fn context_count(&self) -> usize {
+        #[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+        {
+            if let Some(ref lazy_contexts) = self.lazy_contexts {
+                return lazy_contexts.contexts.len();
+            }
+        }
+        self.contexts.len()
+    }
+
+    /// The metadata as stored in dumps, so that a set dumped with the `metadata` feature
+    /// can be loaded without it and the other way around
+    pub(crate) fn metadata_json(&self) -> Result<Option<String>, serde_json::Error> {
+        #[cfg(feature = "metadata")]
+        {
+            if !self.metadata.scoped_metadata.is_empty() {
+                return serde_json::to_string(&self.metadata).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    pub(crate) fn set_metadata_json(&mut self, json: Option<String>) -> Result<(), serde_json::Error> {
+        #[cfg(feature = "metadata")]
+        {
+            if let Some(json) = json {
+                self.metadata = serde_json::from_str(&json)?;
+            }
+        }
+        #[cfg(not(feature = "metadata"))]
+        {
+            let _ = json;
+        }
+        Ok(())
+    }
+
     fn first_line_cache(&self) -> &FirstLineCache {
         if let Some(cache) = self.first_line_cache.borrow() {
             cache
@@ -508,8 +870,11 @@ impl SyntaxSetBuilder {
             contexts: all_contexts,
             path_syntaxes,
             first_line_cache: AtomicLazyCell::new(),
+            fingerprint: AtomicLazyCell::new(),
             #[cfg(feature = "metadata")]
             metadata,
+            #[cfg(any(feature = "dump-load", feature = "dump-load-rs"))]
+            lazy_contexts: None,
         }
     }
 
@@ -579,40 +944,8 @@ impl SyntaxSetBuilder {
     }
 
     fn link_ref(context_ref: &mut ContextReference, syntax: &SyntaxReference, syntaxes: &[SyntaxReference]) {
-        // println!("{:?}", context_ref);
-        use super::syntax_definition::ContextReference::*;
-        let linked_context_id = match *context_ref {
-            Named(ref s) | Inline(ref s) => {
-                // This isn't actually correct, but it is better than nothing/crashing.
-                // This is being phased out anyhow, see https://github.com/sublimehq/Packages/issues/73
-                // Fixes issue #30
-                if s == "$top_level_main" {
-                    syntax.contexts.get("main")
-                } else {
-                    syntax.contexts.get(s)
-                }
-            }
-            ByScope { scope, ref sub_context } => {
-                let context_name = sub_context.as_ref().map_or("main", |x| &**x);
-                syntaxes
-                    .iter()
-                    .rev()
-                    .find(|s| s.scope == scope)
-                    .and_then(|s| s.contexts.get(context_name))
-            }
-            File { ref name, ref sub_context } => {
-                let context_name = sub_context.as_ref().map_or("main", |x| &**x);
-                syntaxes
-                    .iter()
-                    .rev()
-                    .find(|s| &s.name == name)
-                    .and_then(|s| s.contexts.get(context_name))
-            }
-            Direct(_) => None,
-        };
-        if let Some(context_id) = linked_context_id {
-            let mut new_ref = Direct(*context_id);
-            mem::swap(context_ref, &mut new_ref);
+        if let Some(context_id) = resolve_context_ref(context_ref, Some(syntax), syntaxes) {
+            *context_ref = ContextReference::Direct(context_id);
         }
     }
 
@@ -633,6 +966,95 @@ impl SyntaxSetBuilder {
     }
 }
 
+/// The context a reference points to, in the syntax `syntax` or the other syntaxes.
+/// Named and inline references can only be resolved with the syntax they're in.
+fn resolve_context_ref(context_ref: &ContextReference,
+                       syntax: Option<&SyntaxReference>,
+                       syntaxes: &[SyntaxReference])
+                       -> Option<ContextId> {
+    use super::syntax_definition::ContextReference::*;
+    let context_id = match *context_ref {
+        Named(ref s) | Inline(ref s) => {
+            // This isn't actually correct, but it is better than nothing/crashing.
+            // This is being phased out anyhow, see https://github.com/sublimehq/Packages/issues/73
+            // Fixes issue #30
+            if s == "$top_level_main" {
+                syntax.and_then(|syntax| syntax.contexts.get("main"))
+            } else {
+                syntax.and_then(|syntax| syntax.contexts.get(s))
+            }
+        }
+        ByScope { scope, ref sub_context } => {
+            let context_name = sub_context.as_ref().map_or("main", |x| &**x);
+            syntaxes
+                .iter()
+                .rev()
+                .find(|s| s.scope == scope)
+                .and_then(|s| s.contexts.get(context_name))
+        }
+        File { ref name, ref sub_context } => {
+            let context_name = sub_context.as_ref().map_or("main", |x| &**x);
+            syntaxes
+                .iter()
+                .rev()
+                .find(|s| &s.name == name)
+                .and_then(|s| s.contexts.get(context_name))
+        }
+        Direct(context_id) => return Some(context_id),
+    };
+    context_id.cloned()
+}
+
+/// The contexts that parsing a context can go on to, through any kind of reference
+fn referenced_contexts(context: &Context,
+                       syntax: Option<&SyntaxReference>,
+                       syntaxes: &[SyntaxReference])
+                       -> Vec<ContextId> {
+    let resolve = |context_ref: &ContextReference| resolve_context_ref(context_ref, syntax, syntaxes);
+    let mut references = Vec::new();
+    references.extend(context.prototype);
+    for pattern in &context.patterns {
+        match *pattern {
+            Pattern::Match(ref match_pat) => {
+                if let MatchOperation::Push(ref context_refs) | MatchOperation::Set(ref context_refs) = match_pat.operation {
+                    references.extend(context_refs.iter().filter_map(&resolve));
+                }
+                references.extend(match_pat.with_prototype.as_ref().and_then(&resolve));
+            }
+            Pattern::Include(ref context_ref) => references.extend(resolve(context_ref)),
+        }
+    }
+    references
+}
+
+/// Updates the references of a context after the contexts of a set have been renumbered
+fn reindex_context(context: &mut Context, new_ids: &[Option<ContextId>]) {
+    let reindex = |id: &mut ContextId| {
+        *id = new_ids[id.index()].expect("referenced context was removed");
+    };
+    if let Some(ref mut prototype) = context.prototype {
+        reindex(prototype);
+    }
+    for pattern in &mut context.patterns {
+        match *pattern {
+            Pattern::Match(ref mut match_pat) => {
+                if let MatchOperation::Push(ref mut context_refs) | MatchOperation::Set(ref mut context_refs) = match_pat.operation {
+                    for context_ref in context_refs.iter_mut() {
+                        if let ContextReference::Direct(ref mut id) = *context_ref {
+                            reindex(id);
+                        }
+                    }
+                }
+                if let Some(ContextReference::Direct(ref mut id)) = match_pat.with_prototype {
+                    reindex(id);
+                }
+            }
+            Pattern::Include(ContextReference::Direct(ref mut id)) => reindex(id),
+            Pattern::Include(_) => {}
+        }
+    }
+}
+
 #[derive(Debug)]
 struct FirstLineCache {
     /// (first line regex, syntax index) pairs for all syntaxes with a first line regex
@@ -736,6 +1158,74 @@ mod tests {
         assert_ops_contain(&ops, &expected);
     }
 
+    #[test]
+    fn fingerprint_identifies_syntax_set() {
+        let build = || {
+            let mut builder = SyntaxSetBuilder::new();
+            builder.add(syntax_a());
+            builder.add(syntax_b());
+            builder
+        };
+        let syntax_set = build().build();
+        assert_eq!(syntax_set.fingerprint(), build().build().fingerprint());
+        assert_eq!(syntax_set.fingerprint(), syntax_set.clone().fingerprint());
+
+        let mut builder = build();
+        builder.add_plain_text_syntax();
+        assert_ne!(syntax_set.fingerprint(), builder.build().fingerprint());
+    }
+
+    #[test]
+    fn can_subset_syntax_set() {
+        let mut builder = SyntaxSetBuilder::new();
+        builder.add_from_folder("testdata/Packages", true).unwrap();
+        builder.add_plain_text_syntax();
+        let ss = builder.build();
+
+        let subset = ss.subset(&["HTML", "source.rust"]);
+        for name in &["HTML", "CSS", "JavaScript", "Rust", "Plain Text"] {
+            assert!(subset.find_syntax_by_name(name).is_some(), "{} missing", name);
+        }
+        assert!(subset.find_syntax_by_name("Ruby").is_none());
+        assert!(subset.context_count() < ss.context_count());
+        assert_eq!(&subset.find_syntax_for_file("testdata/parser.rs").unwrap().unwrap().name, "Rust");
+
+        let html = "<style>a { color: red; }</style><script>var x = 1;</script>\n";
+        let expected = ParseState::new(ss.find_syntax_by_name("HTML").unwrap()).parse_line(html, &ss);
+        let ops = ParseState::new(subset.find_syntax_by_name("HTML").unwrap()).parse_line(html, &subset);
+        assert_eq!(ops, expected);
+    }
+
+    #[cfg(all(feature = "assets", any(feature = "dump-load", feature = "dump-load-rs")))]
+    #[test]
+    fn subset_keeps_only_reachable_contexts() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let subset = ss.subset(&["HTML"]);
+        for name in &["HTML", "CSS", "JavaScript (Babel)", "Plain Text"] {
+            assert!(subset.find_syntax_by_name(name).is_some(), "{} missing", name);
+        }
+        assert!(subset.find_syntax_by_name("Rust Enhanced").is_none());
+        assert!(subset.contexts.len() < ss.context_count());
+
+        // every kept context is reachable from the start of an included syntax
+        let mut reachable = vec![false; subset.contexts.len()];
+        let mut queue: Vec<ContextId> = subset.syntaxes.iter()
+            .flat_map(|syntax| syntax.contexts.get("__start").cloned())
+            .collect();
+        while let Some(id) = queue.pop() {
+            if !reachable[id.index()] {
+                reachable[id.index()] = true;
+                queue.extend(referenced_contexts(subset.get_context(&id), None, &subset.syntaxes));
+            }
+        }
+        assert!(reachable.iter().all(|&r| r));
+
+        let html = "<style>a { color: red; }</style><script>var x = 1;</script>\n";
+        let expected = ParseState::new(ss.find_syntax_by_name("HTML").unwrap()).parse_line(html, &ss);
+        let ops = ParseState::new(subset.find_syntax_by_name("HTML").unwrap()).parse_line(html, &subset);
+        assert_eq!(ops, expected);
+    }
+
     #[test]
     fn can_add_more_syntaxes_with_builder() {
         let syntax_set_original = {
@@ -0,0 +1,369 @@
+//! Writes a `SyntaxDefinition` back out as a `.sublime-syntax` file, the inverse
+//! of `yaml_load`. Useful for tools that generate or transform syntax definitions.
+use super::scope::*;
+use super::syntax_definition::*;
+use yaml_rust::{Yaml, YamlEmitter};
+use yaml_rust::yaml::Hash;
+use std::error::Error;
+use std::fmt;
+
+/// Errors that can occur while exporting a `SyntaxDefinition` to YAML
+#[derive(Debug)]
+pub enum ExportSyntaxError {
+    /// The definition contains references that were linked by a `SyntaxSet`,
+    /// for example because it came from `SyntaxSet::into_builder`. These point
+    /// to contexts by index and can't be written as YAML.
+    LinkedReference,
+    /// An inline reference to a context that isn't in the definition
+    MissingContext(String),
+}
+
+impl fmt::Display for ExportSyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::ExportSyntaxError::*;
+
+        match *self {
+            LinkedReference => write!(f, "Linked context references can't be exported"),
+            MissingContext(ref name) => write!(f, "Context '{}' is missing", name),
+        }
+    }
+}
+
+impl Error for ExportSyntaxError {}
+
+impl SyntaxDefinition {
+    /// Writes this definition as a `.sublime-syntax` file, which can be loaded again
+    /// with `load_from_str` into an equal `SyntaxDefinition`.
+    ///
+    /// The anonymous contexts created for inline lists in the original file become
+    /// inline lists again, and pushes created for `embed` are turned back into `embed`
+    /// and `escape`. The regexes are written with their variables already substituted,
+    /// and for definitions loaded with `lines_include_newline` set to false, with the
+    /// changes made for matching lines without newlines.
+    pub fn to_sublime_syntax(&self) -> Result<String, ExportSyntaxError> {
+        let mut top = Hash::new();
+        top.insert(key("name"), Yaml::String(self.name.clone()));
+        if !self.file_extensions.is_empty() {
+            let extensions = self.file_extensions.iter().map(|e| Yaml::String(e.clone())).collect();
+            top.insert(key("file_extensions"), Yaml::Array(extensions));
+        }
+        if let Some(ref first_line_match) = self.first_line_match {
+            top.insert(key("first_line_match"), Yaml::String(first_line_match.clone()));
+        }
+        top.insert(key("scope"), Yaml::String(self.scope.build_string()));
+        if self.hidden {
+            top.insert(key("hidden"), Yaml::Boolean(true));
+        }
+        if !self.variables.is_empty() {
+            let mut variables: Vec<_> = self.variables.iter().collect();
+            variables.sort();
+            let mut map = Hash::new();
+            for (name, value) in variables {
+                map.insert(Yaml::String(name.clone()), Yaml::String(value.clone()));
+            }
+            top.insert(key("variables"), Yaml::Hash(map));
+        }
+
+        // `__start` and `__main` are added by the loader
+        let mut names: Vec<&String> = self.contexts.keys()
+            .filter(|name| !name.starts_with("#anon_") && *name != "__start" && *name != "__main")
+            .collect();
+        names.sort_by_key(|name| (*name != "main", *name != "prototype", *name));
+        let mut contexts = Hash::new();
+        for name in names {
+            let items = self.context_items(name, name != "prototype")?;
+            contexts.insert(Yaml::String(name.clone()), items);
+        }
+        top.insert(key("contexts"), Yaml::Hash(contexts));
+
+        let mut out = String::from("%YAML 1.2\n");
+        YamlEmitter::new(&mut out).dump(&Yaml::Hash(top))
+            .expect("writing YAML to a string can't fail");
+        out.push('\n');
+        Ok(out)
+    }
+
+    /// `include_prototype_default` is what `meta_include_prototype` is when the file
+    /// doesn't mention it, which is false for prototypes and `with_prototype` contexts.
+    fn context_items(&self, name: &str, include_prototype_default: bool) -> Result<Yaml, ExportSyntaxError> {
+        let context = self.contexts.get(name)
+            .ok_or_else(|| ExportSyntaxError::MissingContext(name.to_owned()))?;
+        let mut items = Vec::new();
+
+        if !context.meta_scope.is_empty() {
+            items.push(single("meta_scope", scopes_yaml(&context.meta_scope)));
+        }
+        let mut meta_content_scope = &context.meta_content_scope[..];
+        if name == "main" && meta_content_scope.first() == Some(&self.scope) {
+            // Added by the loader
+            meta_content_scope = &meta_content_scope[1..];
+        }
+        if !meta_content_scope.is_empty() {
+            items.push(single("meta_content_scope", scopes_yaml(meta_content_scope)));
+        }
+        if context.meta_include_prototype != include_prototype_default {
+            items.push(single("meta_include_prototype", Yaml::Boolean(context.meta_include_prototype)));
+        }
+        match context.clear_scopes {
+            Some(ClearAmount::All) => items.push(single("clear_scopes", Yaml::Boolean(true))),
+            Some(ClearAmount::TopN(n)) => items.push(single("clear_scopes", Yaml::Integer(n as i64))),
+            None => {}
+        }
+
+        for pattern in &context.patterns {
+            let item = match *pattern {
+                Pattern::Include(ref reference) => single("include", self.reference_yaml(reference)?),
+                Pattern::Match(ref match_pat) => self.match_yaml(match_pat)?,
+            };
+            items.push(item);
+        }
+        Ok(Yaml::Array(items))
+    }
+
+    fn match_yaml(&self, match_pat: &MatchPattern) -> Result<Yaml, ExportSyntaxError> {
+        let mut map = Hash::new();
+        map.insert(key("match"), Yaml::String(match_pat.regex_str.clone()));
+        if !match_pat.scope.is_empty() {
+            map.insert(key("scope"), scopes_yaml(&match_pat.scope));
+        }
+        if let Some(ref captures) = match_pat.captures {
+            map.insert(key("captures"), captures_yaml(captures));
+        }
+
+        if let Some((embedded, escape_context, escape)) = self.as_embed(match_pat) {
+            map.insert(key("embed"), self.reference_yaml(embedded)?);
+            if !escape_context.meta_content_scope.is_empty() {
+                map.insert(key("embed_scope"), scopes_yaml(&escape_context.meta_content_scope));
+            }
+            map.insert(key("escape"), Yaml::String(escape.regex_str.clone()));
+            if let Some(ref captures) = escape.captures {
+                map.insert(key("escape_captures"), captures_yaml(captures));
+            }
+            return Ok(Yaml::Hash(map));
+        }
+
+        match match_pat.operation {
+            MatchOperation::Push(ref references) => {
+                map.insert(key("push"), self.push_args_yaml(references)?);
+            }
+            MatchOperation::Set(ref references) => {
+                map.insert(key("set"), self.push_args_yaml(references)?);
+            }
+            MatchOperation::Pop => {
+                map.insert(key("pop"), Yaml::Boolean(true));
+            }
+            MatchOperation::None => {}
+        }
+        match match_pat.with_prototype {
+            Some(ContextReference::Inline(ref name)) => {
+                map.insert(key("with_prototype"), self.context_items(name, false)?);
+            }
+            Some(ref reference) => {
+                let include = single("include", self.reference_yaml(reference)?);
+                map.insert(key("with_prototype"), Yaml::Array(vec![include]));
+            }
+            None => {}
+        }
+        Ok(Yaml::Hash(map))
+    }
+
+    /// Recognizes the push created for `embed`: an anonymous context that pops at
+    /// the escape, pushed on top of the embedded context, with a `with_prototype`
+    /// that pops everything when the escape is coming up.
+    /// Returns the embedded context, the escape context and its pattern.
+    fn as_embed<'a>(&'a self, match_pat: &'a MatchPattern)
+                    -> Option<(&'a ContextReference, &'a Context, &'a MatchPattern)> {
+        let (escape_context, embedded) = match match_pat.operation {
+            MatchOperation::Push(ref references) if references.len() == 2 => {
+                match references[0] {
+                    ContextReference::Inline(ref name) => (self.anonymous_context(name)?, &references[1]),
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+        let prototype = match match_pat.with_prototype {
+            Some(ContextReference::Inline(ref name)) => self.anonymous_context(name)?,
+            _ => return None,
+        };
+
+        let escape = only_pop_pattern(escape_context)?;
+        let lookahead = only_pop_pattern(prototype)?;
+        let is_embed = !escape_context.meta_include_prototype &&
+            escape_context.meta_scope.is_empty() &&
+            escape_context.clear_scopes.is_none() &&
+            !prototype.meta_include_prototype &&
+            prototype.meta_scope.is_empty() &&
+            prototype.meta_content_scope.is_empty() &&
+            prototype.clear_scopes.is_none() &&
+            lookahead.captures.is_none() &&
+            lookahead.regex_str == format!("(?={})", escape.regex_str);
+        if is_embed {
+            Some((embedded, escape_context, escape))
+        } else {
+            None
+        }
+    }
+
+    fn anonymous_context(&self, name: &str) -> Option<&Context> {
+        if name.starts_with("#anon_") {
+            self.contexts.get(name)
+        } else {
+            None
+        }
+    }
+
+    fn push_args_yaml(&self, references: &[ContextReference]) -> Result<Yaml, ExportSyntaxError> {
+        if references.len() == 1 {
+            self.reference_yaml(&references[0])
+        } else {
+            let args: Result<Vec<Yaml>, _> = references.iter().map(|r| self.reference_yaml(r)).collect();
+            Ok(Yaml::Array(args?))
+        }
+    }
+
+    fn reference_yaml(&self, reference: &ContextReference) -> Result<Yaml, ExportSyntaxError> {
+        let with_sub_context = |s: String, sub_context: &Option<String>| match *sub_context {
+            Some(ref sub_context) => Yaml::String(format!("{}#{}", s, sub_context)),
+            None => Yaml::String(s),
+        };
+        Ok(match *reference {
+            ContextReference::Named(ref name) => Yaml::String(name.clone()),
+            ContextReference::ByScope { scope, ref sub_context } => {
+                with_sub_context(format!("scope:{}", scope.build_string()), sub_context)
+            }
+            ContextReference::File { ref name, ref sub_context } => {
+                with_sub_context(format!("{}.sublime-syntax", name), sub_context)
+            }
+            ContextReference::Inline(ref name) if name.starts_with("#anon_") => {
+                self.context_items(name, true)?
+            }
+            ContextReference::Inline(ref name) => Yaml::String(name.clone()),
+            ContextReference::Direct(_) => return Err(ExportSyntaxError::LinkedReference),
+        })
+    }
+}
+
+/// The single pattern of a context if it is a plain `pop`
+fn only_pop_pattern(context: &Context) -> Option<&MatchPattern> {
+    if context.patterns.len() != 1 {
+        return None;
+    }
+    match context.patterns[0] {
+        Pattern::Match(ref pattern) if pattern.operation == MatchOperation::Pop &&
+            pattern.scope.is_empty() && pattern.with_prototype.is_none() => Some(pattern),
+        _ => None,
+    }
+}
+
+fn key(s: &str) -> Yaml {
+    Yaml::String(s.to_owned())
+}
+
+fn single(k: &str, value: Yaml) -> Yaml {
+    let mut map = Hash::new();
+    map.insert(key(k), value);
+    Yaml::Hash(map)
+}
+
+fn scopes_yaml(scopes: &[Scope]) -> Yaml {
+    let names: Vec<String> = scopes.iter().map(|s| s.build_string()).collect();
+    Yaml::String(names.join(" "))
+}
+
+fn captures_yaml(captures: &CaptureMapping) -> Yaml {
+    let mut map = Hash::new();
+    for &(index, ref scopes) in captures {
+        map.insert(Yaml::Integer(index as i64), scopes_yaml(scopes));
+    }
+    Yaml::Hash(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use parsing::syntax_definition::*;
+    use std::fs::File;
+    use std::io::Read;
+
+    fn assert_round_trips(defn: &SyntaxDefinition) -> String {
+        let yaml = defn.to_sublime_syntax().unwrap();
+        let reloaded = SyntaxDefinition::load_from_str(&yaml, true, None).unwrap();
+        assert_eq!(&reloaded, defn, "exported as:\n{}", yaml);
+        yaml
+    }
+
+    fn load_file(path: &str) -> SyntaxDefinition {
+        let mut s = String::new();
+        File::open(path).unwrap().read_to_string(&mut s).unwrap();
+        SyntaxDefinition::load_from_str(&s, true, None).unwrap()
+    }
+
+    #[test]
+    fn can_export_syntax() {
+        let defn = SyntaxDefinition::load_from_str(r#"
+        name: Export Test
+        scope: source.export
+        file_extensions: [ext, export]
+        first_line_match: '^#!.*export'
+        hidden: true
+        variables:
+          ident: '[a-z]+'
+        contexts:
+          prototype:
+            - match: '#.*'
+              scope: comment.line
+          main:
+            - meta_content_scope: meta.main
+            - match: '({{ident}})\s*(=)'
+              captures:
+                1: variable.other
+                2: keyword.operator
+              push: [value, "scope:source.other#strings", Other.sublime-syntax]
+            - match: '"'
+              push:
+                - meta_scope: string.quoted
+                - clear_scopes: 1
+                - match: '"'
+                  pop: true
+              with_prototype:
+                - match: 'x'
+                  scope: invalid
+            - include: scope:source.other
+          value:
+            - meta_include_prototype: false
+            - clear_scopes: true
+            - match: '\d+'
+              set: [[{match: 'a', pop: true}], value]
+        "#, true, None).unwrap();
+
+        let yaml = assert_round_trips(&defn);
+        assert!(!yaml.contains("#anon"));
+        assert!(!yaml.contains("__start"));
+    }
+
+    #[test]
+    fn can_export_embed_and_escape() {
+        let defn = load_file("testdata/embed_escape_test.sublime-syntax");
+        let yaml = assert_round_trips(&defn);
+        assert!(yaml.contains("embed: embedded_context"));
+        assert!(yaml.contains("escape_captures"));
+    }
+
+    #[test]
+    fn can_export_packages() {
+        for path in &["testdata/Packages/Rust/Rust.sublime-syntax",
+                      "testdata/Packages/HTML/HTML.sublime-syntax"] {
+            assert_round_trips(&load_file(path));
+        }
+    }
+
+    #[test]
+    fn linked_references_are_errors() {
+        let mut defn = SyntaxDefinition::load_from_str(
+            "name: A\nscope: source.a\ncontexts: {main: [{include: other}], other: []}",
+            true, None).unwrap();
+        defn.contexts.get_mut("main").unwrap().patterns[0] =
+            Pattern::Include(ContextReference::Direct(ContextId::new(0)));
+        assert!(defn.to_sublime_syntax().is_err());
+    }
+}
@@ -0,0 +1,218 @@
+//! Rendering highlighted code as RTF, for pasting it into word processors.
+//!
+//! RTF needs a table of all colours before the text, so lines are added to an
+//! `RtfGenerator` one at a time and the document is put together at the end.
+//! The lines are the same `(Style, &str)` regions `html::styled_line_to_highlighted_html`
+//! takes, as returned by `HighlightLines`.
+//!
+//! ```
+//! use syntect::easy::HighlightLines;
+//! use syntect::parsing::SyntaxSet;
+//! use syntect::highlighting::ThemeSet;
+//! use syntect::rtf::RtfGenerator;
+//! use syntect::util::LinesWithEndings;
+//!
+//! let ss = SyntaxSet::load_defaults_newlines();
+//! let ts = ThemeSet::load_defaults();
+//! let theme = &ts.themes["base16-ocean.dark"];
+//! let syntax = ss.find_syntax_by_extension("rs").unwrap();
+//!
+//! let mut h = HighlightLines::new(syntax, theme);
+//! let mut rtf = RtfGenerator::new(theme);
+//! for line in LinesWithEndings::from("fn main() {\n    println!(\"héllo\");\n}\n") {
+//!     rtf.add_styled_line(&h.highlight(line, &ss));
+//! }
+//! let document = rtf.finalize();
+//! assert!(document.starts_with("{\\rtf1\\ansi"));
+//! assert!(document.contains("h\\u233?llo"));
+//! ```
+use std::fmt::Write;
+use easy::HighlightLines;
+use highlighting::{Color, FontStyle, Style, Theme};
+use parsing::{SyntaxReference, SyntaxSet};
+use util::LinesWithEndings;
+
+/// Builds an RTF document out of highlighted lines, see the module docs.
+#[derive(Debug, Clone)]
+pub struct RtfGenerator {
+    background: Color,
+    /// The colour table, in order of first use. RTF colour indices start at 1,
+    /// since index 0 is the default colour.
+    colors: Vec<Color>,
+    body: String,
+}
+
+impl RtfGenerator {
+    /// Creates a generator whose paragraphs are shaded with the theme's background colour
+    pub fn new(theme: &Theme) -> RtfGenerator {
+        let background = theme.settings.background.unwrap_or(Color::WHITE);
+        let mut generator = RtfGenerator {
+            background,
+            colors: Vec::new(),
+            body: String::new(),
+        };
+        generator.color_index(background);
+        generator
+    }
+
+    /// Adds a line of regions, as returned by `HighlightLines::highlight`. A newline at
+    /// the end of the line ends the paragraph.
+    pub fn add_styled_line(&mut self, v: &[(Style, &str)]) {
+        let mut pending: Option<(Style, String)> = None;
+        for &(style, text) in v {
+            if let Some((pending_style, ref mut pending_text)) = pending {
+                if pending_style == style {
+                    pending_text.push_str(text);
+                    continue;
+                }
+            }
+            if let Some((pending_style, pending_text)) = pending.take() {
+                self.write_run(pending_style, &pending_text);
+            }
+            pending = Some((style, text.to_owned()));
+        }
+        if let Some((pending_style, pending_text)) = pending {
+            self.write_run(pending_style, &pending_text);
+        }
+    }
+
+    /// Returns the whole RTF document
+    pub fn finalize(self) -> String {
+        let mut s = String::from("{\\rtf1\\ansi\\ansicpg1252\\deff0\\uc1\n");
+        s.push_str("{\\fonttbl{\\f0\\fmodern\\fcharset0 Courier New;}}\n");
+        s.push_str("{\\colortbl;");
+        for c in &self.colors {
+            write!(s, "\\red{}\\green{}\\blue{};", c.r, c.g, c.b).unwrap();
+        }
+        s.push_str("}\n");
+        let background = self.colors.iter().position(|&c| same_rgb(c, self.background)).unwrap() + 1;
+        writeln!(s, "\\pard\\plain\\f0\\fs20\\cbpat{}", background).unwrap();
+        s.push_str(&self.body);
+        s.push_str("}\n");
+        s
+    }
+
+    fn color_index(&mut self, c: Color) -> usize {
+        if let Some(i) = self.colors.iter().position(|&known| same_rgb(known, c)) {
+            return i + 1;
+        }
+        self.colors.push(c);
+        self.colors.len()
+    }
+
+    fn write_run(&mut self, style: Style, text: &str) {
+        for (i, piece) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.body.push_str("\\par\n");
+            }
+            if piece.is_empty() {
+                continue;
+            }
+            let color = self.color_index(style.foreground);
+            write!(self.body, "{{\\cf{}", color).unwrap();
+            if style.font_style.contains(FontStyle::BOLD) {
+                self.body.push_str("\\b");
+            }
+            if style.font_style.contains(FontStyle::ITALIC) {
+                self.body.push_str("\\i");
+            }
+            if style.font_style.contains(FontStyle::UNDERLINE) {
+                self.body.push_str("\\ul");
+            }
+            self.body.push(' ');
+            escape_rtf(&mut self.body, piece);
+            self.body.push('}');
+        }
+    }
+}
+
+/// RTF colours have no alpha channel
+fn same_rgb(a: Color, b: Color) -> bool {
+    a.r == b.r && a.g == b.g && a.b == b.b
+}
+
+/// Escapes the RTF special characters and writes everything that isn't ASCII as
+/// `\u` codes, with `?` as the fallback for readers that don't support them.
+fn escape_rtf(s: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '\\' => s.push_str("\\\\"),
+            '{' => s.push_str("\\{"),
+            '}' => s.push_str("\\}"),
+            '\t' => s.push_str("\\tab "),
+            '\r' => {}
+            c if (c as u32) < 0x80 => s.push(c),
+            c => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units).iter() {
+                    // the code is a signed 16 bit number
+                    write!(s, "\\u{}?", *unit as i16).unwrap();
+                }
+            }
+        }
+    }
+}
+
+/// Convenience method that combines `RtfGenerator` and `HighlightLines` from
+/// `syntect::easy` to create an RTF document for a string (which can contain many lines).
+///
+/// Note that the `syntax` passed in must be from a `SyntaxSet` compiled for newline characters.
+pub fn highlighted_rtf_for_string(s: &str, ss: &SyntaxSet, syntax: &SyntaxReference, theme: &Theme) -> String {
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let mut generator = RtfGenerator::new(theme);
+    for line in LinesWithEndings::from(s) {
+        let regions = highlighter.highlight(line, ss);
+        generator.add_styled_line(&regions[..]);
+    }
+    generator.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use highlighting::ThemeSet;
+
+    fn style(foreground: Color, font_style: FontStyle) -> Style {
+        Style { foreground, background: Color::WHITE, font_style }
+    }
+
+    #[test]
+    fn escapes_text() {
+        let mut s = String::new();
+        escape_rtf(&mut s, "{a\\b}\tcafé 😀");
+        assert_eq!(s, "\\{a\\\\b\\}\\tab caf\\u233? \\u-10179?\\u-8704?");
+    }
+
+    #[test]
+    fn builds_color_table_from_used_styles() {
+        let red = Color { r: 0xff, g: 0, b: 0, a: 0xff };
+        let blue = Color { r: 0, g: 0, b: 0x80, a: 0xff };
+        let ts = ThemeSet::load_defaults();
+        let mut generator = RtfGenerator::new(&ts.themes["InspiredGitHub"]);
+        generator.add_styled_line(&[
+            (style(red, FontStyle::BOLD), "fn"),
+            (style(red, FontStyle::BOLD), " "),
+            (style(blue, FontStyle::ITALIC | FontStyle::UNDERLINE), "x\n"),
+        ]);
+        generator.add_styled_line(&[(style(red, FontStyle::empty()), "}")]);
+        let rtf = generator.finalize();
+
+        assert!(rtf.contains("{\\colortbl;\\red255\\green255\\blue255;\\red255\\green0\\blue0;\\red0\\green0\\blue128;}\n"));
+        assert!(rtf.contains("\\pard\\plain\\f0\\fs20\\cbpat1\n"));
+        assert!(rtf.ends_with("{\\cf2\\b fn }{\\cf3\\i\\ul x}\\par\n{\\cf2 \\}}}\n"));
+    }
+
+    #[test]
+    fn strings() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let ts = ThemeSet::load_defaults();
+        let theme = &ts.themes["base16-ocean.dark"];
+        let syntax = ss.find_syntax_by_extension("rs").unwrap();
+        let rtf = highlighted_rtf_for_string("let x = 5;\nlet y = x;\n", &ss, syntax, theme);
+        assert!(rtf.starts_with("{\\rtf1\\ansi\\ansicpg1252\\deff0\\uc1\n"));
+        // the background comes first in the colour table
+        assert!(rtf.contains("{\\colortbl;\\red43\\green48\\blue59;"));
+        assert!(rtf.contains("{\\cf") && rtf.contains("5}"));
+        assert_eq!(rtf.matches("\\par\n").count(), 2);
+    }
+}
@@ -0,0 +1,518 @@
+//! Encoding parse results as [LSP semantic tokens](https://microsoft.github.io/language-server-protocol/specifications/specification-current/#textDocument_semanticTokens),
+//! for language servers that want to serve syntect highlighting.
+//!
+//! A `SemanticTokensLegend` maps scope stacks to token types and modifiers using
+//! `ScopeSelectors`, the same way themes map them to styles. Its `token_types` and
+//! `token_modifiers` are what the server advertises as its legend.
+//!
+//! ```
+//! use syntect::parsing::SyntaxSet;
+//! use syntect::semantic_tokens::{semantic_tokens_for_string, encode, SemanticTokensLegend};
+//!
+//! let ss = SyntaxSet::load_defaults_newlines();
+//! let syntax = ss.find_syntax_by_extension("rs").unwrap();
+//! let legend = SemanticTokensLegend::default();
+//!
+//! let tokens = semantic_tokens_for_string("// é\nfn main() {}\n", &ss, syntax, &legend);
+//! let data = encode(&tokens);
+//! // the comment is 4 UTF-16 code units long and the function name starts 3 characters
+//! // after the `fn` keyword on the next line
+//! assert_eq!(&data[..5], &[0, 0, 4, legend.token_type_index("comment").unwrap(), 0]);
+//! assert_eq!(&data[5..10], &[1, 0, 2, legend.token_type_index("keyword").unwrap(), 0]);
+//! assert_eq!(&data[10..15], &[0, 3, 4, legend.token_type_index("function").unwrap(),
+//!                             legend.token_modifier_bit("declaration").unwrap()]);
+//! ```
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use easy::ScopeRegionIterator;
+use highlighting::ScopeSelectors;
+use parsing::{MatchPower, ParseScopeError, ParseState, Scope, ScopeStack, ScopeStackOp, SyntaxReference, SyntaxSet};
+use util::LinesWithEndings;
+
+/// Errors that can occur while adding token types and modifiers to a legend
+#[derive(Debug)]
+pub enum LegendError {
+    /// The scope selectors couldn't be parsed
+    ParseScope(ParseScopeError),
+    /// The modifiers are encoded as a 32 bit set, so there can't be more than 32
+    TooManyModifiers,
+}
+
+impl From<ParseScopeError> for LegendError {
+    fn from(error: ParseScopeError) -> LegendError {
+        LegendError::ParseScope(error)
+    }
+}
+
+impl fmt::Display for LegendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::LegendError::*;
+
+        match *self {
+            ParseScope(ref error) => write!(f, "Invalid scope selector: {:?}", error),
+            TooManyModifiers => write!(f, "A legend can have at most 32 token modifiers"),
+        }
+    }
+}
+
+impl Error for LegendError {}
+
+/// Which token types and modifiers scopes are mapped to.
+///
+/// The default legend maps the common TextMate scope names to the standard LSP token
+/// types, like `entity.name.function` to `function` and `storage.modifier` to `modifier`.
+#[derive(Debug, Clone)]
+pub struct SemanticTokensLegend {
+    /// The token types, whose index is used in the encoded tokens
+    pub token_types: Vec<String>,
+    /// The token modifiers, whose index is the bit used in the encoded tokens
+    pub token_modifiers: Vec<String>,
+    type_rules: Vec<(ScopeSelectors, u32)>,
+    modifier_rules: Vec<(ScopeSelectors, u32)>,
+}
+
+impl SemanticTokensLegend {
+    /// A legend without any token types or modifiers
+    pub fn new() -> SemanticTokensLegend {
+        SemanticTokensLegend {
+            token_types: Vec::new(),
+            token_modifiers: Vec::new(),
+            type_rules: Vec::new(),
+            modifier_rules: Vec::new(),
+        }
+    }
+
+    /// Maps scopes matching the selectors to a token type, which is added to
+    /// `token_types` if it isn't there yet.
+    ///
+    /// If several token types match a scope stack, the one with the best match wins,
+    /// or the one added first if they match equally well.
+    pub fn add_token_type(&mut self, name: &str, selectors: &str) -> Result<(), ParseScopeError> {
+        let selectors = ScopeSelectors::from_str(selectors)?;
+        let index = index_of(&mut self.token_types, name);
+        self.type_rules.push((selectors, index));
+        Ok(())
+    }
+
+    /// Maps scopes matching the selectors to a token modifier, which is added to
+    /// `token_modifiers` if it isn't there yet. Every matching modifier is set.
+    ///
+    /// The modifiers are encoded as a 32 bit set, so adding a 33rd one returns
+    /// `LegendError::TooManyModifiers`.
+    pub fn add_token_modifier(&mut self, name: &str, selectors: &str) -> Result<(), LegendError> {
+        let selectors = ScopeSelectors::from_str(selectors)?;
+        if self.token_modifiers.len() >= 32 && !self.token_modifiers.iter().any(|m| m == name) {
+            return Err(LegendError::TooManyModifiers);
+        }
+        let index = index_of(&mut self.token_modifiers, name);
+        self.modifier_rules.push((selectors, index));
+        Ok(())
+    }
+
+    /// The index of a token type in `token_types`
+    pub fn token_type_index(&self, name: &str) -> Option<u32> {
+        self.token_types.iter().position(|t| t == name).map(|i| i as u32)
+    }
+
+    /// The bit of a token modifier in the encoded modifier set, or `None` if it isn't
+    /// one of the first 32 modifiers
+    pub fn token_modifier_bit(&self, name: &str) -> Option<u32> {
+        self.token_modifiers.iter().position(|m| m == name).and_then(|i| 1u32.checked_shl(i as u32))
+    }
+
+    /// The token type and the set of modifiers for a scope stack, or `None` if no
+    /// token type matches it.
+    pub fn classify(&self, stack: &[Scope]) -> Option<(u32, u32)> {
+        let mut best: Option<(MatchPower, u32)> = None;
+        for &(ref selectors, index) in &self.type_rules {
+            if let Some(power) = selectors.does_match(stack) {
+                let better = match best {
+                    Some((best_power, _)) => power > best_power,
+                    None => true,
+                };
+                if better {
+                    best = Some((power, index));
+                }
+            }
+        }
+        best.map(|(_, token_type)| {
+            let modifiers = self.modifier_rules.iter()
+                .filter(|(selectors, _)| selectors.does_match(stack).is_some())
+                .fold(0, |set, &(_, index)| set | 1 << index);
+            (token_type, modifiers)
+        })
+    }
+}
+
+impl Default for SemanticTokensLegend {
+    fn default() -> SemanticTokensLegend {
+        let mut legend = SemanticTokensLegend::new();
+        let types = [
+            ("namespace", "entity.name.namespace, entity.name.module"),
+            ("type", "entity.name.type, support.type"),
+            ("class", "entity.name.class, support.class"),
+            ("enum", "entity.name.enum"),
+            ("interface", "entity.name.interface, entity.name.trait"),
+            ("struct", "entity.name.struct"),
+            ("parameter", "variable.parameter"),
+            ("variable", "variable"),
+            ("property", "variable.other.member, support.variable.property"),
+            ("function", "entity.name.function, support.function, variable.function"),
+            ("macro", "entity.name.macro, support.macro"),
+            ("keyword", "keyword, storage.type"),
+            ("modifier", "storage.modifier"),
+            ("comment", "comment"),
+            ("string", "string"),
+            ("number", "constant.numeric"),
+            ("regexp", "string.regexp"),
+            ("operator", "keyword.operator"),
+        ];
+        for &(name, selectors) in types.iter() {
+            legend.add_token_type(name, selectors).unwrap();
+        }
+        let modifiers = [
+            ("declaration", "entity.name"),
+            ("readonly", "variable.other.constant, entity.name.constant"),
+            ("deprecated", "invalid.deprecated"),
+            ("documentation", "comment.block.documentation, comment.line.documentation"),
+            ("defaultLibrary", "support"),
+        ];
+        for &(name, selectors) in modifiers.iter() {
+            legend.add_token_modifier(name, selectors).unwrap();
+        }
+        legend
+    }
+}
+
+fn index_of(names: &mut Vec<String>, name: &str) -> u32 {
+    match names.iter().position(|n| n == name) {
+        Some(i) => i as u32,
+        None => {
+            names.push(name.to_owned());
+            (names.len() - 1) as u32
+        }
+    }
+}
+
+/// A token with an absolute position, in UTF-16 code units like LSP positions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SemanticToken {
+    pub line: u32,
+    pub start: u32,
+    pub length: u32,
+    pub token_type: u32,
+    pub token_modifiers: u32,
+}
+
+/// Finds the tokens in a line, given the operations `ParseState::parse_line` returned
+/// for it and the scope stack from the end of the previous line, which is updated.
+///
+/// Neighbouring regions with the same type and modifiers become one token, and line
+/// endings are left out since tokens can't span lines.
+pub fn semantic_tokens_for_line(legend: &SemanticTokensLegend,
+                                line_number: u32,
+                                line: &str,
+                                ops: &[(usize, ScopeStackOp)],
+                                stack: &mut ScopeStack)
+                                -> Vec<SemanticToken> {
+    let mut tokens: Vec<SemanticToken> = Vec::new();
+    let mut column = 0;
+    for (text, op) in ScopeRegionIterator::new(ops, line) {
+        stack.apply(op);
+        let length: u32 = text.chars()
+            .filter(|&c| c != '\n' && c != '\r')
+            .map(|c| c.len_utf16() as u32)
+            .sum();
+        if length == 0 {
+            continue;
+        }
+        if let Some((token_type, token_modifiers)) = legend.classify(stack.as_slice()) {
+            let merged = match tokens.last_mut() {
+                Some(last) if last.start + last.length == column &&
+                              last.token_type == token_type &&
+                              last.token_modifiers == token_modifiers => {
+                    last.length += length;
+                    true
+                }
+                _ => false,
+            };
+            if !merged {
+                tokens.push(SemanticToken {
+                    line: line_number,
+                    start: column,
+                    length,
+                    token_type,
+                    token_modifiers,
+                });
+            }
+        }
+        column += length;
+    }
+    tokens
+}
+
+/// Parses a string (which can contain many lines) and finds all its tokens.
+///
+/// Note that the `syntax` passed in must be from a `SyntaxSet` compiled for newline characters.
+pub fn semantic_tokens_for_string(s: &str,
+                                  ss: &SyntaxSet,
+                                  syntax: &SyntaxReference,
+                                  legend: &SemanticTokensLegend)
+                                  -> Vec<SemanticToken> {
+    let mut parse_state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    let mut tokens = Vec::new();
+    for (i, line) in LinesWithEndings::from(s).enumerate() {
+        let ops = parse_state.parse_line(line, ss);
+        tokens.extend(semantic_tokens_for_line(legend, i as u32, line, &ops, &mut stack));
+    }
+    tokens
+}
+
+/// Encodes tokens, which must be in document order, as the `data` array of a
+/// semantic tokens response: five numbers per token, with the line relative to the
+/// previous token and the start relative to the previous token if it's on the same line.
+pub fn encode(tokens: &[SemanticToken]) -> Vec<u32> {
+    let mut data = Vec::with_capacity(tokens.len() * 5);
+    let (mut line, mut start) = (0, 0);
+    for token in tokens {
+        let delta_line = token.line - line;
+        let delta_start = if delta_line == 0 { token.start - start } else { token.start };
+        data.extend_from_slice(&[delta_line, delta_start, token.length, token.token_type, token.token_modifiers]);
+        line = token.line;
+        start = token.start;
+    }
+    data
+}
+
+/// An edit of a previous `data` array, as sent in a semantic tokens delta response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensEdit {
+    pub start: u32,
+    pub delete_count: u32,
+    pub data: Vec<u32>,
+}
+
+/// The edits that turn the `previous` data into the `current` data.
+///
+/// This is a single edit replacing everything between the tokens the two have in
+/// common at the start and at the end, or no edits if they're the same.
+pub fn diff(previous: &[u32], current: &[u32]) -> Vec<SemanticTokensEdit> {
+    let prefix = previous.chunks(5).zip(current.chunks(5))
+        .take_while(|&(a, b)| a == b)
+        .count() * 5;
+    let common = previous.len().min(current.len()) - prefix;
+    let suffix = (0..common / 5)
+        .take_while(|&i| previous[previous.len() - 5 * i - 5..previous.len() - 5 * i] ==
+                         current[current.len() - 5 * i - 5..current.len() - 5 * i])
+        .count() * 5;
+    let deleted = &previous[prefix..previous.len() - suffix];
+    let inserted = &current[prefix..current.len() - suffix];
+    if deleted.is_empty() && inserted.is_empty() {
+        return Vec::new();
+    }
+    vec![SemanticTokensEdit {
+        start: prefix as u32,
+        delete_count: deleted.len() as u32,
+        data: inserted.to_vec(),
+    }]
+}
+
+/// The LSP `SemanticTokens` result of a full request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokens {
+    pub result_id: String,
+    pub data: Vec<u32>,
+}
+
+/// The LSP `SemanticTokensDelta` result of a delta request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensDelta {
+    pub result_id: String,
+    pub edits: Vec<SemanticTokensEdit>,
+}
+
+/// The answer to a delta request, which is all the tokens if the previous result
+/// isn't known anymore
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum SemanticTokensResult {
+    Full(SemanticTokens),
+    Delta(SemanticTokensDelta),
+}
+
+/// Keeps the last tokens sent for a document, to answer delta requests.
+#[derive(Debug, Clone, Default)]
+pub struct SemanticTokensHistory {
+    next_id: u64,
+    last: Option<(String, Vec<u32>)>,
+}
+
+impl SemanticTokensHistory {
+    pub fn new() -> SemanticTokensHistory {
+        SemanticTokensHistory::default()
+    }
+
+    /// Answers a full request with the current data
+    pub fn full(&mut self, data: Vec<u32>) -> SemanticTokens {
+        let result_id = self.remember(data.clone());
+        SemanticTokens { result_id, data }
+    }
+
+    /// Answers a delta request with the edits from the data sent with `previous_result_id`,
+    /// or with all the data if that isn't the last data sent.
+    pub fn delta(&mut self, previous_result_id: &str, data: Vec<u32>) -> SemanticTokensResult {
+        let edits = match self.last {
+            Some((ref id, ref previous)) if id == previous_result_id => Some(diff(previous, &data)),
+            _ => None,
+        };
+        match edits {
+            Some(edits) => {
+                let result_id = self.remember(data);
+                SemanticTokensResult::Delta(SemanticTokensDelta { result_id, edits })
+            }
+            None => SemanticTokensResult::Full(self.full(data)),
+        }
+    }
+
+    fn remember(&mut self, data: Vec<u32>) -> String {
+        let result_id = self.next_id.to_string();
+        self.next_id += 1;
+        self.last = Some((result_id.clone(), data));
+        result_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(line: u32, start: u32, length: u32, token_type: u32) -> SemanticToken {
+        SemanticToken { line, start, length, token_type, token_modifiers: 0 }
+    }
+
+    #[test]
+    fn classifies_scopes() {
+        let legend = SemanticTokensLegend::default();
+        let classify = |s: &str| legend.classify(ScopeStack::from_str(s).unwrap().as_slice());
+        let t = |name: &str| legend.token_type_index(name).unwrap();
+        let m = |name: &str| legend.token_modifier_bit(name).unwrap();
+
+        assert_eq!(classify("source.rust meta.function.rust entity.name.function.rust"),
+                   Some((t("function"), m("declaration"))));
+        assert_eq!(classify("source.rust storage.modifier.rust"), Some((t("modifier"), 0)));
+        // the more specific selector wins
+        assert_eq!(classify("source.rust keyword.operator.rust"), Some((t("operator"), 0)));
+        assert_eq!(classify("source.rust support.function.rust"), Some((t("function"), m("defaultLibrary"))));
+        assert_eq!(classify("source.rust meta.block.rust"), None);
+    }
+
+    #[test]
+    fn custom_legends() {
+        let mut legend = SemanticTokensLegend::new();
+        legend.add_token_type("function", "entity.name.function").unwrap();
+        legend.add_token_type("function", "meta.function-call").unwrap();
+        legend.add_token_modifier("async", "storage.modifier.async").unwrap();
+        assert_eq!(legend.token_types, vec!["function".to_owned()]);
+        assert_eq!(legend.token_modifiers, vec!["async".to_owned()]);
+        let stack = ScopeStack::from_str("source.rs meta.function-call.rs").unwrap();
+        assert_eq!(legend.classify(stack.as_slice()), Some((0, 0)));
+    }
+
+    #[test]
+    fn rejects_more_than_32_modifiers() {
+        let mut legend = SemanticTokensLegend::new();
+        for i in 0..32 {
+            legend.add_token_modifier(&format!("modifier{}", i), "source").unwrap();
+        }
+        match legend.add_token_modifier("modifier32", "source") {
+            Err(LegendError::TooManyModifiers) => {}
+            other => panic!("expected too many modifiers, got {:?}", other),
+        }
+        legend.add_token_modifier("modifier31", "text").unwrap();
+        assert_eq!(legend.token_modifier_bit("modifier31"), Some(1 << 31));
+        let stack = ScopeStack::from_str("source.rs").unwrap();
+        legend.add_token_type("source", "source").unwrap();
+        assert_eq!(legend.classify(stack.as_slice()), Some((0, u32::max_value())));
+    }
+
+    #[test]
+    fn merges_regions_and_counts_utf16() {
+        let mut legend = SemanticTokensLegend::new();
+        legend.add_token_type("string", "string").unwrap();
+        let line = "x = \"😀\" + \"é\"\n";
+        let string = Scope::new("string.quoted").unwrap();
+        let punctuation = Scope::new("punctuation.definition.string").unwrap();
+        let ops = vec![
+            (4, ScopeStackOp::Push(string)),
+            (4, ScopeStackOp::Push(punctuation)),
+            (5, ScopeStackOp::Pop(1)),
+            (9, ScopeStackOp::Push(punctuation)),
+            (10, ScopeStackOp::Pop(2)),
+            (13, ScopeStackOp::Push(string)),
+            (17, ScopeStackOp::Pop(1)),
+        ];
+        let mut stack = ScopeStack::new();
+        let tokens = semantic_tokens_for_line(&legend, 3, line, &ops, &mut stack);
+        assert_eq!(tokens, vec![token(3, 4, 4, 0), token(3, 11, 3, 0)]);
+        assert!(stack.as_slice().is_empty());
+    }
+
+    #[test]
+    fn encodes_relative_positions() {
+        let tokens = [token(0, 2, 3, 1), token(0, 7, 1, 2), token(2, 4, 5, 1)];
+        assert_eq!(encode(&tokens), vec![0, 2, 3, 1, 0,
+                                         0, 5, 1, 2, 0,
+                                         2, 4, 5, 1, 0]);
+    }
+
+    #[test]
+    fn diffs_data() {
+        let previous = encode(&[token(0, 0, 2, 0), token(1, 0, 2, 0), token(2, 0, 2, 0)]);
+        let current = encode(&[token(0, 0, 2, 0), token(1, 0, 4, 1), token(1, 5, 1, 0), token(2, 0, 2, 0)]);
+        assert_eq!(diff(&previous, &current), vec![SemanticTokensEdit {
+            start: 5,
+            delete_count: 5,
+            data: current[5..15].to_vec(),
+        }]);
+        assert_eq!(diff(&current, &current), vec![]);
+        assert_eq!(diff(&[], &current)[0].data, current);
+    }
+
+    #[test]
+    fn answers_delta_requests() {
+        let mut history = SemanticTokensHistory::new();
+        let data = encode(&[token(0, 0, 2, 0)]);
+        assert_eq!(history.full(data.clone()),
+                   SemanticTokens { result_id: "0".to_owned(), data: data.clone() });
+        assert_eq!(history.delta("0", data.clone()),
+                   SemanticTokensResult::Delta(SemanticTokensDelta { result_id: "1".to_owned(), edits: vec![] }));
+        // an unknown id gets everything
+        assert_eq!(history.delta("0", data.clone()),
+                   SemanticTokensResult::Full(SemanticTokens { result_id: "2".to_owned(), data: data.clone() }));
+        let json = ::serde_json::to_string(&history.delta("2", vec![])).unwrap();
+        assert_eq!(json, "{\"resultId\":\"3\",\"edits\":[{\"start\":0,\"deleteCount\":5,\"data\":[]}]}");
+    }
+
+    #[cfg(all(feature = "assets", any(feature = "dump-load", feature = "dump-load-rs")))]
+    #[test]
+    fn strings() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let syntax = ss.find_syntax_by_extension("rs").unwrap();
+        let legend = SemanticTokensLegend::default();
+        let tokens = semantic_tokens_for_string("pub fn main() {}\n", &ss, syntax, &legend);
+        let function = SemanticToken {
+            line: 0,
+            start: 7,
+            length: 4,
+            token_type: legend.token_type_index("function").unwrap(),
+            token_modifiers: legend.token_modifier_bit("declaration").unwrap(),
+        };
+        assert!(tokens.contains(&function));
+        assert_eq!(tokens[0], token(0, 0, 3, legend.token_type_index("modifier").unwrap()));
+    }
+}
@@ -0,0 +1,297 @@
+//! Rendering highlighted code as SVG, for images of code that scale without a browser.
+//!
+//! Every line is a `<text>` element with a `<tspan>` per region. The layout assumes a
+//! monospace font where every character is `SvgOptions::char_width` wide, which is
+//! used for the size of the image and the line number gutter.
+//!
+//! ```
+//! use syntect::parsing::SyntaxSet;
+//! use syntect::highlighting::ThemeSet;
+//! use syntect::svg::{highlighted_svg_for_string, SvgOptions};
+//!
+//! let ss = SyntaxSet::load_defaults_newlines();
+//! let ts = ThemeSet::load_defaults();
+//! let syntax = ss.find_syntax_by_extension("rs").unwrap();
+//!
+//! let options = SvgOptions {
+//!     line_numbers: true,
+//!     highlighted_lines: vec![2],
+//!     ..SvgOptions::default()
+//! };
+//! let svg = highlighted_svg_for_string("fn main() {\n    run();\n}\n", &ss, syntax,
+//!                                      &ts.themes["base16-ocean.dark"], &options);
+//! assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+//! ```
+use std::fmt::Write;
+use easy::HighlightLines;
+use escape::Escape;
+use highlighting::{Color, FontStyle, Style, Theme};
+use parsing::{SyntaxReference, SyntaxSet};
+use util::LinesWithEndings;
+
+/// How the code is laid out
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgOptions {
+    /// The `font-family`, which should be a monospace font
+    pub font_family: String,
+    /// The font size in pixels
+    pub font_size: f32,
+    /// The distance between the baselines of lines in pixels
+    pub line_height: f32,
+    /// The width of a character in pixels
+    pub char_width: f32,
+    /// The space around the code in pixels
+    pub padding: f32,
+    /// How many columns a tab takes up
+    pub tab_width: usize,
+    /// Draw a gutter with line numbers
+    pub line_numbers: bool,
+    /// The number of the first line
+    pub first_line_number: usize,
+    /// The numbers of the lines to draw with the theme's line highlight colour,
+    /// counting from `first_line_number`
+    pub highlighted_lines: Vec<usize>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions {
+            font_family: "monospace".to_owned(),
+            font_size: 14.0,
+            line_height: 20.0,
+            char_width: 8.4,
+            padding: 10.0,
+            tab_width: 4,
+            line_numbers: false,
+            first_line_number: 1,
+            highlighted_lines: Vec::new(),
+        }
+    }
+}
+
+/// Formats a length without needless decimals
+fn px(f: f32) -> String {
+    let s = format!("{:.2}", f);
+    s.trim_end_matches('0').trim_end_matches('.').to_owned()
+}
+
+/// Writes an attribute like `fill="#rrggbb"`, with an `-opacity` attribute for alpha
+fn write_color_attr(s: &mut String, attr: &str, c: Color) {
+    write!(s, " {}=\"#{:02x}{:02x}{:02x}\"", attr, c.r, c.g, c.b).unwrap();
+    if c.a != 0xFF {
+        write!(s, " {}-opacity=\"{}\"", attr, px(c.a as f32 / 255.0)).unwrap();
+    }
+}
+
+/// Expands tabs and drops the line ending, returning the text and the next column
+fn expand_tabs(text: &str, mut column: usize, tab_width: usize) -> (String, usize) {
+    let mut expanded = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\n' | '\r' => {}
+            '\t' => {
+                let spaces = tab_width - column % tab_width;
+                for _ in 0..spaces {
+                    expanded.push(' ');
+                }
+                column += spaces;
+            }
+            c => {
+                expanded.push(c);
+                column += 1;
+            }
+        }
+    }
+    (expanded, column)
+}
+
+/// Output an SVG image of highlighted lines, as returned by `HighlightLines::highlight`.
+///
+/// The background and the default text colour come from the theme, as do the gutter
+/// colours and the line highlight colour.
+pub fn styled_lines_to_svg(lines: &[Vec<(Style, &str)>], theme: &Theme, options: &SvgOptions) -> String {
+    let background = theme.settings.background.unwrap_or(Color::WHITE);
+    let foreground = theme.settings.foreground.unwrap_or(Color::BLACK);
+
+    // lay out the text first, to know how wide the image is
+    let mut columns = 0;
+    let laid_out: Vec<Vec<(Style, String)>> = lines.iter()
+        .map(|regions| {
+            let mut column = 0;
+            let tspans = regions.iter()
+                .map(|&(style, text)| {
+                    let (expanded, next_column) = expand_tabs(text, column, options.tab_width);
+                    column = next_column;
+                    (style, expanded)
+                })
+                .filter(|(_, text)| !text.is_empty())
+                .collect();
+            columns = columns.max(column);
+            tspans
+        })
+        .collect();
+
+    let last_line_number = options.first_line_number + lines.len().saturating_sub(1);
+    let gutter_width = if options.line_numbers {
+        // the digits with a character of space on each side
+        (last_line_number.to_string().len() + 2) as f32 * options.char_width
+    } else {
+        0.0
+    };
+    let code_x = gutter_width + options.padding;
+    let width = code_x + columns as f32 * options.char_width + options.padding;
+    let height = options.padding * 2.0 + lines.len() as f32 * options.line_height;
+    // roughly centers the text in its line
+    let baseline = (options.line_height + options.font_size * 0.7) / 2.0;
+
+    let mut s = String::new();
+    writeln!(s, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" \
+                 font-family=\"{}\" font-size=\"{}\">",
+             Escape(&options.font_family), px(options.font_size), w = px(width), h = px(height)).unwrap();
+    s.push_str("<rect width=\"100%\" height=\"100%\"");
+    write_color_attr(&mut s, "fill", background);
+    s.push_str("/>\n");
+
+    if options.line_numbers {
+        write!(s, "<rect width=\"{}\" height=\"100%\"", px(gutter_width)).unwrap();
+        write_color_attr(&mut s, "fill", theme.settings.gutter.unwrap_or(background));
+        s.push_str("/>\n");
+    }
+
+    let line_highlight = theme.settings.line_highlight.unwrap_or(Color { a: 0x30, ..foreground });
+    for i in 0..lines.len() {
+        if options.highlighted_lines.contains(&(options.first_line_number + i)) {
+            write!(s, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+                   px(gutter_width),
+                   px(options.padding + i as f32 * options.line_height),
+                   px(width - gutter_width),
+                   px(options.line_height)).unwrap();
+            write_color_attr(&mut s, "fill", line_highlight);
+            s.push_str("/>\n");
+        }
+    }
+
+    if options.line_numbers {
+        write!(s, "<g text-anchor=\"end\"").unwrap();
+        write_color_attr(&mut s, "fill", theme.settings.gutter_foreground.unwrap_or(foreground));
+        s.push_str(">\n");
+        for i in 0..lines.len() {
+            writeln!(s, "<text x=\"{}\" y=\"{}\">{}</text>",
+                     px(gutter_width - options.char_width),
+                     px(options.padding + i as f32 * options.line_height + baseline),
+                     options.first_line_number + i).unwrap();
+        }
+        s.push_str("</g>\n");
+    }
+
+    for (i, tspans) in laid_out.iter().enumerate() {
+        if tspans.is_empty() {
+            continue;
+        }
+        write!(s, "<text x=\"{}\" y=\"{}\" xml:space=\"preserve\">",
+               px(code_x),
+               px(options.padding + i as f32 * options.line_height + baseline)).unwrap();
+        for &(style, ref text) in tspans {
+            s.push_str("<tspan");
+            write_color_attr(&mut s, "fill", style.foreground);
+            if style.font_style.contains(FontStyle::BOLD) {
+                s.push_str(" font-weight=\"bold\"");
+            }
+            if style.font_style.contains(FontStyle::ITALIC) {
+                s.push_str(" font-style=\"italic\"");
+            }
+            if style.font_style.contains(FontStyle::UNDERLINE) {
+                s.push_str(" text-decoration=\"underline\"");
+            }
+            write!(s, ">{}</tspan>", Escape(text)).unwrap();
+        }
+        s.push_str("</text>\n");
+    }
+
+    s.push_str("</svg>\n");
+    s
+}
+
+/// Convenience method that combines `styled_lines_to_svg` and `HighlightLines` from
+/// `syntect::easy` to create an SVG image of a string (which can contain many lines).
+///
+/// Note that the `syntax` passed in must be from a `SyntaxSet` compiled for newline characters.
+pub fn highlighted_svg_for_string(s: &str,
+                                  ss: &SyntaxSet,
+                                  syntax: &SyntaxReference,
+                                  theme: &Theme,
+                                  options: &SvgOptions)
+                                  -> String {
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let lines: Vec<Vec<(Style, &str)>> = LinesWithEndings::from(s)
+        .map(|line| highlighter.highlight(line, ss))
+        .collect();
+    styled_lines_to_svg(&lines, theme, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use highlighting::ThemeSet;
+
+    #[test]
+    fn formats_lengths() {
+        assert_eq!(px(20.0), "20");
+        assert_eq!(px(8.4), "8.4");
+        assert_eq!(px(8.4 * 3.0), "25.2");
+    }
+
+    #[test]
+    fn expands_tabs() {
+        assert_eq!(expand_tabs("\tx\ty\n", 0, 4), ("    x   y".to_owned(), 9));
+        assert_eq!(expand_tabs("\t", 2, 4), ("  ".to_owned(), 4));
+    }
+
+    #[test]
+    fn lays_out_lines() {
+        let ts = ThemeSet::load_defaults();
+        let theme = &ts.themes["InspiredGitHub"];
+        let red = Style {
+            foreground: Color { r: 0xff, g: 0, b: 0, a: 0xff },
+            background: Color::WHITE,
+            font_style: FontStyle::BOLD | FontStyle::ITALIC,
+        };
+        let lines = vec![
+            vec![(red, "if a < b && c {\n")],
+            vec![(red, "\n")],
+            vec![(red, "\tx\n")],
+        ];
+        let options = SvgOptions {
+            font_family: "Fira Code".to_owned(),
+            line_numbers: true,
+            first_line_number: 9,
+            highlighted_lines: vec![10],
+            ..SvgOptions::default()
+        };
+        let svg = styled_lines_to_svg(&lines, theme, &options);
+
+        // 2 digit line numbers make a 4 character gutter, and the longest line has 15 characters
+        let width = px(4.0 * 8.4 + 10.0 + 15.0 * 8.4 + 10.0);
+        assert!(svg.starts_with(&format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"80\" \
+                                          viewBox=\"0 0 {w} 80\" font-family=\"Fira Code\" font-size=\"14\">\n\
+                                          <rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\n", w = width)));
+        assert!(svg.contains(&format!("<rect x=\"33.6\" y=\"30\" width=\"{}\" height=\"20\"", px(4.0 * 8.4 + 20.0 + 15.0 * 8.4 - 33.6))));
+        assert!(svg.contains(">9</text>\n") && svg.contains(">11</text>\n"));
+        assert!(svg.contains("xml:space=\"preserve\"><tspan fill=\"#ff0000\" font-weight=\"bold\" font-style=\"italic\">\
+                              if a &lt; b &amp;&amp; c {</tspan></text>\n"));
+        assert!(svg.contains(">    x</tspan>"));
+        // the empty line has no text
+        assert_eq!(svg.matches("<tspan").count(), 2);
+        assert!(svg.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn strings() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let ts = ThemeSet::load_defaults();
+        let syntax = ss.find_syntax_by_extension("rs").unwrap();
+        let svg = highlighted_svg_for_string("fn main() {}\n", &ss, syntax, &ts.themes["base16-ocean.dark"], &SvgOptions::default());
+        assert!(svg.contains("<rect width=\"100%\" height=\"100%\" fill=\"#2b303b\"/>"));
+        assert!(svg.contains(">main</tspan>"));
+    }
+}
@@ -0,0 +1,946 @@
+//! Running the syntax tests that come with Sublime Text syntax definitions.
+//!
+//! A syntax test file starts with a header line naming the syntax to test, like
+//! `// SYNTAX TEST "Packages/Rust/Rust.sublime-syntax"`, and contains comment lines
+//! with `^` or `<-` assertions, each checking that the scopes of part of the previous
+//! non-assertion line match a selector. This is the same format Sublime Text uses,
+//! so syntax definitions can be tested without it installed.
+//!
+//! The Sublime Text 4 additions are supported as well:
+//!
+//! - `@` assertions check that a symbol of the given kind (`definition`, `local-definition`,
+//!   `global-definition`, `reference` or `none`) covers exactly the marked columns, using the
+//!   symbol list settings from the `.tmPreferences` metadata. Unless the header has the
+//!   `partial-symbols` option, every symbol on a line with `@` assertions has to be asserted.
+//! - the `reindent-unindented`, `reindent-unchanged` and `reindent` header options check the
+//!   indentation of every line against the indentation rules from the metadata.
+//!
+//! Symbols and indentation rules need the `metadata` feature. Without it no line has any
+//! symbols and the reindent options are ignored.
+//!
+//! Instead of printing anything, the functions here return a `SyntaxTestReport` with
+//! the result of every assertion, so you can run syntax tests from `cargo test` or
+//! write your own reporter. The `reporters` module writes JUnit XML and JSON, and
+//! tracks known failures in a baseline. See the `syntest` example for a command line runner.
+//!
+//! ```
+//! use syntect::parsing::SyntaxSet;
+//! use syntect::syntax_test::test_str;
+//!
+//! let ss = SyntaxSet::load_defaults_newlines();
+//! let test = "# SYNTAX TEST \"Packages/Python/Python.sublime-syntax\"\n\
+//!             def main(): pass\n\
+//!             # <- storage.type.function\n";
+//! let report = test_str(&ss, test).unwrap();
+//! assert!(report.success());
+//! ```
+pub mod reporters;
+
+use easy::ScopeRegionIterator;
+use highlighting::ScopeSelectors;
+use onig::Regex;
+use parsing::{ParseState, Scope, ScopeStack, ScopeStackOp, SyntaxSet};
+use util::LinesWithEndings;
+
+use std::cmp::{max, min};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+lazy_static! {
+    /// Matches the header line, capturing the test token that starts assertions,
+    /// the path of the syntax file and the optional token that ends assertions
+    pub static ref SYNTAX_TEST_HEADER_PATTERN: Regex =
+        Regex::new(r#"^(\s*\S+)\s+SYNTAX\sTEST\s+((?:[\w-]+\s+)*)"([^"]+)"\s*(\S+)?$"#).unwrap();
+    /// Matches an assertion after the test token, capturing `<-`, the `^` range or
+    /// the `@` range, and the scope selector or symbol kind text
+    pub static ref SYNTAX_TEST_ASSERTION_PATTERN: Regex =
+        Regex::new(r#"\s*(?:(<-)|(\^+)|(@+))(.*)$"#).unwrap();
+}
+
+/// Error type for syntax tests that couldn't be run at all
+#[derive(Debug)]
+pub enum SyntaxTestError {
+    /// the test file couldn't be read
+    Io(io::Error),
+    /// the first line of the file isn't a syntax test header
+    MalformedHeader,
+    /// the syntax file named in the header isn't in the syntax set
+    SyntaxDefinitionNotFound(String),
+}
+
+impl From<io::Error> for SyntaxTestError {
+    fn from(error: io::Error) -> SyntaxTestError {
+        SyntaxTestError::Io(error)
+    }
+}
+
+impl fmt::Display for SyntaxTestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::SyntaxTestError::*;
+
+        match *self {
+            Io(ref error) => write!(f, "Error reading syntax test: {}", error),
+            MalformedHeader => write!(f, "Malformed syntax test header"),
+            SyntaxDefinitionNotFound(ref path) => write!(f, "Syntax definition not found: {}", path),
+        }
+    }
+}
+
+impl Error for SyntaxTestError {
+    fn cause(&self) -> Option<&dyn Error> {
+        match *self {
+            SyntaxTestError::Io(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Summary of a syntax test file, counting assertions per column like Sublime Text does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxTestFileResult {
+    /// number of failed and total assertions
+    FailedAssertions(usize, usize),
+    /// total number of assertions
+    Success(usize),
+}
+
+/// Options for running syntax tests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntaxTestOptions {
+    /// Whether lines that only contain assertions are parsed as well.
+    /// Sublime Text does parse them, so this defaults to `true`.
+    pub parse_test_lines: bool,
+}
+
+impl Default for SyntaxTestOptions {
+    fn default() -> SyntaxTestOptions {
+        SyntaxTestOptions { parse_test_lines: true }
+    }
+}
+
+/// The first line of a syntax test file, like
+/// `// SYNTAX TEST partial-symbols "Packages/Rust/Rust.sublime-syntax"`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyntaxTestHeader {
+    /// The token that starts assertions, usually a line comment
+    pub testtoken_start: String,
+    /// The token that ends assertions, for languages with only block comments
+    pub testtoken_end: Option<String>,
+    /// The path of the syntax file to test
+    pub syntax_file: String,
+    /// Whether only some of the symbols on lines with `@` assertions need to be asserted
+    pub partial_symbols: bool,
+    /// Whether every line, once unindented, has to be reindented to its original indentation
+    pub reindent_unindented: bool,
+    /// Whether reindenting the file as it is has to leave it unchanged
+    pub reindent_unchanged: bool,
+}
+
+impl SyntaxTestHeader {
+    /// Parses a header line, returning `None` if it isn't one. Unknown options are ignored.
+    pub fn parse(line: &str) -> Option<SyntaxTestHeader> {
+        let captures = SYNTAX_TEST_HEADER_PATTERN.captures(line)?;
+        let mut header = SyntaxTestHeader {
+            testtoken_start: captures.at(1).unwrap().to_owned(),
+            testtoken_end: captures.at(4).map(|token| token.to_owned()),
+            syntax_file: captures.at(3).unwrap().to_owned(),
+            partial_symbols: false,
+            reindent_unindented: false,
+            reindent_unchanged: false,
+        };
+        for option in captures.at(2).unwrap_or("").split_whitespace() {
+            match option {
+                "partial-symbols" => header.partial_symbols = true,
+                "reindent-unindented" => header.reindent_unindented = true,
+                "reindent-unchanged" => header.reindent_unchanged = true,
+                "reindent" => {
+                    header.reindent_unindented = true;
+                    header.reindent_unchanged = true;
+                }
+                _ => {}
+            }
+        }
+        Some(header)
+    }
+}
+
+/// The result of one assertion in a syntax test file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssertionResult {
+    /// The line of the assertion, starting at 1 for the header
+    pub line: usize,
+    /// The line the assertion is tested against
+    pub tested_line: usize,
+    /// The first column (in chars) the assertion covers
+    pub column_begin: usize,
+    /// The column (in chars) after the last one the assertion covers
+    pub column_end: usize,
+    /// The scope selector the assertion expects, with surrounding whitespace trimmed
+    pub selector: String,
+    /// The results of checking the selector against each differently scoped
+    /// part of the column range
+    pub checks: Vec<RangeCheck>,
+}
+
+/// The result of checking an assertion's selector against a part of the tested line
+/// that has a single scope stack
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RangeCheck {
+    /// The first column (in chars) of this part
+    pub column_begin: usize,
+    /// The column (in chars) after the last one of this part
+    pub column_end: usize,
+    /// The text of the tested line in this part
+    pub text: String,
+    /// The actual scope stack of this part
+    pub scope_stack: Vec<Scope>,
+    /// Whether the selector matched the scope stack
+    pub success: bool,
+}
+
+impl AssertionResult {
+    /// Whether the selector matched across the whole column range
+    pub fn success(&self) -> bool {
+        self.checks.iter().all(|check| check.success)
+    }
+
+    /// The checks that failed
+    pub fn failures(&self) -> Vec<&RangeCheck> {
+        self.checks.iter().filter(|check| !check.success).collect()
+    }
+}
+
+/// The kind of a symbol, according to the symbol list settings in the metadata
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SymbolKind {
+    /// Shown in the symbol list of the file only
+    LocalDefinition,
+    /// Shown in the project wide symbol index
+    GlobalDefinition,
+    /// Shown in the project wide reference index
+    Reference,
+}
+
+impl SymbolKind {
+    /// Whether this kind is what the text of an `@` assertion expects
+    pub fn matches(self, expected: &str) -> bool {
+        match expected {
+            "definition" => self != SymbolKind::Reference,
+            "local-definition" => self == SymbolKind::LocalDefinition,
+            "global-definition" => self == SymbolKind::GlobalDefinition,
+            "reference" => self == SymbolKind::Reference,
+            _ => false,
+        }
+    }
+}
+
+/// A symbol on a line that was tested with `@` assertions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Symbol {
+    /// The line of the symbol
+    pub line: usize,
+    /// The first column (in chars) of the symbol
+    pub column_begin: usize,
+    /// The column (in chars) after the last one of the symbol
+    pub column_end: usize,
+    /// The text of the symbol
+    pub text: String,
+    pub kind: SymbolKind,
+}
+
+/// The result of an `@` assertion
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolAssertionResult {
+    /// The line of the assertion
+    pub line: usize,
+    /// The line the assertion is tested against
+    pub tested_line: usize,
+    /// The first column (in chars) the assertion covers
+    pub column_begin: usize,
+    /// The column (in chars) after the last one the assertion covers
+    pub column_end: usize,
+    /// The kind of symbol the assertion expects, like `definition` or `none`
+    pub expected: String,
+    /// The first symbol overlapping the asserted columns, if any
+    pub symbol: Option<Symbol>,
+    pub success: bool,
+}
+
+/// A line whose indentation doesn't match what the indentation rules give
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReindentMismatch {
+    pub line: usize,
+    /// The indentation the rules give
+    pub expected_indent: String,
+    /// The indentation in the file
+    pub actual_indent: String,
+}
+
+/// The results of all assertions in a syntax test file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyntaxTestReport {
+    pub header: SyntaxTestHeader,
+    /// Every `^` and `<-` assertion in the file, in order
+    pub assertions: Vec<AssertionResult>,
+    /// Every `@` assertion in the file, in order
+    pub symbol_assertions: Vec<SymbolAssertionResult>,
+    /// Symbols on lines with `@` assertions that weren't asserted, unless the
+    /// header has the `partial-symbols` option
+    pub unasserted_symbols: Vec<Symbol>,
+    /// Lines failing the reindent checks requested by the header
+    pub reindent_mismatches: Vec<ReindentMismatch>,
+}
+
+impl SyntaxTestReport {
+    /// Whether every assertion passed
+    pub fn success(&self) -> bool {
+        self.assertions.iter().all(|a| a.success()) &&
+            self.symbol_assertions.iter().all(|a| a.success) &&
+            self.unasserted_symbols.is_empty() &&
+            self.reindent_mismatches.is_empty()
+    }
+
+    /// The number of asserted columns, plus one for each `@` assertion, unasserted
+    /// symbol and reindent mismatch
+    pub fn total_assertions(&self) -> usize {
+        let columns: usize = self.assertions.iter().map(|a| a.column_end - a.column_begin).sum();
+        columns + self.symbol_assertions.len() + self.unasserted_symbols.len() + self.reindent_mismatches.len()
+    }
+
+    /// The number of asserted columns where the selector didn't match, plus one for
+    /// each failed `@` assertion, unasserted symbol and reindent mismatch
+    pub fn failed_assertions(&self) -> usize {
+        let columns: usize = self.assertions
+            .iter()
+            .flat_map(|a| a.checks.iter())
+            .filter(|check| !check.success)
+            .map(|check| check.column_end - check.column_begin)
+            .sum();
+        columns +
+            self.symbol_assertions.iter().filter(|a| !a.success).count() +
+            self.unasserted_symbols.len() +
+            self.reindent_mismatches.len()
+    }
+
+    /// Counts the assertions the same way the `syntest` example always has
+    pub fn summary(&self) -> SyntaxTestFileResult {
+        let failures = self.failed_assertions();
+        if failures > 0 {
+            SyntaxTestFileResult::FailedAssertions(failures, self.total_assertions())
+        } else {
+            SyntaxTestFileResult::Success(self.total_assertions())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AssertionKind {
+    Scope,
+    Symbol,
+}
+
+#[derive(Debug)]
+struct AssertionRange<'a> {
+    kind: AssertionKind,
+    begin_char: usize,
+    end_char: usize,
+    scope_selector_text: &'a str,
+    is_pure_assertion_line: bool,
+}
+
+/// A line kept around for the reindent checks, which skip lines with assertions
+/// and the header
+#[derive(Debug)]
+struct IndentLine {
+    line: usize,
+    text: String,
+    /// The scope stack at the start of the line
+    scope: Vec<Scope>,
+}
+
+/// What the indentation rules in the metadata say about a line
+#[derive(Debug, Default)]
+#[cfg_attr(not(feature = "metadata"), allow(dead_code))]
+struct IndentRules {
+    unindented: bool,
+    decrease: bool,
+    increase: bool,
+    bracket_increase: bool,
+    disable_indent_next_line: bool,
+}
+
+#[derive(Debug)]
+struct ScopedText {
+    scope: Vec<Scope>,
+    char_start: usize,
+    text_len: usize,
+}
+
+/// Reads and runs the syntax test at `path` with the default options.
+pub fn test_file<P: AsRef<Path>>(syntax_set: &SyntaxSet, path: P) -> Result<SyntaxTestReport, SyntaxTestError> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+    test_str(syntax_set, &contents)
+}
+
+/// Runs the syntax test in `contents` with the default options.
+pub fn test_str(syntax_set: &SyntaxSet, contents: &str) -> Result<SyntaxTestReport, SyntaxTestError> {
+    test_str_with_callback(syntax_set, contents, SyntaxTestOptions::default(), |_, _, _, _| {})
+}
+
+/// Runs the syntax test in `contents`, calling `on_line` for every line that isn't
+/// only assertions with its line number, its text, the scope stack before it and the
+/// operations it was parsed into. This is useful for debugging a syntax definition.
+pub fn test_str_with_callback<F>(syntax_set: &SyntaxSet,
+                                 contents: &str,
+                                 options: SyntaxTestOptions,
+                                 mut on_line: F)
+                                 -> Result<SyntaxTestReport, SyntaxTestError>
+    where F: FnMut(usize, &str, &ScopeStack, &[(usize, ScopeStackOp)])
+{
+    let mut lines = LinesWithEndings::from(contents).map(|line| line.replace("\r", ""));
+
+    // an empty file is an invalid file
+    let mut line = lines.next().ok_or(SyntaxTestError::MalformedHeader)?;
+
+    // parse the syntax test header in the first line of the file
+    let header = SyntaxTestHeader::parse(&line).ok_or(SyntaxTestError::MalformedHeader)?;
+    let testtoken_start = header.testtoken_start.as_str();
+    let testtoken_end = header.testtoken_end.as_ref().map(|s| s.as_str());
+    let reindent = header.reindent_unindented || header.reindent_unchanged;
+
+    // find the relevant syntax definition to parse the file with - case is important!
+    let syntax = syntax_set.find_syntax_by_path(&header.syntax_file)
+        .ok_or_else(|| SyntaxTestError::SyntaxDefinitionNotFound(header.syntax_file.clone()))?;
+
+    // iterate over the lines of the file, testing them
+    let mut state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+
+    let mut current_line_number = 1;
+    let mut test_against_line_number = 1;
+    let mut scopes_on_line_being_tested = Vec::new();
+    let mut previous_non_assertion_line = line.clone();
+    let mut assertions = Vec::new();
+    // only computed for lines with `@` assertions
+    let mut symbols_on_line_being_tested = None;
+    let mut symbol_assertions = Vec::new();
+    let mut unasserted_symbols = Vec::new();
+    let mut indent_lines = Vec::new();
+
+    loop { // over lines of file, starting with the header line
+        let mut line_only_has_assertion = false;
+        let mut line_has_assertion = false;
+        if let Some(assertion) = get_line_assertion_details(testtoken_start, testtoken_end, &line) {
+            match assertion.kind {
+                AssertionKind::Scope => {
+                    assertions.push(process_assertion(&assertion,
+                                                      &scopes_on_line_being_tested,
+                                                      &previous_non_assertion_line,
+                                                      current_line_number,
+                                                      test_against_line_number));
+                }
+                AssertionKind::Symbol => {
+                    let symbols = symbols_on_line_being_tested.get_or_insert_with(|| {
+                        find_symbols(syntax_set,
+                                     &scopes_on_line_being_tested,
+                                     &previous_non_assertion_line,
+                                     test_against_line_number)
+                    });
+                    symbol_assertions.push(process_symbol_assertion(&assertion,
+                                                                    symbols,
+                                                                    current_line_number,
+                                                                    test_against_line_number));
+                }
+            }
+            line_only_has_assertion = assertion.is_pure_assertion_line;
+            line_has_assertion = true;
+        }
+        if reindent && current_line_number > 1 && !line_has_assertion {
+            indent_lines.push(IndentLine {
+                line: current_line_number,
+                text: line.clone(),
+                scope: stack.as_slice().to_vec(),
+            });
+        }
+        if !line_only_has_assertion || options.parse_test_lines {
+            if !line_has_assertion { // ST seems to ignore lines that have assertions when calculating which line the assertion tests against
+                if !header.partial_symbols {
+                    collect_unasserted_symbols(symbols_on_line_being_tested.take(), &symbol_assertions, &mut unasserted_symbols);
+                }
+                symbols_on_line_being_tested = None;
+                scopes_on_line_being_tested.clear();
+                test_against_line_number = current_line_number;
+                previous_non_assertion_line = line.clone();
+            }
+            let ops = state.parse_line(&line, syntax_set);
+            if !line_only_has_assertion {
+                on_line(current_line_number, &line, &stack, &ops);
+            }
+            let mut col: usize = 0;
+            for (s, op) in ScopeRegionIterator::new(&ops, &line) {
+                stack.apply(op);
+                if s.is_empty() { // in this case we don't care about blank tokens
+                    continue;
+                }
+                if !line_has_assertion {
+                    // if the line has no assertions on it, remember the scopes on the line so we can test against them later
+                    let len = s.chars().count();
+                    scopes_on_line_being_tested.push(ScopedText {
+                        char_start: col,
+                        text_len: len,
+                        scope: stack.as_slice().to_vec(),
+                    });
+                    col += len;
+                }
+            }
+        }
+
+        current_line_number += 1;
+        line = match lines.next() {
+            Some(line) => line,
+            None => break,
+        };
+    }
+
+    if !header.partial_symbols {
+        collect_unasserted_symbols(symbols_on_line_being_tested.take(), &symbol_assertions, &mut unasserted_symbols);
+    }
+
+    let mut reindent_mismatches = Vec::new();
+    if header.reindent_unindented {
+        reindent_mismatches = check_reindent(syntax_set, &indent_lines, true);
+    }
+    if header.reindent_unchanged {
+        for mismatch in check_reindent(syntax_set, &indent_lines, false) {
+            if !reindent_mismatches.iter().any(|m| m.line == mismatch.line) {
+                reindent_mismatches.push(mismatch);
+            }
+        }
+        reindent_mismatches.sort_by_key(|m| m.line);
+    }
+
+    Ok(SyntaxTestReport {
+        header,
+        assertions,
+        symbol_assertions,
+        unasserted_symbols,
+        reindent_mismatches,
+    })
+}
+
+fn get_line_assertion_details<'a>(testtoken_start: &str, testtoken_end: Option<&str>, line: &'a str) -> Option<AssertionRange<'a>> {
+    // if the test start token specified in the test file's header is on the line
+    let index = line.find(testtoken_start)?;
+    let (before_token_start, token_and_rest_of_line) = line.split_at(index);
+    let after_token_start = &token_and_rest_of_line[testtoken_start.len()..];
+    let captures = SYNTAX_TEST_ASSERTION_PATTERN.captures(after_token_start)?;
+
+    let mut sst = captures.at(4).unwrap_or(""); // get the scope selector text
+    let mut only_whitespace_after_token_end = true;
+
+    if let Some(token) = testtoken_end { // if there is an end token defined in the test file header
+        if let Some(end_token_pos) = sst.find(token) { // and there is an end token in the line
+            let (ss, after_token_end) = sst.split_at(end_token_pos); // the scope selector text ends at the end token
+            sst = ss;
+            only_whitespace_after_token_end = after_token_end.trim_end().is_empty();
+        }
+    }
+
+    let (kind, range) = match captures.pos(3) {
+        Some(range) => (AssertionKind::Symbol, Some(range)),
+        None => (AssertionKind::Scope, captures.pos(2)),
+    };
+    let (begin_char, end_char) = match range {
+        Some((start, end)) => (index + testtoken_start.len() + start, index + testtoken_start.len() + end),
+        None => (index, index + 1),
+    };
+    Some(AssertionRange {
+        kind,
+        begin_char,
+        end_char,
+        scope_selector_text: sst,
+        // if only whitespace surrounds the test tokens on the line, then it is a pure assertion line
+        is_pure_assertion_line: before_token_start.trim_start().is_empty() && only_whitespace_after_token_end,
+    })
+}
+
+fn process_assertion(assertion: &AssertionRange,
+                     test_against_line_scopes: &[ScopedText],
+                     test_against_line: &str,
+                     line: usize,
+                     tested_line: usize)
+                     -> AssertionResult {
+    // format the scope selector to include a space at the beginning, because, currently, ScopeSelector expects excludes to begin with " -"
+    // and they are sometimes in the syntax test as ^^^-comment, for example
+    let selector = ScopeSelectors::from_str(&format!(" {}", assertion.scope_selector_text)).ok();
+    let check = |column_begin: usize, column_end: usize, scope: &[Scope]| {
+        RangeCheck {
+            column_begin,
+            column_end,
+            text: test_against_line.chars().skip(column_begin).take(column_end - column_begin).collect(),
+            scope_stack: scope.to_vec(),
+            // a selector that can't be parsed never matches
+            success: selector.as_ref().and_then(|s| s.does_match(scope)).is_some(),
+        }
+    };
+
+    // find the scope at the specified start column, and start matching the selector through the rest of the tokens on the line from there until the end column is reached
+    let mut checks: Vec<RangeCheck> = test_against_line_scopes.iter()
+        .skip_while(|s| s.char_start + s.text_len <= assertion.begin_char)
+        .take_while(|s| s.char_start < assertion.end_char)
+        .map(|s| check(max(s.char_start, assertion.begin_char),
+                       min(s.char_start + s.text_len, assertion.end_char),
+                       &s.scope))
+        .collect();
+
+    // don't ignore assertions after the newline, they should be treated as though they are asserting against the newline
+    if let Some(last) = test_against_line_scopes.last() {
+        if last.char_start + last.text_len < assertion.end_char {
+            checks.push(check(max(last.char_start + last.text_len, assertion.begin_char),
+                              assertion.end_char,
+                              &last.scope));
+        }
+    }
+
+    AssertionResult {
+        line,
+        tested_line,
+        column_begin: assertion.begin_char,
+        column_end: assertion.end_char,
+        selector: assertion.scope_selector_text.trim().to_owned(),
+        checks,
+    }
+}
+
+/// Finds the symbols on a line, merging adjacent parts of the same kind
+fn find_symbols(syntax_set: &SyntaxSet, scopes: &[ScopedText], text: &str, line: usize) -> Vec<Symbol> {
+    let mut symbols: Vec<Symbol> = Vec::new();
+    for scoped_text in scopes {
+        let kind = match symbol_kind(syntax_set, &scoped_text.scope) {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let column_end = scoped_text.char_start + scoped_text.text_len;
+        if let Some(last) = symbols.last_mut() {
+            if last.kind == kind && last.column_end == scoped_text.char_start {
+                last.column_end = column_end;
+                continue;
+            }
+        }
+        symbols.push(Symbol {
+            line,
+            column_begin: scoped_text.char_start,
+            column_end,
+            text: String::new(),
+            kind,
+        });
+    }
+    for symbol in &mut symbols {
+        symbol.text = text.chars().skip(symbol.column_begin).take(symbol.column_end - symbol.column_begin).collect();
+    }
+    symbols
+}
+
+#[cfg(feature = "metadata")]
+fn symbol_kind(syntax_set: &SyntaxSet, scope: &[Scope]) -> Option<SymbolKind> {
+    let metadata = syntax_set.metadata().metadata_for_scope(scope);
+    if metadata.show_in_indexed_symbol_list() {
+        Some(SymbolKind::GlobalDefinition)
+    } else if metadata.show_in_symbol_list() {
+        Some(SymbolKind::LocalDefinition)
+    } else if metadata.show_in_indexed_reference_list() {
+        Some(SymbolKind::Reference)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(feature = "metadata"))]
+fn symbol_kind(_syntax_set: &SyntaxSet, _scope: &[Scope]) -> Option<SymbolKind> {
+    None
+}
+
+fn process_symbol_assertion(assertion: &AssertionRange,
+                            symbols: &[Symbol],
+                            line: usize,
+                            tested_line: usize)
+                            -> SymbolAssertionResult {
+    let expected = assertion.scope_selector_text.trim();
+    let symbol = symbols.iter()
+        .find(|s| s.column_begin < assertion.end_char && s.column_end > assertion.begin_char)
+        .cloned();
+    let success = match expected {
+        "none" => symbol.is_none(),
+        // the symbol has to cover exactly the asserted columns
+        _ => symbol.as_ref().filter(|s| {
+            s.column_begin == assertion.begin_char && s.column_end == assertion.end_char && s.kind.matches(expected)
+        }).is_some(),
+    };
+    SymbolAssertionResult {
+        line,
+        tested_line,
+        column_begin: assertion.begin_char,
+        column_end: assertion.end_char,
+        expected: expected.to_owned(),
+        symbol,
+        success,
+    }
+}
+
+fn collect_unasserted_symbols(symbols: Option<Vec<Symbol>>,
+                              symbol_assertions: &[SymbolAssertionResult],
+                              unasserted_symbols: &mut Vec<Symbol>) {
+    for symbol in symbols.unwrap_or_default() {
+        let asserted = symbol_assertions.iter().any(|a| {
+            a.tested_line == symbol.line && a.column_begin < symbol.column_end && a.column_end > symbol.column_begin
+        });
+        if !asserted {
+            unasserted_symbols.push(symbol);
+        }
+    }
+}
+
+/// Reindents the lines with the indentation rules, either starting from nothing like
+/// Sublime Text does for `reindent-unindented`, or keeping the indentation of each
+/// previous line for `reindent-unchanged`.
+fn check_reindent(syntax_set: &SyntaxSet, lines: &[IndentLine], from_scratch: bool) -> Vec<ReindentMismatch> {
+    // the file's own indentation is the unit, as Sublime Text would use the view settings
+    let unit = lines.iter()
+        .map(|l| leading_whitespace(&l.text))
+        .find(|ws| !ws.is_empty())
+        .map(|ws| if ws.starts_with('\t') { "\t" } else { ws })
+        .unwrap_or("    ");
+
+    let mut mismatches = Vec::new();
+    let mut level: usize = 0;
+    let mut bracket = false;
+    for l in lines {
+        let text = l.text.trim_end_matches('\n');
+        if text.trim().is_empty() {
+            continue;
+        }
+        let pattern_text = if from_scratch { text.trim_start() } else { text };
+        let rules = match indent_rules(syntax_set, &l.scope, pattern_text) {
+            Some(rules) => rules,
+            None => return Vec::new(),
+        };
+        if rules.unindented {
+            continue;
+        }
+
+        let base = if rules.decrease { level.saturating_sub(1) } else { level };
+        let bracketed = bracket && !rules.decrease;
+        let indent = if bracketed { base + 1 } else { base };
+        let actual = leading_whitespace(text);
+        if actual != unit.repeat(indent) {
+            mismatches.push(ReindentMismatch {
+                line: l.line,
+                expected_indent: unit.repeat(indent),
+                actual_indent: actual.to_owned(),
+            });
+        }
+
+        let current = if from_scratch { indent } else { actual.matches(unit).count() };
+        level = if rules.increase {
+            current + 1
+        } else if bracketed {
+            current.saturating_sub(1)
+        } else {
+            current
+        };
+        bracket = !rules.increase && rules.bracket_increase && !rules.disable_indent_next_line;
+    }
+    mismatches
+}
+
+fn leading_whitespace(text: &str) -> &str {
+    let end = text.find(|c: char| c != ' ' && c != '\t').unwrap_or(text.len());
+    &text[..end]
+}
+
+#[cfg(feature = "metadata")]
+fn indent_rules(syntax_set: &SyntaxSet, scope: &[Scope], line: &str) -> Option<IndentRules> {
+    let metadata = syntax_set.metadata().metadata_for_scope(scope);
+    Some(IndentRules {
+        unindented: metadata.unindented_line(line),
+        decrease: metadata.decrease_indent(line),
+        increase: metadata.increase_indent(line),
+        bracket_increase: metadata.bracket_increase(line),
+        disable_indent_next_line: metadata.disable_indent_next_line(line),
+    })
+}
+
+#[cfg(not(feature = "metadata"))]
+fn indent_rules(_syntax_set: &SyntaxSet, _scope: &[Scope], _line: &str) -> Option<IndentRules> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_assertions() {
+        let assertion = get_line_assertion_details("//", None, "   // ^^^ keyword.control\n").unwrap();
+        assert_eq!(assertion.begin_char, 6);
+        assert_eq!(assertion.end_char, 9);
+        assert_eq!(assertion.scope_selector_text.trim(), "keyword.control");
+        assert!(assertion.is_pure_assertion_line);
+
+        let assertion = get_line_assertion_details("<!--", Some("-->"), "<!-- <- comment --> <b>\n").unwrap();
+        assert_eq!((assertion.begin_char, assertion.end_char), (0, 1));
+        assert_eq!(assertion.scope_selector_text.trim(), "comment");
+        assert!(!assertion.is_pure_assertion_line);
+
+        assert!(get_line_assertion_details("//", None, "let x = 1; // not an assertion\n").is_none());
+
+        let assertion = get_line_assertion_details("#", None, "#  @@@ definition\n").unwrap();
+        assert_eq!(assertion.kind, AssertionKind::Symbol);
+        assert_eq!((assertion.begin_char, assertion.end_char), (3, 6));
+        assert_eq!(assertion.scope_selector_text.trim(), "definition");
+    }
+
+    #[test]
+    fn can_parse_header() {
+        let header = SyntaxTestHeader::parse("// SYNTAX TEST \"Packages/Rust/Rust.sublime-syntax\"\n").unwrap();
+        assert_eq!(header.testtoken_start, "//");
+        assert_eq!(header.testtoken_end, None);
+        assert_eq!(header.syntax_file, "Packages/Rust/Rust.sublime-syntax");
+        assert!(!header.partial_symbols && !header.reindent_unindented && !header.reindent_unchanged);
+
+        let header = SyntaxTestHeader::parse("<!-- SYNTAX TEST partial-symbols reindent \"Packages/HTML/HTML.sublime-syntax\" -->").unwrap();
+        assert_eq!(header.testtoken_start, "<!--");
+        assert_eq!(header.testtoken_end, Some("-->".to_owned()));
+        assert_eq!(header.syntax_file, "Packages/HTML/HTML.sublime-syntax");
+        assert!(header.partial_symbols && header.reindent_unindented && header.reindent_unchanged);
+
+        assert!(SyntaxTestHeader::parse("// SYNTAX TEST Packages/Rust/Rust.sublime-syntax\n").is_none());
+    }
+
+    #[test]
+    fn can_run_syntax_test() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        let test = "# SYNTAX TEST \"Packages/Python/Python.sublime-syntax\"\n\
+                    def main(): pass\n\
+                    # <- storage.type.function\n\
+                    #  ^^^^^ entity.name.function\n\
+                    #           ^ comment\n";
+        let report = test_str(&ss, test).unwrap();
+        assert_eq!(report.header.syntax_file, "Packages/Python/Python.sublime-syntax");
+        assert_eq!(report.assertions.len(), 3);
+        assert!(report.assertions[0].success());
+        assert_eq!(report.assertions[0].tested_line, 2);
+
+        let failed = &report.assertions[1];
+        assert!(!failed.success());
+        assert_eq!((failed.line, failed.column_begin, failed.column_end), (4, 3, 8));
+        assert_eq!(failed.selector, "entity.name.function");
+        // the space before "main" is scoped differently than "main"
+        let texts: Vec<&str> = failed.checks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec![" ", "main"]);
+        assert!(!failed.checks[0].success);
+        assert!(failed.checks[1].success);
+
+        assert!(!report.assertions[2].success());
+        assert_eq!(report.summary(), SyntaxTestFileResult::FailedAssertions(2, 7));
+    }
+
+    #[cfg(all(feature = "metadata", feature = "yaml-load"))]
+    #[test]
+    fn can_check_symbols_and_indentation() {
+        use parsing::SyntaxSetBuilder;
+        use std::env;
+        use std::fs;
+
+        fn preferences(scope: &str, settings: &str) -> String {
+            format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                     <plist version=\"1.0\">\n\
+                     <dict>\n\
+                     <key>scope</key><string>{}</string>\n\
+                     <key>settings</key><dict>{}</dict>\n\
+                     </dict>\n\
+                     </plist>\n", scope, settings)
+        }
+
+        let dir = env::temp_dir().join(format!("syntect-syntax-test-{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Test.sublime-syntax"), r#"%YAML 1.2
+---
+name: Test
+scope: source.test
+file_extensions: [test]
+contexts:
+  main:
+    - match: '\bfn (\w+)'
+      captures:
+        1: entity.name.function.test
+    - match: '\bcall (\w+)'
+      captures:
+        1: variable.function.test
+"#).unwrap();
+        fs::write(dir.join("Definitions.tmPreferences"),
+                  preferences("source.test entity.name.function",
+                              "<key>showInSymbolList</key><integer>1</integer>\
+                               <key>showInIndexedSymbolList</key><integer>1</integer>")).unwrap();
+        fs::write(dir.join("References.tmPreferences"),
+                  preferences("source.test variable.function",
+                              "<key>showInIndexedReferenceList</key><integer>1</integer>")).unwrap();
+        fs::write(dir.join("Indentation Rules.tmPreferences"),
+                  preferences("source.test",
+                              "<key>increaseIndentPattern</key><string>\\{\\s*$</string>\
+                               <key>decreaseIndentPattern</key><string>^\\s*\\}</string>")).unwrap();
+
+        let mut builder = SyntaxSetBuilder::new();
+        builder.add_from_folder(&dir, true).unwrap();
+        let ss = builder.build();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let test = "// SYNTAX TEST reindent-unchanged \"Test.sublime-syntax\"\n\
+                    fn main {\n\
+                    // @@@@ global-definition\n    \
+                    call foo; call baz\n\
+                    //       @@@ reference\n\
+                    }\n\
+                    fn bad {\n\
+                    // @@ definition\n  \
+                    call x\n\
+                    }\n";
+        let report = test_str(&ss, test).unwrap();
+        assert!(report.header.reindent_unchanged);
+
+        let results: Vec<(usize, bool)> = report.symbol_assertions.iter().map(|a| (a.tested_line, a.success)).collect();
+        assert_eq!(results, vec![(2, true), (4, true), (7, false)]);
+        let bad = report.symbol_assertions[2].symbol.as_ref().unwrap();
+        assert_eq!((bad.column_begin, bad.column_end, bad.text.as_str()), (3, 6, "bad"));
+
+        assert_eq!(report.unasserted_symbols.len(), 1);
+        assert_eq!(report.unasserted_symbols[0].text, "baz");
+        assert_eq!(report.unasserted_symbols[0].kind, SymbolKind::Reference);
+
+        assert_eq!(report.reindent_mismatches, vec![ReindentMismatch {
+            line: 9,
+            expected_indent: "    ".to_owned(),
+            actual_indent: "  ".to_owned(),
+        }]);
+        assert_eq!(report.summary(), SyntaxTestFileResult::FailedAssertions(3, 5));
+    }
+
+    #[test]
+    fn reports_header_errors() {
+        let ss = SyntaxSet::load_defaults_newlines();
+        match test_str(&ss, "fn main() {}\n") {
+            Err(SyntaxTestError::MalformedHeader) => {}
+            other => panic!("expected a malformed header error, got {:?}", other),
+        }
+        match test_str(&ss, "// SYNTAX TEST \"Packages/Nope/Nope.sublime-syntax\"\n") {
+            Err(SyntaxTestError::SyntaxDefinitionNotFound(ref path)) => assert_eq!(path, "Packages/Nope/Nope.sublime-syntax"),
+            other => panic!("expected a missing syntax error, got {:?}", other),
+        }
+    }
+}
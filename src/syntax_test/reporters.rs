@@ -0,0 +1,384 @@
+//! Writing syntax test results as JUnit XML or JSON for CI dashboards, and tracking
+//! known failures per assertion with a baseline file.
+//!
+//! A baseline has one known failure per line, each a JSON object, sorted so that
+//! it diffs well when checked in. Comparing the baseline of a new run against the
+//! checked in one shows exactly which assertions started failing or were fixed.
+use escape::Escape;
+use serde_json;
+use super::{SyntaxTestError, SyntaxTestReport};
+
+use std::collections::BTreeSet;
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+/// The result of running the syntax test in one file
+#[derive(Debug)]
+pub struct TestedFile {
+    /// The path of the test file, as it should appear in reports
+    pub path: String,
+    pub result: Result<SyntaxTestReport, SyntaxTestError>,
+}
+
+/// One test case in the reports, for an assertion line or another check
+struct TestCase {
+    name: String,
+    failure: Option<String>,
+}
+
+fn test_cases(report: &SyntaxTestReport) -> Vec<TestCase> {
+    let mut cases = Vec::new();
+    for assertion in &report.assertions {
+        let failures: Vec<String> = assertion.failures()
+            .iter()
+            .map(|check| {
+                let stack: Vec<String> = check.scope_stack.iter().map(|s| s.build_string()).collect();
+                format!("expected {:?} on line {}, columns {}-{} (with text {:?}), actual scope stack: {}",
+                        assertion.selector,
+                        assertion.tested_line,
+                        check.column_begin,
+                        check.column_end,
+                        check.text,
+                        stack.join(" "))
+            })
+            .collect();
+        cases.push(TestCase {
+            name: format!("line {}: {}", assertion.line, assertion.selector),
+            failure: if failures.is_empty() { None } else { Some(failures.join("\n")) },
+        });
+    }
+    for assertion in &report.symbol_assertions {
+        let found = match assertion.symbol {
+            Some(ref s) => format!("found {:?} {:?} at columns {}-{}", s.kind, s.text, s.column_begin, s.column_end),
+            None => "found no symbol".to_owned(),
+        };
+        cases.push(TestCase {
+            name: format!("line {}: @ {}", assertion.line, assertion.expected),
+            failure: if assertion.success {
+                None
+            } else {
+                Some(format!("expected {:?} on line {}, columns {}-{}, {}",
+                             assertion.expected,
+                             assertion.tested_line,
+                             assertion.column_begin,
+                             assertion.column_end,
+                             found))
+            },
+        });
+    }
+    for symbol in &report.unasserted_symbols {
+        cases.push(TestCase {
+            name: format!("line {}: unasserted symbol {:?}", symbol.line, symbol.text),
+            failure: Some(format!("symbol {:?} ({:?}) at columns {}-{} has no @ assertion",
+                                  symbol.text,
+                                  symbol.kind,
+                                  symbol.column_begin,
+                                  symbol.column_end)),
+        });
+    }
+    for mismatch in &report.reindent_mismatches {
+        cases.push(TestCase {
+            name: format!("line {}: reindent", mismatch.line),
+            failure: Some(format!("expected indentation {:?}, actual indentation {:?}",
+                                  mismatch.expected_indent,
+                                  mismatch.actual_indent)),
+        });
+    }
+    cases
+}
+
+/// Writes the results as a JUnit XML document, with a test suite per file and a
+/// test case per assertion line. Files that couldn't be tested have a single
+/// test case with an error.
+pub fn to_junit_xml(files: &[TestedFile]) -> String {
+    let mut suites = String::new();
+    let mut total_tests = 0;
+    let mut total_failures = 0;
+    let mut total_errors = 0;
+
+    for file in files {
+        let path = Escape(&file.path);
+        match file.result {
+            Ok(ref report) => {
+                let cases = test_cases(report);
+                let failures = cases.iter().filter(|c| c.failure.is_some()).count();
+                total_tests += cases.len();
+                total_failures += failures;
+                writeln!(suites, "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\">",
+                         path, cases.len(), failures).unwrap();
+                for case in &cases {
+                    match case.failure {
+                        Some(ref failure) => {
+                            let message = failure.lines().next().unwrap_or("");
+                            writeln!(suites, "    <testcase classname=\"{}\" name=\"{}\">", path, Escape(&case.name)).unwrap();
+                            writeln!(suites, "      <failure message=\"{}\">{}</failure>", Escape(message), Escape(failure)).unwrap();
+                            writeln!(suites, "    </testcase>").unwrap();
+                        }
+                        None => {
+                            writeln!(suites, "    <testcase classname=\"{}\" name=\"{}\"/>", path, Escape(&case.name)).unwrap();
+                        }
+                    }
+                }
+                writeln!(suites, "  </testsuite>").unwrap();
+            }
+            Err(ref error) => {
+                total_tests += 1;
+                total_errors += 1;
+                writeln!(suites, "  <testsuite name=\"{}\" tests=\"1\" failures=\"0\" errors=\"1\">", path).unwrap();
+                writeln!(suites, "    <testcase classname=\"{}\" name=\"syntax test\">", path).unwrap();
+                writeln!(suites, "      <error message=\"{}\"/>", Escape(&error.to_string())).unwrap();
+                writeln!(suites, "    </testcase>").unwrap();
+                writeln!(suites, "  </testsuite>").unwrap();
+            }
+        }
+    }
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(xml, "<testsuites name=\"syntax tests\" tests=\"{}\" failures=\"{}\" errors=\"{}\">",
+             total_tests, total_failures, total_errors).unwrap();
+    xml.push_str(&suites);
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+#[derive(Serialize)]
+struct JsonFile<'a> {
+    path: &'a str,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report: Option<&'a SyntaxTestReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Writes the results as a JSON array with an object per file, holding either the
+/// whole `SyntaxTestReport` or the error that stopped the file from being tested.
+pub fn to_json(files: &[TestedFile]) -> Result<String, serde_json::Error> {
+    let json_files: Vec<JsonFile> = files.iter()
+        .map(|file| {
+            JsonFile {
+                path: &file.path,
+                success: file.result.as_ref().map(|r| r.success()).unwrap_or(false),
+                report: file.result.as_ref().ok(),
+                error: file.result.as_ref().err().map(|e| e.to_string()),
+            }
+        })
+        .collect();
+    serde_json::to_string_pretty(&json_files)
+}
+
+/// What kind of check a known failure is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailureKind {
+    /// a `^` or `<-` assertion
+    Scope,
+    /// an `@` assertion
+    Symbol,
+    UnassertedSymbol,
+    Reindent,
+    /// the file couldn't be tested at all
+    Error,
+}
+
+/// A failing assertion, identified by where it is and what it expects
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct KnownFailure {
+    pub path: String,
+    /// The line of the assertion, or of the unasserted symbol or badly indented line.
+    /// 0 for errors.
+    pub line: usize,
+    pub column_begin: usize,
+    pub column_end: usize,
+    pub kind: FailureKind,
+    /// The selector, the expected symbol kind, the expected indentation or the error
+    pub expected: String,
+}
+
+/// A set of known failures
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Baseline {
+    pub failures: BTreeSet<KnownFailure>,
+}
+
+/// The difference between a baseline and the failures of a new run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BaselineDiff {
+    /// Failures that aren't in the baseline
+    pub new_failures: Vec<KnownFailure>,
+    /// Failures in the baseline that don't happen anymore
+    pub fixed: Vec<KnownFailure>,
+}
+
+impl BaselineDiff {
+    /// Whether there are no new failures
+    pub fn is_clean(&self) -> bool {
+        self.new_failures.is_empty()
+    }
+}
+
+impl Baseline {
+    /// Collects every failure in the results
+    pub fn from_results(files: &[TestedFile]) -> Baseline {
+        let mut failures = BTreeSet::new();
+        for file in files {
+            let report = match file.result {
+                Ok(ref report) => report,
+                Err(ref error) => {
+                    failures.insert(KnownFailure {
+                        path: file.path.clone(),
+                        line: 0,
+                        column_begin: 0,
+                        column_end: 0,
+                        kind: FailureKind::Error,
+                        expected: error.to_string(),
+                    });
+                    continue;
+                }
+            };
+            let failure = |line, column_begin, column_end, kind, expected: &str| {
+                KnownFailure {
+                    path: file.path.clone(),
+                    line,
+                    column_begin,
+                    column_end,
+                    kind,
+                    expected: expected.to_owned(),
+                }
+            };
+            for a in report.assertions.iter().filter(|a| !a.success()) {
+                failures.insert(failure(a.line, a.column_begin, a.column_end, FailureKind::Scope, &a.selector));
+            }
+            for a in report.symbol_assertions.iter().filter(|a| !a.success) {
+                failures.insert(failure(a.line, a.column_begin, a.column_end, FailureKind::Symbol, &a.expected));
+            }
+            for s in &report.unasserted_symbols {
+                failures.insert(failure(s.line, s.column_begin, s.column_end, FailureKind::UnassertedSymbol, &s.text));
+            }
+            for m in &report.reindent_mismatches {
+                failures.insert(failure(m.line, 0, m.actual_indent.chars().count(), FailureKind::Reindent, &m.expected_indent));
+            }
+        }
+        Baseline { failures }
+    }
+
+    /// Compares the failures of a new run against this baseline
+    pub fn compare(&self, current: &Baseline) -> BaselineDiff {
+        BaselineDiff {
+            new_failures: current.failures.difference(&self.failures).cloned().collect(),
+            fixed: self.failures.difference(&current.failures).cloned().collect(),
+        }
+    }
+}
+
+impl FromStr for Baseline {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Baseline, serde_json::Error> {
+        let mut failures = BTreeSet::new();
+        for line in s.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+            failures.insert(serde_json::from_str(line)?);
+        }
+        Ok(Baseline { failures })
+    }
+}
+
+impl fmt::Display for Baseline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for failure in &self.failures {
+            writeln!(f, "{}", serde_json::to_string(failure).map_err(|_| fmt::Error)?)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parsing::Scope;
+    use syntax_test::{AssertionResult, RangeCheck, SyntaxTestHeader};
+
+    fn tested_files() -> Vec<TestedFile> {
+        let check = |column_begin, column_end, text: &str, scope: &str, success| {
+            RangeCheck {
+                column_begin,
+                column_end,
+                text: text.to_owned(),
+                scope_stack: vec![Scope::new("source.rust").unwrap(), Scope::new(scope).unwrap()],
+                success,
+            }
+        };
+        let report = SyntaxTestReport {
+            header: SyntaxTestHeader::parse("// SYNTAX TEST \"Packages/Rust/Rust.sublime-syntax\"").unwrap(),
+            assertions: vec![
+                AssertionResult {
+                    line: 3,
+                    tested_line: 2,
+                    column_begin: 0,
+                    column_end: 2,
+                    selector: "storage.type.function".to_owned(),
+                    checks: vec![check(0, 2, "fn", "storage.type.function.rust", true)],
+                },
+                AssertionResult {
+                    line: 4,
+                    tested_line: 2,
+                    column_begin: 3,
+                    column_end: 7,
+                    selector: "keyword & <stuff>".to_owned(),
+                    checks: vec![check(3, 7, "main", "entity.name.function.rust", false)],
+                },
+            ],
+            symbol_assertions: Vec::new(),
+            unasserted_symbols: Vec::new(),
+            reindent_mismatches: Vec::new(),
+        };
+        vec![
+            TestedFile { path: "syntax_test_rust.rs".to_owned(), result: Ok(report) },
+            TestedFile { path: "syntax_test_broken.rs".to_owned(), result: Err(SyntaxTestError::MalformedHeader) },
+        ]
+    }
+
+    #[test]
+    fn can_write_junit_xml() {
+        let xml = to_junit_xml(&tested_files());
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"));
+        assert!(xml.contains("<testsuites name=\"syntax tests\" tests=\"3\" failures=\"1\" errors=\"1\">"));
+        assert!(xml.contains("<testcase classname=\"syntax_test_rust.rs\" name=\"line 3: storage.type.function\"/>"));
+        assert!(xml.contains("name=\"line 4: keyword &amp; &lt;stuff&gt;\">"));
+        assert!(xml.contains("actual scope stack: source.rust entity.name.function.rust</failure>"));
+        assert!(xml.contains("<error message=\"Malformed syntax test header\"/>"));
+    }
+
+    #[test]
+    fn can_write_json() {
+        let json: serde_json::Value = serde_json::from_str(&to_json(&tested_files()).unwrap()).unwrap();
+        assert_eq!(json[0]["path"], "syntax_test_rust.rs");
+        assert_eq!(json[0]["success"], false);
+        assert_eq!(json[0]["report"]["assertions"][1]["checks"][0]["scope_stack"][1], "entity.name.function.rust");
+        assert_eq!(json[1]["error"], "Malformed syntax test header");
+    }
+
+    #[test]
+    fn can_compare_baselines() {
+        let files = tested_files();
+        let baseline = Baseline::from_results(&files);
+        assert_eq!(baseline.failures.len(), 2);
+
+        let text = baseline.to_string();
+        assert_eq!(text.lines().count(), 2);
+        let parsed: Baseline = text.parse().unwrap();
+        assert_eq!(parsed, baseline);
+        assert!(baseline.compare(&parsed).is_clean());
+
+        let mut current = baseline.clone();
+        let fixed = current.failures.iter().find(|f| f.kind == FailureKind::Error).cloned().unwrap();
+        current.failures.remove(&fixed);
+        let new_failure = KnownFailure { line: 5, ..fixed.clone() };
+        current.failures.insert(new_failure.clone());
+
+        let diff = baseline.compare(&current);
+        assert_eq!(diff.new_failures, vec![new_failure]);
+        assert_eq!(diff.fixed, vec![fixed]);
+        assert!(!diff.is_clean());
+    }
+}
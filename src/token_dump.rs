@@ -0,0 +1,223 @@
+//! Dumping the tokens of parsed text with their full scope stacks as JSON, for
+//! debugging syntax definitions and snapshot testing them.
+//!
+//! Unlike `util::debug_print_ops`, which prints the raw push and pop operations, a
+//! dump has every token of every line with the scope stack it's in, and optionally
+//! the `Style` a theme gives it. Dumps can be written as one JSON array or as JSON
+//! Lines with a line of text per line of code, which is easier to diff, and loaded
+//! back to compare them with `first_difference`.
+//!
+//! ```
+//! use syntect::parsing::SyntaxSet;
+//! use syntect::token_dump::{dump_tokens, to_json_lines, from_json_lines, first_difference};
+//!
+//! let ss = SyntaxSet::load_defaults_newlines();
+//! let syntax = ss.find_syntax_by_extension("rs").unwrap();
+//!
+//! let dump = dump_tokens("let x = 5;\n", &ss, syntax);
+//! let snapshot = to_json_lines(&dump).unwrap();
+//! let expected = from_json_lines(&snapshot).unwrap();
+//! assert_eq!(first_difference(&expected, &dump), None);
+//! ```
+use easy::ScopeRegionIterator;
+use highlighting::{Highlighter, Style, Theme};
+use parsing::{ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet};
+use serde_json;
+use util::LinesWithEndings;
+
+/// A token with the same scope stack all the way through
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DumpedToken {
+    /// The byte offset of the start of the token in its line
+    pub start: usize,
+    /// The byte offset of the end of the token in its line
+    pub end: usize,
+    pub text: String,
+    /// The scope stack, outermost scope first
+    pub scopes: Vec<Scope>,
+    /// The style of the token, if the dump was made with a theme
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<Style>,
+}
+
+/// The tokens of a line, whose text put together is the whole line including the line ending
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DumpedLine {
+    /// The line number, starting at 1
+    pub line: usize,
+    pub tokens: Vec<DumpedToken>,
+}
+
+/// Where a dump is different from an expected one, see `first_difference`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DumpDifference {
+    /// The line number, starting at 1
+    pub line: usize,
+    /// The line in the expected dump, `None` if the actual dump has more lines
+    pub expected: Option<DumpedLine>,
+    /// The line in the actual dump, `None` if it has fewer lines
+    pub actual: Option<DumpedLine>,
+}
+
+/// Parses a string (which can contain many lines) and dumps the tokens of every line.
+///
+/// Note that the `syntax` passed in must be from a `SyntaxSet` compiled for newline characters.
+pub fn dump_tokens(s: &str, ss: &SyntaxSet, syntax: &SyntaxReference) -> Vec<DumpedLine> {
+    dump(s, ss, syntax, None)
+}
+
+/// Like `dump_tokens`, but also gives every token the `Style` the theme has for its scopes.
+pub fn dump_tokens_with_styles(s: &str, ss: &SyntaxSet, syntax: &SyntaxReference, theme: &Theme) -> Vec<DumpedLine> {
+    let highlighter = Highlighter::new(theme);
+    dump(s, ss, syntax, Some(&highlighter))
+}
+
+fn dump(s: &str, ss: &SyntaxSet, syntax: &SyntaxReference, highlighter: Option<&Highlighter>) -> Vec<DumpedLine> {
+    let mut parse_state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    let mut lines = Vec::new();
+    for (i, line) in LinesWithEndings::from(s).enumerate() {
+        let ops = parse_state.parse_line(line, ss);
+        let mut tokens = Vec::new();
+        let mut start = 0;
+        for (text, op) in ScopeRegionIterator::new(&ops, line) {
+            stack.apply(op);
+            if text.is_empty() {
+                continue;
+            }
+            tokens.push(DumpedToken {
+                start,
+                end: start + text.len(),
+                text: text.to_owned(),
+                scopes: stack.as_slice().to_vec(),
+                style: highlighter.map(|h| h.style_for_stack(stack.as_slice())),
+            });
+            start += text.len();
+        }
+        lines.push(DumpedLine { line: i + 1, tokens });
+    }
+    lines
+}
+
+/// Writes a dump as a pretty printed JSON array of lines
+pub fn to_json(lines: &[DumpedLine]) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(lines)
+}
+
+/// Writes a dump as JSON Lines, a JSON object for each line of code
+pub fn to_json_lines(lines: &[DumpedLine]) -> serde_json::Result<String> {
+    let mut s = String::new();
+    for line in lines {
+        s.push_str(&serde_json::to_string(line)?);
+        s.push('\n');
+    }
+    Ok(s)
+}
+
+/// Loads a dump written by `to_json`
+pub fn from_json(s: &str) -> serde_json::Result<Vec<DumpedLine>> {
+    serde_json::from_str(s)
+}
+
+/// Loads a dump written by `to_json_lines`, ignoring blank lines
+pub fn from_json_lines(s: &str) -> serde_json::Result<Vec<DumpedLine>> {
+    s.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect()
+}
+
+/// Compares a dump with an expected one, returning the first line that's different.
+pub fn first_difference(expected: &[DumpedLine], actual: &[DumpedLine]) -> Option<DumpDifference> {
+    for i in 0..expected.len().max(actual.len()) {
+        let (e, a) = (expected.get(i), actual.get(i));
+        if e != a {
+            return Some(DumpDifference {
+                line: i + 1,
+                expected: e.cloned(),
+                actual: a.cloned(),
+            });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use highlighting::{Color, FontStyle};
+
+    fn line(number: usize, tokens: &[(usize, &str, &str)]) -> DumpedLine {
+        DumpedLine {
+            line: number,
+            tokens: tokens.iter().map(|&(start, text, scopes)| DumpedToken {
+                start,
+                end: start + text.len(),
+                text: text.to_owned(),
+                scopes: scopes.split_whitespace().map(|s| Scope::new(s).unwrap()).collect(),
+                style: None,
+            }).collect(),
+        }
+    }
+
+    #[test]
+    fn round_trips_json() {
+        let mut dump = vec![
+            line(1, &[(0, "x", "source.test variable"), (1, " = \"é\"\n", "source.test")]),
+            line(2, &[]),
+        ];
+        dump[0].tokens[0].style = Some(Style {
+            foreground: Color::BLACK,
+            background: Color::WHITE,
+            font_style: FontStyle::BOLD,
+        });
+
+        let json_lines = to_json_lines(&dump).unwrap();
+        assert_eq!(json_lines.lines().count(), 2);
+        assert!(json_lines.starts_with("{\"line\":1,\"tokens\":[{\"start\":0,\"end\":1,\"text\":\"x\",\
+                                        \"scopes\":[\"source.test\",\"variable\"],\"style\":"));
+        assert!(json_lines.ends_with("{\"line\":2,\"tokens\":[]}\n"));
+        assert_eq!(from_json_lines(&json_lines).unwrap(), dump);
+        assert_eq!(from_json(&to_json(&dump).unwrap()).unwrap(), dump);
+        assert!(from_json_lines("{\"line\":1}\n").is_err());
+    }
+
+    #[test]
+    fn finds_differences() {
+        let expected = vec![line(1, &[(0, "a", "source")]), line(2, &[(0, "b", "source")])];
+        let mut actual = expected.clone();
+        assert_eq!(first_difference(&expected, &actual), None);
+
+        actual[1].tokens[0].scopes.push(Scope::new("string").unwrap());
+        let difference = first_difference(&expected, &actual).unwrap();
+        assert_eq!(difference.line, 2);
+        assert_eq!(difference.expected.as_ref(), expected.get(1));
+
+        let difference = first_difference(&expected, &expected[..1]).unwrap();
+        assert_eq!((difference.line, difference.actual), (2, None));
+    }
+
+    #[cfg(all(feature = "assets", any(feature = "dump-load", feature = "dump-load-rs")))]
+    #[test]
+    fn dumps_tokens() {
+        use highlighting::ThemeSet;
+        let ss = SyntaxSet::load_defaults_newlines();
+        let ts = ThemeSet::load_defaults();
+        let syntax = ss.find_syntax_by_extension("rs").unwrap();
+        let source = "let x = 5;\n// é\n";
+
+        let dump = dump_tokens(source, &ss, syntax);
+        assert_eq!(dump.len(), 2);
+        let text: String = dump.iter().flat_map(|l| l.tokens.iter()).map(|t| &t.text[..]).collect();
+        assert_eq!(text, source);
+        let five = dump[0].tokens.iter().find(|t| t.text == "5").unwrap();
+        assert_eq!((five.start, five.end), (8, 9));
+        assert_eq!(five.scopes[0], Scope::new("source.rust").unwrap());
+        assert!(Scope::new("constant.numeric").unwrap().is_prefix_of(*five.scopes.last().unwrap()));
+        assert!(five.style.is_none());
+
+        let styled = dump_tokens_with_styles(source, &ss, syntax, &ts.themes["base16-ocean.dark"]);
+        let five = styled[0].tokens.iter().find(|t| t.text == "5").unwrap();
+        assert_eq!(five.style.unwrap().foreground, Color { r: 0xd0, g: 0x87, b: 0x70, a: 0xff });
+    }
+}
@@ -1,11 +1,22 @@
 //! Convenient helper functions for common use cases:
 //! printing to terminal, iterating lines with `\n`s, modifying ranges of highlighted output
-use highlighting::{Style, StyleModifier};
+use highlighting::{Color, Style, StyleModifier};
 use std::fmt::Write;
 use std::ops::Range;
 #[cfg(feature = "parsing")]
 use parsing::ScopeStackOp;
 
+/// Determines how background color attributes are generated by the `html` and `pango` modules
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IncludeBackground {
+    /// Don't include `background-color`, for performance or so that you can use your own background.
+    No,
+    /// Set background color attributes on every node
+    Yes,
+    /// Only set the `background-color` if it is different than the default (presumably set on a parent element)
+    IfDifferent(Color),
+}
+
 /// Formats the styled fragments using 24-bit color
 /// terminal escape codes. Meant for debugging and testing.
 /// It's currently fairly inefficient in its use of escape codes.
@@ -41,6 +52,7 @@ pub fn as_24_bit_terminal_escaped(v: &[(Style, &str)], bg: bool) -> String {
 
 /// Print out the various push and pop operations in a vector
 /// with visual alignment to the line. Obviously for debugging.
+/// For a dump of the tokens that tools can read, see the `token_dump` module.
 #[cfg(feature = "parsing")]
 pub fn debug_print_ops(line: &str, ops: &[(usize, ScopeStackOp)]) {
     for &(i, ref op) in ops.iter() {
@@ -154,6 +166,7 @@ pub fn split_at<'a, A: Clone>(v: &[(A, &'a str)], split_i: usize) -> (Vec<(A, &'
 }
 
 /// Modify part of a highlighted line using a style modifier, useful for highlighting sections of a line.
+/// To apply many ranges at once, like search matches and selections, use `highlighting::Overlay`.
 ///
 /// # Examples
 ///